@@ -0,0 +1,27 @@
+#![no_std]
+#![no_main]
+
+use core::fmt::Write;
+use raca_std::fs::{FileDescriptor, OpenMode};
+
+extern crate alloc;
+
+#[no_mangle]
+pub fn main() -> usize {
+    let mut fd = if FileDescriptor::stdout().is_open() {
+        FileDescriptor::stdout()
+    } else {
+        FileDescriptor::open("/dev/terminal", OpenMode::Write).unwrap()
+    };
+
+    match raca_std::kmsg::read_all() {
+        Ok(log) => {
+            write!(fd, "{}", log).unwrap();
+            0
+        }
+        Err(_) => {
+            writeln!(fd, "dmesg: cannot read /dev/kmsg").unwrap();
+            1
+        }
+    }
+}
@@ -0,0 +1,227 @@
+#![no_std]
+#![no_main]
+
+use alloc::string::String;
+use core::fmt::Write;
+use raca_std::{
+    disk::{GptDisk, GptEntry, Guid},
+    fs::{FileDescriptor, OpenMode},
+};
+
+extern crate alloc;
+
+const SECTOR_SIZE: u64 = 512;
+
+fn ask(fd: &mut FileDescriptor, question: &str) -> String {
+    let mut answer = String::new();
+    write!(fd, "{}", question).unwrap();
+    // the terminal echoes the answer as it is typed
+    fd.stdin_read_line(&mut answer);
+    String::from(answer.trim())
+}
+
+fn type_name(guid: Guid) -> &'static str {
+    match guid {
+        Guid::EFI_SYSTEM => "EFI System",
+        Guid::BASIC_DATA => "Basic data",
+        Guid::LINUX_FILESYSTEM => "Linux filesystem",
+        _ => "Unknown",
+    }
+}
+
+fn parse_type(s: &str) -> Option<Guid> {
+    match s {
+        "" | "data" => Some(Guid::BASIC_DATA),
+        "efi" => Some(Guid::EFI_SYSTEM),
+        "linux" => Some(Guid::LINUX_FILESYSTEM),
+        _ => Guid::parse(s),
+    }
+}
+
+/// Parse `+<n>[K|M|G]` into a number of sectors.
+fn parse_size(s: &str) -> Option<u64> {
+    let s = s.strip_prefix('+')?;
+    let (number, unit) = match s.char_indices().last()? {
+        (idx, 'K') | (idx, 'k') => (&s[..idx], 1024),
+        (idx, 'M') | (idx, 'm') => (&s[..idx], 1024 * 1024),
+        (idx, 'G') | (idx, 'g') => (&s[..idx], 1024 * 1024 * 1024),
+        _ => (s, SECTOR_SIZE),
+    };
+    let bytes = number.parse::<u64>().ok()? * unit;
+    Some(bytes.div_ceil(SECTOR_SIZE))
+}
+
+fn format_size(sectors: u64) -> String {
+    let bytes = sectors * SECTOR_SIZE;
+    let units = [("G", 1 << 30), ("M", 1 << 20), ("K", 1 << 10)];
+    for (unit, scale) in units {
+        if bytes >= scale {
+            return alloc::format!("{}{}", bytes / scale, unit);
+        }
+    }
+    alloc::format!("{}B", bytes)
+}
+
+fn print_table(fd: &mut FileDescriptor, disk: &GptDisk) {
+    let info = match disk.info() {
+        Ok(info) => info,
+        Err(_) => {
+            writeln!(fd, "No GPT on this disk, create one with 'g'.").unwrap();
+            return;
+        }
+    };
+    writeln!(
+        fd,
+        "Usable sectors: {} - {}",
+        info.first_usable_lba, info.last_usable_lba
+    )
+    .unwrap();
+
+    writeln!(fd, "Slot  First       Last        Size   Type              Name").unwrap();
+    for (slot, entry) in disk.entries().unwrap_or_default() {
+        writeln!(
+            fd,
+            "{:<5} {:<11} {:<11} {:<6} {:<17} {}",
+            slot,
+            entry.first_lba,
+            entry.last_lba,
+            format_size(entry.last_lba - entry.first_lba + 1),
+            type_name(entry.type_guid),
+            entry.name
+        )
+        .unwrap();
+    }
+}
+
+fn new_partition(fd: &mut FileDescriptor, disk: &GptDisk) {
+    let type_guid = match parse_type(&ask(fd, "Type (data, efi, linux or a GUID) [data]: ")) {
+        Some(type_guid) => type_guid,
+        None => {
+            writeln!(fd, "Invalid partition type.").unwrap();
+            return;
+        }
+    };
+    let name = ask(fd, "Name: ");
+
+    let size = ask(fd, "Size (+<n>[K|M|G]) [largest free space]: ");
+    let sectors = if size.is_empty() {
+        0
+    } else {
+        match parse_size(&size) {
+            Some(sectors) if sectors > 0 => sectors,
+            _ => {
+                writeln!(fd, "Invalid size.").unwrap();
+                return;
+            }
+        }
+    };
+
+    let (first_lba, last_lba) = match disk.find_free(sectors) {
+        Some(range) => range,
+        None => {
+            writeln!(fd, "Not enough free space.").unwrap();
+            return;
+        }
+    };
+
+    let entry = GptEntry {
+        type_guid,
+        unique_guid: Guid::NIL,
+        first_lba,
+        last_lba,
+        attributes: 0,
+        name,
+    };
+    match disk.add(&entry) {
+        Ok(slot) => writeln!(fd, "Created partition {}.", slot).unwrap(),
+        Err(_) => writeln!(fd, "Failed to create the partition.").unwrap(),
+    }
+}
+
+fn ask_slot(fd: &mut FileDescriptor, disk: &GptDisk) -> Option<(usize, GptEntry)> {
+    let slot = ask(fd, "Partition slot: ").parse::<usize>().ok();
+    let entry = slot.and_then(|slot| Some((slot, disk.entry(slot)?)));
+    if entry.is_none() {
+        writeln!(fd, "No such partition.").unwrap();
+    }
+    entry
+}
+
+fn delete_partition(fd: &mut FileDescriptor, disk: &GptDisk) {
+    if let Some((slot, _)) = ask_slot(fd, disk) {
+        match disk.delete(slot) {
+            Ok(_) => writeln!(fd, "Deleted partition {}.", slot).unwrap(),
+            Err(_) => writeln!(fd, "Failed to delete the partition.").unwrap(),
+        }
+    }
+}
+
+fn resize_partition(fd: &mut FileDescriptor, disk: &GptDisk) {
+    let (slot, entry) = match ask_slot(fd, disk) {
+        Some(slot) => slot,
+        None => return,
+    };
+
+    let sectors = match parse_size(&ask(fd, "New size (+<n>[K|M|G]): ")) {
+        Some(sectors) if sectors > 0 => sectors,
+        _ => {
+            writeln!(fd, "Invalid size.").unwrap();
+            return;
+        }
+    };
+
+    match disk.resize(slot, entry.first_lba + sectors - 1) {
+        Ok(_) => writeln!(fd, "Resized partition {}. The filesystem is unchanged.", slot).unwrap(),
+        Err(_) => writeln!(fd, "The new size doesn't fit.").unwrap(),
+    }
+}
+
+fn help(fd: &mut FileDescriptor) {
+    writeln!(fd, "  p   print the partition table").unwrap();
+    writeln!(fd, "  n   add a new partition").unwrap();
+    writeln!(fd, "  d   delete a partition").unwrap();
+    writeln!(fd, "  r   resize a partition").unwrap();
+    writeln!(fd, "  g   create a new empty GPT").unwrap();
+    writeln!(fd, "  q   quit").unwrap();
+    writeln!(fd, "Changes are written to the disk right away.").unwrap();
+}
+
+#[no_mangle]
+pub fn main() -> usize {
+    let mut fd = FileDescriptor::open("/dev/terminal", OpenMode::Write).unwrap();
+
+    let path = ask(&mut fd, "Disk (e.g. /dev/hdb): ");
+    let mut disk = match GptDisk::open(&path) {
+        Ok(disk) => disk,
+        Err(_) => {
+            writeln!(fd, "fdisk: cannot open {}", path).unwrap();
+            return 1;
+        }
+    };
+
+    help(&mut fd);
+
+    loop {
+        match ask(&mut fd, "Command (m for help): ").as_str() {
+            "p" => print_table(&mut fd, &disk),
+            "n" => new_partition(&mut fd, &disk),
+            "d" => delete_partition(&mut fd, &disk),
+            "r" => resize_partition(&mut fd, &disk),
+            "g" => {
+                if ask(&mut fd, "This erases all partitions. Continue? [y/N]: ") == "y" {
+                    match disk.create_table() {
+                        Ok(_) => writeln!(fd, "Created a new GPT.").unwrap(),
+                        Err(_) => writeln!(fd, "Failed to create a GPT.").unwrap(),
+                    }
+                }
+            }
+            "q" => break,
+            "m" | "" => help(&mut fd),
+            other => writeln!(fd, "Unknown command: {}", other).unwrap(),
+        }
+    }
+
+    disk.close();
+    fd.close();
+    0
+}
@@ -17,6 +17,12 @@ pub fn main() {
     let process = raca_std::task::Process::new(&buf, "shell", 0, 0);
     process.run();
 
+    // a second shell on the serial port, for headless sessions
+    if let Ok(serial) = FileDescriptor::open("/dev/ttyS0", raca_std::fs::OpenMode::Write) {
+        let process = raca_std::task::Process::new(&buf, "shell", serial.0, serial.0);
+        process.run();
+    }
+
     wait();
 
     let fd = FileDescriptor::open("/dev/terminal", raca_std::fs::OpenMode::Write).unwrap();
@@ -0,0 +1,55 @@
+#![no_std]
+#![no_main]
+
+use alloc::{format, string::String};
+use core::fmt::Write;
+use raca_std::{
+    fs::{FileDescriptor, OpenMode},
+    keymap,
+};
+
+extern crate alloc;
+
+fn ask(fd: &mut FileDescriptor, question: &str) -> String {
+    let mut answer = String::new();
+    write!(fd, "{}", question).unwrap();
+    fd.stdin_read_line(&mut answer);
+    String::from(answer.trim())
+}
+
+#[no_mangle]
+pub fn main() -> usize {
+    let mut fd = FileDescriptor::open("/dev/terminal", OpenMode::Write).unwrap();
+
+    let (Ok(current), Ok(scancode_set)) = (keymap::layout(), keymap::scancode_set()) else {
+        writeln!(fd, "loadkeys: cannot read the keyboard settings").unwrap();
+        return 1;
+    };
+
+    writeln!(fd, "Keyboard layouts:").unwrap();
+    for (name, description) in keymap::LAYOUTS {
+        let mark = if name == current { '*' } else { ' ' };
+        writeln!(fd, " {} {:<4}{}", mark, name, description).unwrap();
+    }
+
+    let layout = ask(&mut fd, "Layout (empty to keep): ");
+    if !layout.is_empty() && keymap::set_layout(&layout).is_err() {
+        writeln!(fd, "loadkeys: unknown layout {}", layout).unwrap();
+        return 1;
+    }
+
+    let question = format!("Scancode set [{}]: ", scancode_set);
+    match ask(&mut fd, &question).as_str() {
+        "" => {}
+        set => match set.parse::<usize>().map(keymap::set_scancode_set) {
+            Ok(Ok(_)) => {}
+            _ => {
+                writeln!(fd, "loadkeys: scancode set has to be 1 or 2").unwrap();
+                return 1;
+            }
+        },
+    }
+
+    fd.close();
+    0
+}
@@ -0,0 +1,18 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+use raca_std::{fs::FileDescriptor, signal};
+
+use crate::run;
+
+pub fn fg(stdio: &mut FileDescriptor, _args: Vec<String>) {
+    let Some(pid) = run::take_stopped_job() else {
+        writeln!(stdio, "fg: no current job").unwrap();
+        return;
+    };
+
+    if signal::kill(pid, signal::SIGCONT).is_err() {
+        writeln!(stdio, "fg: ({}) - No such process", pid).unwrap();
+        return;
+    }
+    run::wait_foreground(stdio, pid);
+}
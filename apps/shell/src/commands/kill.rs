@@ -0,0 +1,41 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+use raca_std::{fs::FileDescriptor, signal};
+
+pub fn kill(stdio: &mut FileDescriptor, args: Vec<String>) {
+    let (sig, pids) = match args.get(1) {
+        Some(flag) if flag == "-l" => {
+            for sig in 1..signal::NSIG {
+                if let Some(name) = signal::name(sig) {
+                    write!(stdio, "{}) SIG{} ", sig, name).unwrap();
+                }
+            }
+            writeln!(stdio).unwrap();
+            return;
+        }
+        Some(flag) if flag.starts_with('-') => match signal::from_name(&flag[1..]) {
+            Some(sig) => (sig, &args[2..]),
+            None => {
+                writeln!(stdio, "kill: unknown signal {}", &flag[1..]).unwrap();
+                return;
+            }
+        },
+        _ => (signal::SIGTERM, &args[1..]),
+    };
+
+    if pids.is_empty() {
+        writeln!(stdio, "Usage: kill [-l] [-SIGNAL] PID...").unwrap();
+        return;
+    }
+
+    for pid in pids {
+        match pid.parse::<usize>() {
+            Ok(id) => {
+                if signal::kill(id, sig).is_err() {
+                    writeln!(stdio, "kill: ({}) - No such process", pid).unwrap();
+                }
+            }
+            Err(_) => writeln!(stdio, "kill: {}: not a process id", pid).unwrap(),
+        }
+    }
+}
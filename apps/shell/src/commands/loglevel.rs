@@ -0,0 +1,21 @@
+use alloc::{string::String, vec::Vec};
+use raca_std::{fs::FileDescriptor, kmsg::LogLevel};
+use core::fmt::Write;
+
+pub fn loglevel(stdio: &mut FileDescriptor, args: Vec<String>) {
+    match args.len() {
+        1 => match raca_std::kmsg::level() {
+            Ok(level) => writeln!(stdio, "{}", level.name()).unwrap(),
+            Err(_) => writeln!(stdio, "loglevel: cannot read the kernel log level").unwrap(),
+        },
+        2 => match LogLevel::from_name(args[1].as_str()) {
+            Some(level) => {
+                if raca_std::kmsg::set_level(level).is_err() {
+                    writeln!(stdio, "loglevel: cannot set the kernel log level").unwrap();
+                }
+            }
+            None => writeln!(stdio, "loglevel: unknown level {}", args[1]).unwrap(),
+        },
+        _ => writeln!(stdio, "Usage: loglevel [off|error|warn|info|debug|trace]").unwrap(),
+    }
+}
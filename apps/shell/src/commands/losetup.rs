@@ -0,0 +1,27 @@
+use alloc::{string::String, vec::Vec};
+use raca_std::fs::FileDescriptor;
+use core::fmt::Write;
+
+pub fn losetup(stdio: &mut FileDescriptor, args: Vec<String>) {
+    if args.len() == 3 && args[1] == "-d" {
+        let detached = args[2]
+            .trim_start_matches("/dev/loop")
+            .parse::<usize>()
+            .ok()
+            .and_then(|id| raca_std::fs::loop_detach(id).ok());
+        if detached.is_none() {
+            writeln!(stdio, "losetup: cannot detach {}\n", args[2]).unwrap();
+        }
+        return;
+    }
+
+    if args.len() != 2 {
+        writeln!(stdio, "Usage: losetup <file> | losetup -d <loop device>\n").unwrap();
+        return;
+    }
+
+    match raca_std::fs::loop_attach(args[1].as_str()) {
+        Ok(id) => writeln!(stdio, "/dev/loop{}", id).unwrap(),
+        Err(_) => writeln!(stdio, "losetup: cannot attach {}\n", args[1]).unwrap(),
+    }
+}
@@ -24,6 +24,8 @@ pub fn ls(stdin: &mut FileDescriptor,args: Vec<String>) {
         match info.ty {
             FileType::Dir => write!(stdin, "\x1b[42m{}\x1b[0m ",info.name).unwrap(),
             FileType::File => write!(stdin, "\x1b[32m{}\x1b[0m ",info.name).unwrap(),
+            FileType::Fifo => write!(stdin, "\x1b[33m{}\x1b[0m ",info.name).unwrap(),
+            FileType::Socket => write!(stdin, "\x1b[35m{}\x1b[0m ",info.name).unwrap(),
         }
     }
     writeln!(stdin).unwrap();
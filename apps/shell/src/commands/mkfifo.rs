@@ -0,0 +1,14 @@
+use alloc::{string::String, vec::Vec};
+use raca_std::fs::FileDescriptor;
+use core::fmt::Write;
+
+pub fn mkfifo(stdio: &mut FileDescriptor, args: Vec<String>) {
+    if args.len() != 2 {
+        writeln!(stdio, "Usage: mkfifo <path>\n").unwrap();
+        return;
+    }
+
+    if raca_std::fs::mkfifo(args[1].as_str()).is_err() {
+        writeln!(stdio, "mkfifo: cannot create {}\n", args[1]).unwrap();
+    }
+}
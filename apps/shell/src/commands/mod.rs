@@ -2,14 +2,26 @@ mod cat;
 mod cd;
 mod echo;
 mod exit;
+mod fg;
+mod kill;
+mod loglevel;
+mod losetup;
 mod ls;
+mod mkfifo;
 mod mount;
+mod stty;
 mod write;
 
 pub use cat::*;
 pub use cd::*;
 pub use echo::*;
 pub use exit::*;
+pub use fg::*;
+pub use kill::*;
+pub use loglevel::*;
+pub use losetup::*;
 pub use ls::*;
+pub use mkfifo::*;
 pub use mount::*;
+pub use stty::*;
 pub use write::*;
@@ -1,18 +1,34 @@
 use alloc::{string::String, vec::Vec};
-use raca_std::fs::FileDescriptor;
+use raca_std::fs::{FileDescriptor, FileSystemType};
 use core::fmt::Write;
 
 pub fn mount(stdio: &mut FileDescriptor, args: Vec<String>) {
+    let (fs_type, args) = if args.len() == 5 && args[1] == "-t" {
+        match FileSystemType::from_name(args[2].as_str()) {
+            Some(fs_type) => (fs_type, [&args[..1], &args[3..]].concat()),
+            None => {
+                writeln!(stdio, "mount: unknown filesystem type {}\n", args[2]).unwrap();
+                return;
+            }
+        }
+    } else {
+        (FileSystemType::Auto, args)
+    };
+
     if args.len() != 3 {
-        writeln!(stdio, "Usage: mount <path> <partition>\n").unwrap();
+        writeln!(
+            stdio,
+            "Usage: mount [-t fat32|iso9660] <partition|UUID=uuid|LABEL=label> <path>\n"
+        )
+        .unwrap();
         return;
     }
 
     let path = args[2].clone();
     let partition = args[1].clone();
 
-    raca_std::fs::mount(path.clone(), partition.clone()).unwrap_or_else(|_| {
-        writeln!(stdio, "Failed to mount {} to {}\n", path, partition).unwrap();
+    raca_std::fs::mount_with_type(path.clone(), partition.clone(), fs_type).unwrap_or_else(|_| {
+        writeln!(stdio, "Failed to mount {} to {}\n", partition, path).unwrap();
     });
 }
 
@@ -0,0 +1,84 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+use raca_std::{fs::FileDescriptor, tty};
+
+/// Local mode flags `stty` knows by name
+const FLAGS: [(&str, u32); 5] = [
+    ("isig", tty::ISIG),
+    ("icanon", tty::ICANON),
+    ("echo", tty::ECHO),
+    ("echoe", tty::ECHOE),
+    ("echoctl", tty::ECHOCTL),
+];
+
+fn show(stdio: &mut FileDescriptor, termios: &tty::Termios) {
+    if let Ok(size) = tty::window_size(*stdio) {
+        writeln!(stdio, "rows {}; columns {};", size.rows, size.cols).unwrap();
+    }
+    for (name, flag) in FLAGS {
+        let prefix = if termios.lflag & flag != 0 { "" } else { "-" };
+        write!(stdio, "{}{} ", prefix, name).unwrap();
+    }
+    writeln!(stdio).unwrap();
+}
+
+pub fn stty(stdio: &mut FileDescriptor, args: Vec<String>) {
+    let Ok(mut termios) = tty::get_attr(*stdio) else {
+        writeln!(stdio, "stty: not a terminal").unwrap();
+        return;
+    };
+
+    if args.len() < 2 {
+        show(stdio, &termios);
+        return;
+    }
+
+    let mut size = tty::window_size(*stdio).unwrap_or_default();
+    let mut resized = false;
+    let mut args = args[1..].iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            // left by doubled spaces on the command line
+            "" => {}
+            "size" => {
+                writeln!(stdio, "{} {}", size.rows, size.cols).unwrap();
+            }
+            "raw" | "-cooked" => termios.make_raw(),
+            "-raw" | "cooked" | "sane" => termios.make_cooked(),
+            "rows" | "cols" | "columns" => {
+                let Some(Ok(value)) = args.next().map(|value| value.parse::<u16>()) else {
+                    writeln!(stdio, "stty: {} needs a number", arg).unwrap();
+                    return;
+                };
+                if arg == "rows" {
+                    size.rows = value;
+                } else {
+                    size.cols = value;
+                }
+                resized = true;
+            }
+            flag => {
+                let (name, on) = match flag.strip_prefix('-') {
+                    Some(name) => (name, false),
+                    None => (flag, true),
+                };
+                let Some((_, bits)) = FLAGS.iter().find(|(known, _)| *known == name) else {
+                    writeln!(stdio, "stty: invalid argument '{}'", flag).unwrap();
+                    return;
+                };
+                if on {
+                    termios.lflag |= bits;
+                } else {
+                    termios.lflag &= !bits;
+                }
+            }
+        }
+    }
+
+    if tty::set_attr(*stdio, &termios).is_err() {
+        writeln!(stdio, "stty: can't change the terminal settings").unwrap();
+    }
+    if resized && tty::set_window_size(*stdio, &size).is_err() {
+        writeln!(stdio, "stty: can't change the terminal size").unwrap();
+    }
+}
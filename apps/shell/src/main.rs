@@ -13,22 +13,10 @@ mod commands;
 mod run;
 
 fn shell_read_line(fd: &mut FileDescriptor, buf: &mut String) {
-    buf.clear(); // make sure that the buf is clean
-
-    let mut tmp_buf = [0; 1];
-    fd.read_exact(&mut tmp_buf);
-
-    while tmp_buf[0] != b'\n' {
-        if tmp_buf[0] == 8 {
-            // backspace
-            if let Some(_) = buf.pop() {
-                write!(fd, "{} {}", 8 as char, 8 as char).unwrap();
-            }
-        } else {
-            write!(fd, "{}", tmp_buf[0] as char).unwrap();
-            buf.push(tmp_buf[0] as char);
-        }
-        fd.read_exact(&mut tmp_buf);
+    // the terminal echoes and edits the line, Ctrl+D on an empty one gives
+    // end of file, which is taken as an empty command
+    if fd.stdin_read_line(buf) == 0 {
+        writeln!(fd).unwrap();
     }
 }
 
@@ -48,13 +36,24 @@ pub fn main() -> usize {
         command_function_list.insert("cd", cd);
         command_function_list.insert("echo", echo);
         command_function_list.insert("exit", exit);
+        command_function_list.insert("fg", fg);
+        command_function_list.insert("kill", kill);
+        command_function_list.insert("loglevel", loglevel);
+        command_function_list.insert("losetup", losetup);
         command_function_list.insert("ls", ls);
+        command_function_list.insert("mkfifo", mkfifo);
         command_function_list.insert("mount", mount);
+        command_function_list.insert("stty", stty);
         command_function_list.insert("write", write);
     }
 
 
-    let mut fd = FileDescriptor::open("/dev/terminal", raca_std::fs::OpenMode::Write).unwrap();
+    // a shell started on another console, like the serial port, gets it as stdout
+    let mut fd = if FileDescriptor::stdout().is_open() {
+        FileDescriptor::stdout()
+    } else {
+        FileDescriptor::open("/dev/terminal", raca_std::fs::OpenMode::Write).unwrap()
+    };
     writeln!(fd, "\n\x1b[34mRACA-Shell \x1b[31mv0.1.0").unwrap();
     writeln!(
         fd,
@@ -68,7 +67,6 @@ pub fn main() -> usize {
 
     loop {
         shell_read_line(&mut fd, &mut input_buf);
-        writeln!(fd).unwrap();
 
         let input =
             String::from_utf8(escape_bytes::unescape(input_buf.as_bytes()).unwrap()).unwrap();
@@ -79,7 +77,7 @@ pub fn main() -> usize {
 
         if let Some(function) = function {
             function(&mut fd, args);
-        } else if let None = run::try_run(args[0].clone()) {
+        } else if let None = run::try_run(&mut fd, args[0].clone(), &input) {
             writeln!(fd, "rash: command not found: \x1b[31m{}\x1b[0m",args[0]).unwrap();
         }
 
@@ -1,7 +1,18 @@
 use alloc::{string::String, vec};
-use raca_std::{fs::{FileDescriptor, FileType, OpenMode}, task::{wait, Process}};
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+use raca_std::{
+    fs::{FileDescriptor, FileType, OpenMode},
+    task::{wait_status, Process, WaitStatus},
+    tty,
+};
 
-pub fn try_run(path: String) -> Option<()> {
+/// The last job stopped with Ctrl+Z, 0 if there is none
+static STOPPED_JOB: AtomicUsize = AtomicUsize::new(0);
+
+pub fn try_run(fd: &mut FileDescriptor, path: String, cmdline: &str) -> Option<()> {
     if let Ok(mut file) = FileDescriptor::open(&path, OpenMode::Read) {
         if file.get_type() == FileType::Dir {
             return None;
@@ -13,18 +24,41 @@ pub fn try_run(path: String) -> Option<()> {
         //let (pipe1_read,pipe1_write) = FileDescriptor::open_pipe().unwrap();
         //let (pipe2_read,pipe2_write) = FileDescriptor::open_pipe().unwrap();
 
-        let process = Process::new(&buf, "temp", 0, 0);
-        process.run();
+        let name = path.rsplit('/').next().unwrap_or(&path);
+        let mut process = Process::new(&buf, name, 0, 0);
+        process.set_cmdline(cmdline);
+        let pid = process.run();
         //loop {
         //    let mut buf = [0;1];
         //    pipe2_read.read(&mut buf);
         //    write!(fd, "{}", buf[0] as char).unwrap();
         //}
         //loop{}
-        wait();
+        wait_foreground(fd, pid);
         // loop{}
         Some(())
     }else {
         None
     }
-}
\ No newline at end of file
+}
+
+/// Give the terminal to `pid` until it exits or is stopped.
+pub fn wait_foreground(fd: &mut FileDescriptor, pid: usize) {
+    // not every stdout is a terminal, there is nothing to hand over then
+    let _ = tty::set_foreground(*fd, Some(pid));
+    let status = wait_status();
+    let _ = tty::set_foreground(*fd, None);
+
+    if let WaitStatus::Stopped(_) = status {
+        STOPPED_JOB.store(pid, Ordering::SeqCst);
+        writeln!(fd, "[{}] Stopped", pid).unwrap();
+    }
+}
+
+/// Take the job stopped last, if there is one.
+pub fn take_stopped_job() -> Option<usize> {
+    match STOPPED_JOB.swap(0, Ordering::SeqCst) {
+        0 => None,
+        pid => Some(pid),
+    }
+}
@@ -28,27 +28,34 @@ struct Args {
     #[argh(switch, short = 's')]
     #[argh(description = "redirect serial to stdio")]
     serial: bool,
+
+    #[argh(switch, short = 'v')]
+    #[argh(description = "attach the data disks as virtio-blk-pci")]
+    virtio: bool,
+
+    #[argh(option)]
+    #[argh(description = "attach an ISO image as an AHCI CD-ROM drive")]
+    cdrom: Option<String>,
 }
 
 fn main() {
-    let init_path = env!("CARGO_BIN_FILE_INIT_init");
-    let shell_path = env!("CARGO_BIN_FILE_SHELL_shell");
+    let apps = [
+        (env!("CARGO_BIN_FILE_INIT_init"), "init.rae"),
+        (env!("CARGO_BIN_FILE_SHELL_shell"), "shell.rae"),
+        (env!("CARGO_BIN_FILE_FDISK_fdisk"), "fdisk.rae"),
+        (env!("CARGO_BIN_FILE_DMESG_dmesg"), "dmesg.rae"),
+        (env!("CARGO_BIN_FILE_LOADKEYS_loadkeys"), "loadkeys.rae"),
+    ];
 
     let app_path = "esp/RACA/app64/".to_string();
 
-    let init_dest = app_path.clone() + "init.rae";
-    let shell_dest = app_path.clone() + "shell.rae";
-
-    io::copy(
-        &mut File::open(Path::new(init_path)).unwrap(),
-        &mut File::create(init_dest).unwrap(),
-    )
-    .unwrap();
-    io::copy(
-        &mut File::open(Path::new(shell_path)).unwrap(),
-        &mut File::create(shell_dest).unwrap(),
-    )
-    .unwrap();
+    for (app_bin_path, app_name) in apps {
+        io::copy(
+            &mut File::open(Path::new(app_bin_path)).unwrap(),
+            &mut File::create(app_path.clone() + app_name).unwrap(),
+        )
+        .unwrap();
+    }
 
     let raca_core_path = env!("CARGO_BIN_FILE_RACA_CORE_raca_core");
     println!(
@@ -107,10 +114,21 @@ fn main() {
         cmd.arg("-device").arg("qemu-xhci,id=xhci");
         cmd.arg("-drive")
             .arg("format=raw,file=disk.img,if=none,id=disk1");
-        cmd.arg("-device").arg("ide-hd,drive=disk1,bus=ahci.2");
         cmd.arg("-drive")
             .arg("format=raw,file=data.img,if=none,id=disk2");
-        cmd.arg("-device").arg("nvme,drive=disk2,serial=1234");
+        if args.virtio {
+            cmd.arg("-device").arg("virtio-blk-pci,drive=disk1,disable-legacy=on,discard=on");
+            cmd.arg("-device").arg("virtio-blk-pci,drive=disk2,disable-legacy=on,discard=on");
+        } else {
+            cmd.arg("-device").arg("ide-hd,drive=disk1,bus=ahci.2");
+            cmd.arg("-device").arg("nvme,drive=disk2,serial=1234");
+        }
+
+        if let Some(cdrom) = args.cdrom {
+            cmd.arg("-drive")
+                .arg(format!("format=raw,file={},if=none,media=cdrom,id=cdrom", cdrom));
+            cmd.arg("-device").arg("ide-cd,drive=cdrom,bus=ahci.1");
+        }
 
         if args.kvm {
             cmd.arg("--enable-kvm");
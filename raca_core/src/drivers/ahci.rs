@@ -6,7 +6,7 @@
 
 // 来自rCore的AHCI驱动 见https://gitee.com/rcore-os/isomorphic_drivers/
 
-use alloc::{collections::BTreeMap, vec::Vec};
+use alloc::{collections::BTreeMap, format, string::String, vec::Vec};
 use framework::{
     drivers::{
         alloc_for_dma, dealloc_for_dma,
@@ -34,6 +34,21 @@ pub struct AHCI {
     cmd_table: &'static mut AHCICommandTable,
     data: &'static mut [u8],
     port: Vec<&'static mut AHCIPort>,
+    device_types: Vec<AHCIDeviceType>,
+    /// Number of the HBA port each device is attached to
+    port_numbers: Vec<usize>,
+    /// Size in bytes of each device, read when it was probed. `None` for an
+    /// optical drive that had no medium then.
+    sizes: Vec<Option<usize>>,
+}
+
+/// Kind of device attached to an AHCI port
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum AHCIDeviceType {
+    /// SATA hard disk, driven with ATA commands
+    Ata,
+    /// SATA optical drive, driven with ATAPI PACKET commands
+    Atapi,
 }
 
 /// AHCI Generic Host Control (3.1)
@@ -259,6 +274,14 @@ const FIS_REG_H2D: u8 = 0x27;
 const CMD_READ_DMA_EXT: u8 = 0x25;
 const CMD_WRITE_DMA_EXT: u8 = 0x35;
 const CMD_IDENTIFY_DEVICE: u8 = 0xec;
+const CMD_PACKET: u8 = 0xa0;
+
+const ATAPI_CMD_TEST_UNIT_READY: u8 = 0x00;
+const ATAPI_CMD_READ_CAPACITY: u8 = 0x25;
+const ATAPI_CMD_READ_10: u8 = 0x28;
+
+/// Port signature of an ATAPI device (3.3.9)
+const SATA_SIG_ATAPI: u32 = 0xeb140101;
 
 /// SATA Register FIS - Host to Device
 ///
@@ -332,7 +355,7 @@ impl AHCI {
         let cmd_list = addr_to_array(cmd_list_va, 4096 / size_of::<AHCICommandHeader>());
         let cmd_table = addr_to_mut_ref(cmd_table_va);
         //let identify_data = unsafe { &*(data_va as *mut ATAIdentifyPacket) };
-        let data = addr_to_array(data_va, BLOCK_SIZE);
+        let data = addr_to_array(data_va, ATAPI_BLOCK_SIZE);
         //log::info!("Read for AHCI");
         //let np = ghc.num_ports();
         let mut ahci = AHCI {
@@ -342,6 +365,9 @@ impl AHCI {
             cmd_table,
             data,
             port: Vec::new(),
+            device_types: Vec::new(),
+            port_numbers: Vec::new(),
+            sizes: Vec::new(),
         };
         ahci.ghc.enable();
 
@@ -434,6 +460,19 @@ impl AHCI {
                 return None;
             }
 
+            if port.signature.read() == SATA_SIG_ATAPI {
+                let port = addr_to_mut_ref::<AHCIPort>(VirtAddr::from_ptr(ahci.ghc.port_ptr(i)));
+                ahci.port.push(port);
+                ahci.device_types.push(AHCIDeviceType::Atapi);
+                ahci.port_numbers.push(i);
+
+                // The first command after power up usually reports UNIT ATTENTION,
+                // so the result is ignored here.
+                let hd = ahci.port.len() - 1;
+                let _ = ahci.send_packet(hd, &[ATAPI_CMD_TEST_UNIT_READY], 0, false);
+                continue;
+            }
+
             let fis = &mut ahci.cmd_table.cfis;
             // Register FIS from HBA to device
             fis.fis_type = FIS_REG_H2D;
@@ -447,10 +486,114 @@ impl AHCI {
             port.spin_on_slot(0);
             let port = addr_to_mut_ref::<AHCIPort>(VirtAddr::from_ptr(ahci.ghc.port_ptr(i)));
             ahci.port.push(port);
+            ahci.device_types.push(AHCIDeviceType::Ata);
+            ahci.port_numbers.push(i);
+        }
+
+        for hd in 0..ahci.port.len() {
+            let size = ahci.probe_size(hd);
+            ahci.sizes.push(size);
         }
         Some(ahci)
     }
 
+    /// Ask the device for its size in bytes.
+    fn probe_size(&mut self, hd: usize) -> Option<usize> {
+        match self.device_types[hd] {
+            AHCIDeviceType::Ata => Some(self.get_hd_size(hd)),
+            AHCIDeviceType::Atapi => {
+                let (blocks, block_size) = self.read_capacity(hd)?;
+                Some(blocks as usize * block_size)
+            }
+        }
+    }
+
+    /// Issue an ATAPI PACKET command carrying the SCSI command `packet`.
+    ///
+    /// `len` is the number of bytes transferred through the data buffer,
+    /// `write` gives the direction of the transfer.
+    fn send_packet(&mut self, hd: usize, packet: &[u8], len: usize, write: bool) -> Option<()> {
+        assert!(packet.len() <= 16 && len <= ATAPI_BLOCK_SIZE);
+
+        // cfl=5
+        self.cmd_list[0].flags = 5 | CommandHeaderFlags::ATAPI.bits();
+        if write {
+            self.cmd_list[0].flags |= CommandHeaderFlags::WRITE.bits();
+        }
+        self.cmd_list[0].prdt_length = if len > 0 { 1 } else { 0 };
+        self.cmd_table.prdt[0].byte_count_i = len.max(1) as u32 - 1;
+
+        self.cmd_table.acmd = [0; 16];
+        self.cmd_table.acmd[..packet.len()].copy_from_slice(packet);
+
+        let fis = &mut self.cmd_table.cfis;
+        // Register FIS from HBA to device
+        fis.fis_type = FIS_REG_H2D;
+        fis.cflags = 1 << 7;
+        // 7.18 PACKET - A0h
+        fis.command = CMD_PACKET;
+        // DMA
+        fis.feature_lo = if len > 0 { 1 } else { 0 };
+        fis.set_lba(0);
+        // byte count limit
+        fis.lba_1 = len as u8;
+        fis.lba_2 = (len >> 8) as u8;
+        fis.sector_count = 0;
+        fis.dev_head = 0;
+        fis.control = 0;
+
+        self.port[hd].issue_command(0);
+        self.port[hd].spin_on_slot(0);
+
+        // restore the slot layout used by the ATA commands
+        self.cmd_list[0].prdt_length = 1;
+        self.cmd_table.prdt[0].byte_count_i = (BLOCK_SIZE - 1) as u32;
+        self.cmd_table.cfis.feature_lo = 0;
+
+        // TFD.STS.ERR
+        if self.port[hd].task_file_data.read().get_bit(0) {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Returns the number of blocks and the block size of an ATAPI device.
+    pub fn read_capacity(&mut self, hd: usize) -> Option<(u64, usize)> {
+        // SCSI READ CAPACITY (10)
+        let packet = [ATAPI_CMD_READ_CAPACITY, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        self.send_packet(hd, &packet, 8, false)?;
+
+        let last_lba = u32::from_be_bytes(self.data[0..4].try_into().unwrap()) as u64;
+        let block_size = u32::from_be_bytes(self.data[4..8].try_into().unwrap()) as usize;
+        Some((last_lba + 1, block_size))
+    }
+
+    /// Read one 2048-byte block from an ATAPI device.
+    pub fn read_atapi_block(&mut self, hd: usize, lba: u64, buf: &mut [u8]) -> Option<()> {
+        let lba = (lba as u32).to_be_bytes();
+        // SCSI READ (10), one block
+        let packet = [
+            ATAPI_CMD_READ_10,
+            0,
+            lba[0],
+            lba[1],
+            lba[2],
+            lba[3],
+            0,
+            0,
+            1,
+            0,
+            0,
+            0,
+        ];
+        self.send_packet(hd, &packet, ATAPI_BLOCK_SIZE, false)?;
+
+        let len = buf.len().min(ATAPI_BLOCK_SIZE);
+        buf[..len].clone_from_slice(&self.data[0..len]);
+        Some(())
+    }
+
     pub fn get_hd_size(&mut self, hd: usize) -> usize {
         self.cmd_list[0].flags = 4;
 
@@ -528,6 +671,7 @@ impl Drop for AHCI {
 }
 
 pub const BLOCK_SIZE: usize = 512;
+pub const ATAPI_BLOCK_SIZE: usize = 2048;
 
 /*fn from_ata_string(data: &[u8]) -> String {
     let mut swapped_data = Vec::new();
@@ -598,17 +742,51 @@ fn find_hd(hd_id: usize) -> Option<usize> {
     }
 }
 
+/// Read 512-byte sectors starting at `start_sec`.
+///
+/// ATAPI devices use 2048-byte blocks, so the sectors are taken out of the
+/// blocks that contain them.
 pub fn read_block(hd: usize, start_sec: u64, buf: &mut [u8]) -> Option<()> {
     assert!(buf.len() % 512 == 0);
     let port_id = find_hd(hd)?;
-    AHCI_CONS.lock()[port_id].read_block(hd, start_sec, buf.len() / 512, buf);
+    let mut ahci_cons = AHCI_CONS.lock();
+    let ahci = &mut ahci_cons[port_id];
+
+    match ahci.device_types[hd] {
+        AHCIDeviceType::Ata => ahci.read_block(hd, start_sec, buf.len() / 512, buf),
+        AHCIDeviceType::Atapi => {
+            let sectors_per_block = ATAPI_BLOCK_SIZE / 512;
+            let sectors = buf.len() / 512;
+            let mut block = [0u8; ATAPI_BLOCK_SIZE];
+
+            // every block is read once, however many of its sectors are wanted
+            let mut done = 0;
+            while done < sectors {
+                let sec = start_sec + done as u64;
+                ahci.read_atapi_block(hd, sec / sectors_per_block as u64, &mut block)?;
+
+                let first = (sec % sectors_per_block as u64) as usize;
+                let count = (sectors_per_block - first).min(sectors - done);
+                buf[done * 512..(done + count) * 512]
+                    .copy_from_slice(&block[first * 512..(first + count) * 512]);
+                done += count;
+            }
+        }
+    }
     Some(())
 }
 
 pub fn write_block(hd: usize, start_sec: u64, buf: &[u8]) -> Option<()> {
     assert!(buf.len() % 512 == 0);
     let port_id = find_hd(hd)?;
-    AHCI_CONS.lock()[port_id].write_block(hd, start_sec, buf.len() / 512, buf);
+    let mut ahci_cons = AHCI_CONS.lock();
+    let ahci = &mut ahci_cons[port_id];
+
+    match ahci.device_types[hd] {
+        AHCIDeviceType::Ata => ahci.write_block(hd, start_sec, buf.len() / 512, buf),
+        // optical media are read-only
+        AHCIDeviceType::Atapi => return None,
+    }
     Some(())
 }
 
@@ -616,7 +794,29 @@ pub fn get_hd_num() -> usize {
     DISK_TO_CON.lock().len()
 }
 
+/// Size of the device in bytes, as found when it was probed.
 pub fn get_hd_size(hd: usize) -> Option<usize> {
     let port_id = find_hd(hd)?;
-    Some(AHCI_CONS.lock()[port_id].get_hd_size(hd))
+    let mut ahci_cons = AHCI_CONS.lock();
+    let ahci = &mut ahci_cons[port_id];
+
+    if let Some(size) = ahci.sizes[hd] {
+        return Some(size);
+    }
+    // a drive that was empty at boot may have a medium by now
+    let size = ahci.probe_size(hd)?;
+    ahci.sizes[hd] = Some(size);
+    Some(size)
+}
+
+pub fn get_hd_type(hd: usize) -> Option<AHCIDeviceType> {
+    let port_id = find_hd(hd)?;
+    Some(AHCI_CONS.lock()[port_id].device_types[hd])
+}
+
+/// Name of the controller and port the device is attached to.
+pub fn get_hd_path(hd: usize) -> Option<String> {
+    let con = find_hd(hd)?;
+    let port = AHCI_CONS.lock()[con].port_numbers[hd];
+    Some(format!("ahci{}-port{}", con, port))
 }
@@ -1,11 +1,44 @@
-use alloc::{sync::Arc, vec::Vec};
+use alloc::{string::String, sync::Arc, vec::Vec};
 use spin::Mutex;
 
+use super::ahci::AHCIDeviceType;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum BlockDeviceType {
+    HardDisk,
+    CdRom,
+    RamDisk,
+}
+
 pub trait BlockDevice: Send + Sync + 'static{
     fn read_block(&self, start_sec: usize, buf: &mut [u8]) -> Option<()>;
     fn write_block(&self, start_sec: usize, buf: &[u8]) -> Option<()>;
     
     fn get_size(&self) -> usize;
+
+    /// Where the device is attached, e.g. `ahci0-port1`. Unlike the position
+    /// in `HD_LIST` this doesn't change when other devices come and go.
+    fn bus_path(&self) -> String;
+
+    /// Size of the blocks the device is addressed in. `read_block` and
+    /// `write_block` always take 512-byte sectors.
+    fn block_size(&self) -> usize {
+        512
+    }
+
+    fn device_type(&self) -> BlockDeviceType {
+        BlockDeviceType::HardDisk
+    }
+
+    /// Write back the volatile write cache of the device.
+    fn flush(&self) -> Option<()> {
+        Some(())
+    }
+
+    /// Tell the device that `count` sectors starting at `start_sec` are unused.
+    fn discard(&self, _start_sec: usize, _count: usize) -> Option<()> {
+        None
+    }
 }
 
 struct AHCIDisk {
@@ -25,6 +58,24 @@ impl BlockDevice for AHCIDisk {
     fn get_size(&self) -> usize {
         super::ahci::get_hd_size(self.num).unwrap()
     }
+
+    fn bus_path(&self) -> String {
+        super::ahci::get_hd_path(self.num).unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        match self.device_type() {
+            BlockDeviceType::CdRom => super::ahci::ATAPI_BLOCK_SIZE,
+            _ => super::ahci::BLOCK_SIZE,
+        }
+    }
+
+    fn device_type(&self) -> BlockDeviceType {
+        match super::ahci::get_hd_type(self.num).unwrap() {
+            AHCIDeviceType::Ata => BlockDeviceType::HardDisk,
+            AHCIDeviceType::Atapi => BlockDeviceType::CdRom,
+        }
+    }
 }
 
 struct NVMeDisk {
@@ -33,22 +84,97 @@ struct NVMeDisk {
 
 impl BlockDevice for NVMeDisk {
     fn read_block(&self, start_sec: usize, buf: &mut [u8]) -> Option<()> {
-        framework::drivers::nvme::read_block(self.num, start_sec as u64, buf);
-        Some(())
+        super::nvme::read_block(self.num, start_sec as u64, buf)
     }
 
     fn write_block(&self, start_sec: usize, buf: &[u8]) -> Option<()> {
-        framework::drivers::nvme::write_block(self.num, start_sec as u64, buf);
-        Some(())
+        super::nvme::write_block(self.num, start_sec as u64, buf)
     }
 
     fn get_size(&self) -> usize {
-        framework::drivers::nvme::get_hd_size(self.num).unwrap()
+        super::nvme::get_hd_size(self.num).unwrap()
+    }
+
+    fn bus_path(&self) -> String {
+        super::nvme::get_hd_path(self.num).unwrap()
+    }
+
+    fn block_size(&self) -> usize {
+        super::nvme::get_hd_block_size(self.num).unwrap()
+    }
+
+    fn flush(&self) -> Option<()> {
+        super::nvme::flush(self.num)
+    }
+}
+
+struct VirtioDisk {
+    num: usize,
+}
+
+impl BlockDevice for VirtioDisk {
+    fn read_block(&self, start_sec: usize, buf: &mut [u8]) -> Option<()> {
+        super::virtio_blk::read_block(self.num, start_sec as u64, buf)
+    }
+
+    fn write_block(&self, start_sec: usize, buf: &[u8]) -> Option<()> {
+        super::virtio_blk::write_block(self.num, start_sec as u64, buf)
+    }
+
+    fn get_size(&self) -> usize {
+        super::virtio_blk::get_hd_size(self.num).unwrap()
+    }
+
+    fn bus_path(&self) -> String {
+        super::virtio_blk::get_hd_path(self.num).unwrap()
+    }
+
+    fn flush(&self) -> Option<()> {
+        super::virtio_blk::flush(self.num)
+    }
+
+    fn discard(&self, start_sec: usize, count: usize) -> Option<()> {
+        super::virtio_blk::discard(self.num, start_sec as u64, count)
+    }
+}
+
+struct RamDisk {
+    num: usize,
+}
+
+impl BlockDevice for RamDisk {
+    fn read_block(&self, start_sec: usize, buf: &mut [u8]) -> Option<()> {
+        super::ramdisk::read_block(self.num, start_sec as u64, buf)
+    }
+
+    fn write_block(&self, start_sec: usize, buf: &[u8]) -> Option<()> {
+        super::ramdisk::write_block(self.num, start_sec as u64, buf)
+    }
+
+    fn get_size(&self) -> usize {
+        super::ramdisk::get_hd_size(self.num).unwrap()
+    }
+
+    fn bus_path(&self) -> String {
+        super::ramdisk::get_hd_path(self.num).unwrap()
+    }
+
+    fn device_type(&self) -> BlockDeviceType {
+        BlockDeviceType::RamDisk
     }
 }
 
 pub static HD_LIST: Mutex<Vec<Arc<dyn BlockDevice>>> = Mutex::new(Vec::new());
 
+/// Create a RAM disk after boot. Returns its index in `HD_LIST` and its
+/// number among the RAM disks.
+pub fn add_ram_disk(size: usize) -> Option<(usize, usize)> {
+    let num = super::ramdisk::create(size)?;
+    let mut hd_list = HD_LIST.lock();
+    hd_list.push(Arc::new(RamDisk { num }));
+    Some((hd_list.len() - 1, num))
+}
+
 pub fn init() {
     let ahci_disk_num = super::ahci::get_hd_num();
 
@@ -57,11 +183,25 @@ pub fn init() {
         HD_LIST.lock().push(disk.clone());
     }
 
-    let nvme_disk_num = framework::drivers::nvme::get_hd_num();
+    let nvme_disk_num = super::nvme::get_hd_num();
 
     for num in 0..nvme_disk_num {
         let disk = Arc::new(NVMeDisk { num });
         HD_LIST.lock().push(disk.clone());
     }
+
+    let virtio_disk_num = super::virtio_blk::get_hd_num();
+
+    for num in 0..virtio_disk_num {
+        let disk = Arc::new(VirtioDisk { num });
+        HD_LIST.lock().push(disk.clone());
+    }
+
+    let ram_disk_num = super::ramdisk::get_hd_num();
+
+    for num in 0..ram_disk_num {
+        let disk = Arc::new(RamDisk { num });
+        HD_LIST.lock().push(disk.clone());
+    }
 }
 
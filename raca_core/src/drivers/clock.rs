@@ -0,0 +1,4 @@
+/// Milliseconds since the timer was set up during boot.
+pub fn uptime_ms() -> u64 {
+    framework::arch::hpet::HPET.elapsed_ms()
+}
@@ -0,0 +1,141 @@
+use framework::{
+    arch::{
+        apic::{end_of_interrupt, get_lapic_id},
+        PciArch, TraitPciArch,
+    },
+    drivers::pci::PciDeviceStructureGeneralDevice,
+    memory::{addr_to_array, addr_to_mut_ref},
+};
+use spin::{Mutex, Once};
+use volatile::Volatile;
+
+use super::{clock, rng};
+use x86_64::{
+    instructions::tables::sidt,
+    structures::idt::{InterruptDescriptorTable, InterruptStackFrame},
+    VirtAddr,
+};
+
+/// First vector handed out to drivers, well above the ones the framework
+/// uses for its own interrupts
+const FIRST_VECTOR: u8 = 0x60;
+/// Number of vectors drivers can have
+const VECTORS: usize = 8;
+
+const PCI_CAP_ID_MSIX: u8 = 0x11;
+/// Message Control: MSI-X Enable
+const MSIX_ENABLE: u32 = 1 << 15;
+/// Message Control: Function Mask
+const MSIX_FUNCTION_MASK: u32 = 1 << 14;
+/// Vector Control: Mask Bit
+const MSIX_ENTRY_MASKED: u32 = 1;
+/// Message address of the local APICs, the destination APIC id goes in
+/// bits 19:12
+const MSI_ADDRESS_BASE: u32 = 0xfee0_0000;
+
+static HANDLERS: [Once<fn()>; VECTORS] = [const { Once::new() }; VECTORS];
+/// Held while a vector is picked and installed
+static ALLOC_LOCK: Mutex<()> = Mutex::new(());
+
+fn dispatch(idx: usize) {
+    // only stirs the pool, the clock is too coarse for device interrupts to
+    // count as entropy
+    rng::mix(clock::uptime_ms() ^ idx as u64);
+
+    if let Some(handler) = HANDLERS[idx].get() {
+        handler();
+    }
+    end_of_interrupt();
+}
+
+macro_rules! stubs {
+    ($($idx:literal => $name:ident),* $(,)?) => {
+        $(
+            extern "x86-interrupt" fn $name(_frame: InterruptStackFrame) {
+                dispatch($idx);
+            }
+        )*
+        const STUBS: [extern "x86-interrupt" fn(InterruptStackFrame); VECTORS] = [$($name),*];
+    };
+}
+
+stubs! {
+    0 => vector_0,
+    1 => vector_1,
+    2 => vector_2,
+    3 => vector_3,
+    4 => vector_4,
+    5 => vector_5,
+    6 => vector_6,
+    7 => vector_7,
+}
+
+/// Install `handler` on a free vector of the IDT all CPUs share, returning
+/// the vector. `handler` runs with interrupts disabled; the EOI is sent
+/// after it returns.
+pub fn register(handler: fn()) -> Option<u8> {
+    let _guard = ALLOC_LOCK.lock();
+    let idt = addr_to_mut_ref::<InterruptDescriptorTable>(sidt().base);
+
+    for (idx, slot) in HANDLERS.iter().enumerate() {
+        let vector = FIRST_VECTOR + idx as u8;
+        if slot.get().is_some() || !idt[vector].handler_addr().is_null() {
+            continue;
+        }
+        slot.call_once(|| handler);
+        idt[vector].set_handler_fn(STUBS[idx]);
+        return Some(vector);
+    }
+
+    log::warn!("irq: out of interrupt vectors");
+    None
+}
+
+/// Route MSI-X table entry 0 of `device` to `vector` on the current CPU and
+/// mask all the others. Returns `None` if the device has no MSI-X capability,
+/// in which case it is left untouched.
+pub fn enable_msix(device: &PciDeviceStructureGeneralDevice, vector: u8) -> Option<()> {
+    let bus_device_function = &device.common_header.bus_device_function;
+    let capability = device
+        .capabilities()?
+        .find(|capability| capability.id == PCI_CAP_ID_MSIX)?;
+
+    let control = capability.private_header as u32;
+    let table_size = (control & 0x7ff) as usize + 1;
+    // Table Offset/Table BIR
+    let table = PciArch::read_config(bus_device_function, capability.offset + 4);
+    let bar = device
+        .standard_device_bar
+        .get_bar((table & 0b111) as u8)
+        .ok()?
+        .virtual_address()?;
+    let entries = addr_to_array::<Volatile<u32>>(
+        VirtAddr::new(bar as u64 + (table & !0b111) as u64),
+        table_size * 4,
+    );
+
+    // keep the function masked while the table is filled in
+    let header = PciArch::read_config(bus_device_function, capability.offset);
+    PciArch::write_config(
+        bus_device_function,
+        capability.offset,
+        header | ((MSIX_ENABLE | MSIX_FUNCTION_MASK) << 16),
+    );
+
+    for entry in entries.chunks_exact_mut(4) {
+        entry[3].write(MSIX_ENTRY_MASKED);
+    }
+    entries[0].write(MSI_ADDRESS_BASE | ((get_lapic_id() as u32) << 12));
+    entries[1].write(0);
+    entries[2].write(vector as u32);
+    entries[3].write(0);
+
+    let header = PciArch::read_config(bus_device_function, capability.offset);
+    PciArch::write_config(
+        bus_device_function,
+        capability.offset,
+        (header | (MSIX_ENABLE << 16)) & !(MSIX_FUNCTION_MASK << 16),
+    );
+
+    Some(())
+}
@@ -1,5 +1,11 @@
 pub mod ahci;
 pub mod block;
+pub mod clock;
 pub mod gpu;
+pub mod irq;
+pub mod nvme;
+pub mod ramdisk;
+pub mod rng;
 pub mod usb;
+pub mod virtio_blk;
 pub mod xhci;
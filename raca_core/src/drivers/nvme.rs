@@ -1,96 +1,774 @@
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use bit_field::BitField;
+use core::{hint::spin_loop, mem::size_of};
 use framework::{
-    drivers::{alloc_for_dma, pci::find_device_with_class},
-    memory::{
-        convert_physical_to_virtual, convert_virtual_to_physical, MemoryManager, FRAME_ALLOCATOR,
-        KERNEL_PAGE_TABLE,
+    arch::apic::get_lapic_id,
+    drivers::{
+        alloc_for_dma,
+        pci::{get_pci_device_structure_mut, PCI_DEVICE_LINKEDLIST},
     },
+    memory::{addr_to_array, addr_to_mut_ref},
 };
-use nvme_driver::*;
-use pci::BAR;
-use x86_64::{
-    structures::paging::{Page, PageTableFlags, PhysFrame},
-    PhysAddr, VirtAddr,
-};
+use spin::{Mutex, Once};
+use volatile::Volatile;
+use x86_64::{instructions::interrupts::without_interrupts, PhysAddr, VirtAddr};
+
+use super::irq;
+use crate::user::{is_scheduling, wait_queue::WaitQueue};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Entries per queue, both for the admin queue and the I/O queues
+const QUEUE_DEPTH: usize = 64;
+/// Upper bound of I/O queue pairs requested from the controller. Requests are
+/// spread over the queues by the id of the submitting CPU.
+const MAX_IO_QUEUES: usize = 16;
+/// Commands each I/O queue has in flight at most
+const COMMANDS_PER_QUEUE: usize = 4;
+/// Size of the bounce buffer of each command, further limited by MDTS
+const MAX_TRANSFER_PAGES: usize = 32;
+
+const ADMIN_OPC_CREATE_IO_SQ: u8 = 0x01;
+const ADMIN_OPC_CREATE_IO_CQ: u8 = 0x05;
+const ADMIN_OPC_IDENTIFY: u8 = 0x06;
+const ADMIN_OPC_SET_FEATURES: u8 = 0x09;
+
+const IO_OPC_FLUSH: u8 = 0x00;
+const IO_OPC_WRITE: u8 = 0x01;
+const IO_OPC_READ: u8 = 0x02;
+
+const IDENTIFY_CNS_NAMESPACE: u32 = 0x00;
+const IDENTIFY_CNS_CONTROLLER: u32 = 0x01;
+const IDENTIFY_CNS_ACTIVE_NAMESPACES: u32 = 0x02;
+
+const FEATURE_NUMBER_OF_QUEUES: u32 = 0x07;
+
+/// NVMe Controller Registers (NVMe 1.4, 3.1)
+#[repr(C)]
+struct NvmeRegisters {
+    /// Controller capabilities
+    capability: Volatile<u64>,
+    /// Version
+    version: Volatile<u32>,
+    /// Interrupt mask set
+    interrupt_mask_set: Volatile<u32>,
+    /// Interrupt mask clear
+    interrupt_mask_clear: Volatile<u32>,
+    /// Controller configuration
+    configuration: Volatile<u32>,
+    reserved: Volatile<u32>,
+    /// Controller status
+    status: Volatile<u32>,
+    /// NVM subsystem reset
+    subsystem_reset: Volatile<u32>,
+    /// Admin queue attributes
+    admin_queue_attributes: Volatile<u32>,
+    /// Admin submission queue base address
+    admin_submission_queue: Volatile<u64>,
+    /// Admin completion queue base address
+    admin_completion_queue: Volatile<u64>,
+}
+
+/// Submission Queue Entry (4.2)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct NvmeCommand {
+    opcode: u8,
+    flags: u8,
+    command_id: u16,
+    namespace_id: u32,
+    reserved: u64,
+    metadata: u64,
+    prp1: u64,
+    prp2: u64,
+    cdw10: u32,
+    cdw11: u32,
+    cdw12: u32,
+    cdw13: u32,
+    cdw14: u32,
+    cdw15: u32,
+}
+
+/// Completion Queue Entry (4.6)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct NvmeCompletion {
+    result: u32,
+    reserved: u32,
+    sq_head: u16,
+    sq_id: u16,
+    command_id: u16,
+    /// Bit 0: phase tag, bit 15-1: status field
+    status: u16,
+}
+
+/// DMA memory the data of one command goes through
+#[derive(Clone, Copy)]
+struct Bounce {
+    buffer_va: VirtAddr,
+    buffer_pa: PhysAddr,
+    /// Page holding the PRP list for transfers larger than two pages
+    prp_list_va: VirtAddr,
+    prp_list_pa: PhysAddr,
+}
+
+impl Bounce {
+    fn new(pages: usize) -> Self {
+        let (buffer_pa, buffer_va) = alloc_for_dma(pages);
+        let (prp_list_pa, prp_list_va) = alloc_for_dma(1);
+        Self {
+            buffer_va,
+            buffer_pa,
+            prp_list_va,
+            prp_list_pa,
+        }
+    }
+}
+
+#[derive(Default)]
+struct CommandSlot {
+    in_use: bool,
+    /// Posted by the controller and not picked up by the submitter yet
+    completion: Option<NvmeCompletion>,
+    /// Allocated the first time the command id is used
+    bounce: Option<Bounce>,
+}
+
+/// The rings of a queue pair, only locked to post a command or collect
+/// completions.
+struct Rings {
+    submission: &'static mut [NvmeCommand],
+    completion: &'static mut [Volatile<NvmeCompletion>],
+    sq_doorbell: &'static mut Volatile<u32>,
+    cq_doorbell: &'static mut Volatile<u32>,
+    sq_tail: usize,
+    cq_head: usize,
+    phase: bool,
+    /// Indexed by command id
+    slots: Vec<CommandSlot>,
+}
+
+impl Rings {
+    /// Hand the completions the controller posted to the commands they
+    /// belong to. Completions are found by the phase tag of the next entry.
+    fn reap(&mut self) {
+        let head = self.cq_head;
+        loop {
+            let completion = self.completion[self.cq_head].read();
+            if completion.status.get_bit(0) != self.phase {
+                break;
+            }
+            if let Some(slot) = self.slots.get_mut(completion.command_id as usize) {
+                slot.completion = Some(completion);
+            }
+
+            self.cq_head += 1;
+            if self.cq_head == QUEUE_DEPTH {
+                self.cq_head = 0;
+                self.phase = !self.phase;
+            }
+        }
+        if self.cq_head != head {
+            self.cq_doorbell.write(self.cq_head as u32);
+        }
+    }
+}
+
+/// A submission queue and the completion queue paired with it.
+///
+/// Each command in flight has a command id of its own, which comes with a
+/// bounce buffer, so a thread waiting for its command holds no locks. The
+/// completions of I/O queues of controllers with an MSI-X vector are
+/// collected by the interrupt, the other queues are polled.
+struct QueuePair {
+    id: u16,
+    /// Whether the completion queue raises an interrupt
+    interrupts: bool,
+    submission_pa: PhysAddr,
+    completion_pa: PhysAddr,
+    /// Size of the bounce buffers
+    buffer_pages: usize,
+    rings: Mutex<Rings>,
+    /// Threads waiting for a command id to come free
+    free_slots: WaitQueue,
+}
+
+impl QueuePair {
+    fn new(
+        id: u16,
+        interrupts: bool,
+        doorbell_base: usize,
+        doorbell_stride: usize,
+        buffer_pages: usize,
+        commands: usize,
+    ) -> Self {
+        let (submission_pa, submission_va) =
+            alloc_for_dma((QUEUE_DEPTH * size_of::<NvmeCommand>()).div_ceil(PAGE_SIZE));
+        let (completion_pa, completion_va) =
+            alloc_for_dma((QUEUE_DEPTH * size_of::<NvmeCompletion>()).div_ceil(PAGE_SIZE));
+
+        let completion = addr_to_array::<Volatile<NvmeCompletion>>(completion_va, QUEUE_DEPTH);
+        for entry in completion.iter_mut() {
+            entry.write(NvmeCompletion::default());
+        }
+
+        let sq_doorbell = doorbell_base + (2 * id as usize) * doorbell_stride;
+        let cq_doorbell = doorbell_base + (2 * id as usize + 1) * doorbell_stride;
 
-struct DmaAllocatorImp;
-
-impl DmaAllocator for DmaAllocatorImp {
-    fn dma_alloc(size: usize) -> usize {
-        log::info!("size : {}", size);
-        let (phys, addr) = alloc_for_dma((size + 4095) / 4096);
-        let _ = <MemoryManager>::map_frame_to_page(
-            PhysFrame::containing_address(phys),
-            Page::containing_address(addr),
-            PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
-            &mut *KERNEL_PAGE_TABLE.lock(),
-            &mut *FRAME_ALLOCATOR.lock(),
+        let rings = Rings {
+            submission: addr_to_array(submission_va, QUEUE_DEPTH),
+            completion,
+            sq_doorbell: addr_to_mut_ref(VirtAddr::new(sq_doorbell as u64)),
+            cq_doorbell: addr_to_mut_ref(VirtAddr::new(cq_doorbell as u64)),
+            sq_tail: 0,
+            cq_head: 0,
+            phase: true,
+            slots: (0..commands).map(|_| CommandSlot::default()).collect(),
+        };
+
+        Self {
+            id,
+            interrupts,
+            submission_pa,
+            completion_pa,
+            buffer_pages,
+            rings: Mutex::new(rings),
+            free_slots: WaitQueue::new(),
+        }
+    }
+
+    /// Take a free command id, waiting for one if they are all in flight.
+    fn command(&self) -> Command {
+        let take = || {
+            // the interrupt handler locks the rings too
+            without_interrupts(|| {
+                let mut rings = self.rings.lock();
+                let id = rings.slots.iter().position(|slot| !slot.in_use)?;
+                rings.slots[id].in_use = true;
+                Some((id, rings.slots[id].bounce))
+            })
+        };
+        let (id, bounce) = if is_scheduling() {
+            self.free_slots.wait_uninterruptible(take)
+        } else {
+            loop {
+                if let Some(slot) = take() {
+                    break slot;
+                }
+                spin_loop();
+            }
+        };
+
+        let bounce = bounce.unwrap_or_else(|| {
+            let bounce = Bounce::new(self.buffer_pages);
+            without_interrupts(|| self.rings.lock().slots[id].bounce = Some(bounce));
+            bounce
+        });
+        Command {
+            queue: self,
+            id: id as u16,
+            buffer: addr_to_array(bounce.buffer_va, self.buffer_pages * PAGE_SIZE),
+            buffer_pa: bounce.buffer_pa,
+            prp_list: addr_to_array(bounce.prp_list_va, PAGE_SIZE / size_of::<u64>()),
+            prp_list_pa: bounce.prp_list_pa,
+        }
+    }
+}
+
+/// A command id taken from a queue, given back when dropped.
+struct Command<'a> {
+    queue: &'a QueuePair,
+    id: u16,
+    /// Bounce buffer used for the data of the command
+    buffer: &'static mut [u8],
+    buffer_pa: PhysAddr,
+    prp_list: &'static mut [u64],
+    prp_list_pa: PhysAddr,
+}
+
+impl Command<'_> {
+    /// Point the PRP entries of `command` at the first `len` bytes of the bounce buffer.
+    fn set_prps(&mut self, command: &mut NvmeCommand, len: usize) {
+        let pages = len.div_ceil(PAGE_SIZE);
+        command.prp1 = self.buffer_pa.as_u64();
+        command.prp2 = match pages {
+            0 | 1 => 0,
+            2 => self.buffer_pa.as_u64() + PAGE_SIZE as u64,
+            _ => {
+                for page in 1..pages {
+                    self.prp_list[page - 1] = self.buffer_pa.as_u64() + (page * PAGE_SIZE) as u64;
+                }
+                self.prp_list_pa.as_u64()
+            }
+        };
+    }
+
+    /// Submit `command` and wait for its completion.
+    fn submit(&mut self, mut command: NvmeCommand) -> Option<NvmeCompletion> {
+        let queue = self.queue;
+        let id = self.id as usize;
+        command.command_id = self.id;
+
+        without_interrupts(|| {
+            let mut rings = queue.rings.lock();
+            let tail = rings.sq_tail;
+            rings.submission[tail] = command;
+            rings.sq_tail = (tail + 1) % QUEUE_DEPTH;
+            let tail = rings.sq_tail as u32;
+            rings.sq_doorbell.write(tail);
+        });
+
+        // partitions are scanned before the scheduler runs, and the admin
+        // queue is only used while probing
+        let can_block = queue.id != 0 && is_scheduling();
+        let completion = if can_block && queue.interrupts {
+            COMPLETIONS.wait_uninterruptible(|| queue.rings.lock().slots[id].completion.take())
+        } else {
+            loop {
+                let completion = without_interrupts(|| {
+                    let mut rings = queue.rings.lock();
+                    rings.reap();
+                    rings.slots[id].completion.take()
+                });
+                if let Some(completion) = completion {
+                    break completion;
+                }
+                if can_block {
+                    framework::task::schedule();
+                } else {
+                    spin_loop();
+                }
+            }
+        };
+
+        if completion.status.get_bits(1..16) != 0 {
+            log::warn!(
+                "NVMe: command {:#x} on queue {} failed with status {:#x}",
+                command.opcode,
+                queue.id,
+                completion.status >> 1
+            );
+            return None;
+        }
+        Some(completion)
+    }
+
+    /// Transfer `blocks` blocks between the bounce buffer and the namespace.
+    fn io(&mut self, opcode: u8, nsid: u32, lba: u64, blocks: usize, block_size: usize) -> Option<()> {
+        let mut command = NvmeCommand {
+            opcode,
+            namespace_id: nsid,
+            cdw10: lba as u32,
+            cdw11: (lba >> 32) as u32,
+            // 0's based number of logical blocks
+            cdw12: (blocks - 1) as u32,
+            ..Default::default()
+        };
+        self.set_prps(&mut command, blocks * block_size);
+        self.submit(command).map(|_| ())
+    }
+}
+
+impl Drop for Command<'_> {
+    fn drop(&mut self) {
+        without_interrupts(|| {
+            let mut rings = self.queue.rings.lock();
+            let slot = &mut rings.slots[self.id as usize];
+            slot.in_use = false;
+            slot.completion = None;
+        });
+        self.queue.free_slots.wake_all();
+    }
+}
+
+pub struct NvmeController {
+    registers: &'static mut NvmeRegisters,
+    admin: QueuePair,
+    io_queues: Vec<QueuePair>,
+    /// Largest transfer in bytes a single command may carry
+    max_transfer: usize,
+}
+
+impl NvmeController {
+    /// `interrupts` tells whether MSI-X vector 0 of the controller has been
+    /// routed to a CPU.
+    pub fn new(header: usize, interrupts: bool) -> Option<Self> {
+        let registers = addr_to_mut_ref::<NvmeRegisters>(VirtAddr::new(header as u64));
+
+        let capability = registers.capability.read();
+        let doorbell_stride = 4 << capability.get_bits(32..36);
+        let doorbell_base = header + 0x1000;
+
+        // ref: NVMe 1.4, 7.6.1 Initialization
+        // disable the controller first
+        registers.configuration.update(|c| {
+            // CC.EN
+            c.set_bit(0, false);
+        });
+        // CSTS.RDY
+        while registers.status.read().get_bit(0) {}
+
+        let admin = QueuePair::new(0, false, doorbell_base, doorbell_stride, 1, 1);
+        registers.admin_queue_attributes.write(
+            ((QUEUE_DEPTH as u32 - 1) << 16) | (QUEUE_DEPTH as u32 - 1),
+        );
+        registers
+            .admin_submission_queue
+            .write(admin.submission_pa.as_u64());
+        registers
+            .admin_completion_queue
+            .write(admin.completion_pa.as_u64());
+
+        registers.configuration.write(
+            // CC.IOCQES = 16 bytes
+            (4 << 20)
+            // CC.IOSQES = 64 bytes
+            | (6 << 16)
+            // CC.EN
+            | 1,
         );
-        log::info!("Allocated: {:x}", addr);
-        addr.as_u64() as usize
+        while !registers.status.read().get_bit(0) {
+            // CSTS.CFS
+            if registers.status.read().get_bit(1) {
+                log::error!("NVMe: controller fatal status during enable");
+                return None;
+            }
+        }
+
+        // INTMS must not be touched with MSI-X, without it nothing handles
+        // the pin interrupt
+        if !interrupts {
+            registers.interrupt_mask_set.write(u32::MAX);
+        }
+
+        let mut controller = Self {
+            registers,
+            admin,
+            io_queues: Vec::new(),
+            max_transfer: MAX_TRANSFER_PAGES * PAGE_SIZE,
+        };
+
+        controller.identify_controller()?;
+        controller.create_io_queues(interrupts, doorbell_base, doorbell_stride)?;
+
+        log::info!(
+            "NVMe: version {:#x}, {} I/O queues, max transfer {} bytes, {}",
+            controller.registers.version.read(),
+            controller.io_queues.len(),
+            controller.max_transfer,
+            if interrupts { "MSI-X" } else { "polled" }
+        );
+
+        Some(controller)
+    }
+
+    fn identify(&self, cns: u32, nsid: u32) -> Option<Vec<u8>> {
+        let mut admin = self.admin.command();
+        let mut command = NvmeCommand {
+            opcode: ADMIN_OPC_IDENTIFY,
+            namespace_id: nsid,
+            cdw10: cns,
+            ..Default::default()
+        };
+        admin.set_prps(&mut command, PAGE_SIZE);
+        admin.submit(command)?;
+        Some(admin.buffer[..PAGE_SIZE].to_vec())
+    }
+
+    fn identify_controller(&mut self) -> Option<()> {
+        let data = self.identify(IDENTIFY_CNS_CONTROLLER, 0)?;
+
+        // MDTS, in units of the minimum memory page size
+        let mdts = data[77];
+        if mdts != 0 {
+            let min_page_size = 1 << (12 + self.registers.capability.read().get_bits(48..52));
+            let limit = (1usize << mdts) * min_page_size;
+            self.max_transfer = self.max_transfer.min(limit);
+        }
+        Some(())
+    }
+
+    fn create_io_queues(
+        &mut self,
+        interrupts: bool,
+        doorbell_base: usize,
+        doorbell_stride: usize,
+    ) -> Option<()> {
+        let wanted = MAX_IO_QUEUES as u32 - 1;
+        let completion = self.admin.command().submit(NvmeCommand {
+            opcode: ADMIN_OPC_SET_FEATURES,
+            cdw10: FEATURE_NUMBER_OF_QUEUES,
+            cdw11: (wanted << 16) | wanted,
+            ..Default::default()
+        })?;
+
+        // 0's based counts of the allocated submission and completion queues
+        let allocated_sq = completion.result.get_bits(0..16) as usize + 1;
+        let allocated_cq = completion.result.get_bits(16..32) as usize + 1;
+        let queue_num = allocated_sq.min(allocated_cq).min(MAX_IO_QUEUES);
+
+        let buffer_pages = self.max_transfer / PAGE_SIZE;
+
+        for id in 1..=queue_num as u16 {
+            let queue = QueuePair::new(
+                id,
+                interrupts,
+                doorbell_base,
+                doorbell_stride,
+                buffer_pages,
+                COMMANDS_PER_QUEUE,
+            );
+            let size = (QUEUE_DEPTH as u32 - 1) << 16;
+
+            let mut admin = self.admin.command();
+            admin.submit(NvmeCommand {
+                opcode: ADMIN_OPC_CREATE_IO_CQ,
+                prp1: queue.completion_pa.as_u64(),
+                cdw10: size | id as u32,
+                // IV 0, IEN, PC
+                cdw11: ((interrupts as u32) << 1) | 1,
+                ..Default::default()
+            })?;
+            admin.submit(NvmeCommand {
+                opcode: ADMIN_OPC_CREATE_IO_SQ,
+                prp1: queue.submission_pa.as_u64(),
+                cdw10: size | id as u32,
+                // CQID, PC
+                cdw11: ((id as u32) << 16) | 1,
+                ..Default::default()
+            })?;
+            drop(admin);
+
+            self.io_queues.push(queue);
+        }
+
+        if self.io_queues.is_empty() {
+            None
+        } else {
+            Some(())
+        }
+    }
+
+    /// Pick the I/O queue of the current CPU.
+    fn io_queue(&self) -> &QueuePair {
+        let idx = get_lapic_id() as usize % self.io_queues.len();
+        &self.io_queues[idx]
+    }
+
+    fn namespaces(self: &Arc<Self>, controller_id: usize) -> Vec<Arc<NvmeNamespace>> {
+        let mut namespaces = Vec::new();
+
+        let list = match self.identify(IDENTIFY_CNS_ACTIVE_NAMESPACES, 0) {
+            Some(list) => list,
+            None => return namespaces,
+        };
+
+        for nsid in list.chunks_exact(4) {
+            let nsid = u32::from_le_bytes(nsid.try_into().unwrap());
+            if nsid == 0 {
+                break;
+            }
+            let data = match self.identify(IDENTIFY_CNS_NAMESPACE, nsid) {
+                Some(data) => data,
+                None => continue,
+            };
+
+            // NSZE
+            let blocks = u64::from_le_bytes(data[0..8].try_into().unwrap());
+            // FLBAS selects the LBA format in use
+            let format = data[26].get_bits(0..4) as usize;
+            // LBAF.LBADS
+            let lba_data_size = data[128 + format * 4 + 2];
+
+            if blocks == 0 || lba_data_size < 9 {
+                continue;
+            }
+
+            namespaces.push(Arc::new(NvmeNamespace {
+                controller: self.clone(),
+                controller_id,
+                id: nsid,
+                blocks,
+                block_size: 1 << lba_data_size,
+            }));
+        }
+
+        namespaces
+    }
+}
+
+/// A namespace of an NVMe controller, exposed as a disk.
+pub struct NvmeNamespace {
+    controller: Arc<NvmeController>,
+    /// Position of the controller in PCI enumeration order
+    controller_id: usize,
+    id: u32,
+    blocks: u64,
+    block_size: usize,
+}
+
+impl NvmeNamespace {
+    pub fn size(&self) -> usize {
+        self.blocks as usize * self.block_size
     }
 
-    fn dma_dealloc(_addr: usize, _size: usize) -> usize {
-        0
+    pub fn block_size(&self) -> usize {
+        self.block_size
     }
 
-    fn phys_to_virt(phys: usize) -> usize {
-        convert_physical_to_virtual(PhysAddr::new(phys as u64)).as_u64() as usize
+    pub fn read(&self, offset: usize, buf: &mut [u8]) -> Option<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let skip = pos % self.block_size;
+            let len = (buf.len() - done).min(self.controller.max_transfer - skip);
+            let blocks = (skip + len).div_ceil(self.block_size);
+
+            let mut command = self.controller.io_queue().command();
+            command.io(
+                IO_OPC_READ,
+                self.id,
+                (pos / self.block_size) as u64,
+                blocks,
+                self.block_size,
+            )?;
+            buf[done..done + len].copy_from_slice(&command.buffer[skip..skip + len]);
+
+            done += len;
+        }
+        Some(())
+    }
+
+    pub fn write(&self, offset: usize, buf: &[u8]) -> Option<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let pos = offset + done;
+            let skip = pos % self.block_size;
+            let len = (buf.len() - done).min(self.controller.max_transfer - skip);
+            let blocks = (skip + len).div_ceil(self.block_size);
+            let lba = (pos / self.block_size) as u64;
+
+            let mut command = self.controller.io_queue().command();
+            if skip != 0 || len % self.block_size != 0 {
+                // partial blocks have to be merged with the data on the disk
+                command.io(IO_OPC_READ, self.id, lba, blocks, self.block_size)?;
+            }
+            command.buffer[skip..skip + len].copy_from_slice(&buf[done..done + len]);
+            command.io(IO_OPC_WRITE, self.id, lba, blocks, self.block_size)?;
+
+            done += len;
+        }
+        Some(())
     }
 
-    fn virt_to_phys(virt: usize) -> usize {
-        convert_virtual_to_physical(VirtAddr::new(virt as u64)).as_u64() as usize
+    pub fn flush(&self) -> Option<()> {
+        self.controller
+            .io_queue()
+            .command()
+            .submit(NvmeCommand {
+                opcode: IO_OPC_FLUSH,
+                namespace_id: self.id,
+                ..Default::default()
+            })
+            .map(|_| ())
     }
 }
 
-struct IrqControllerImp;
+static NVME_NAMESPACES: Mutex<Vec<Arc<NvmeNamespace>>> = Mutex::new(Vec::new());
+
+/// Threads waiting for a completion on any I/O queue. All the controllers
+/// share one vector, so an interrupt collects the completions of every queue
+/// and makes every waiter check its own command.
+static COMPLETIONS: WaitQueue = WaitQueue::new();
+/// The vector the controllers signal completions on, if one was free
+static VECTOR: Once<Option<u8>> = Once::new();
+/// Every controller, once they are all set up
+static CONTROLLERS: Once<Vec<Arc<NvmeController>>> = Once::new();
 
-impl IrqController for IrqControllerImp {
-    fn enable_irq(_irq_num: usize) {}
+fn handle_interrupt() {
+    for controller in CONTROLLERS.get().into_iter().flatten() {
+        for queue in controller.io_queues.iter().filter(|queue| queue.interrupts) {
+            queue.rings.lock().reap();
+        }
+    }
+    COMPLETIONS.wake_all();
+}
 
-    fn disable_irq(_irq_num: usize) {}
+fn find_namespace(hd: usize) -> Option<Arc<NvmeNamespace>> {
+    NVME_NAMESPACES.lock().get(hd).cloned()
 }
 
 pub fn init() {
-    let nvme_device = find_device_with_class(0x01, 0x08);
-    if nvme_device.len() == 0 {
-        log::error!("No NVME Device");
-        return;
-    }
-    let nvme_device = &nvme_device[0];
-    log::info!("NVME Bars :{:?}", nvme_device.bars);
-    if let Some(BAR::Memory(addr, _, _, _)) = nvme_device.bars[0] {
-        log::info!("OK!");
-        //assert!(len as usize <= 4096);
-        let header = /*convert_physical_to_virtual(PhysAddr::new(addr)).as_u64() as usize*/ addr as usize;
-        //let size = len as usize;
-
-        log::info!("NVME Addr Phys: {:x} Virt: {:x}", addr, header);
-        // NVME控制器的MMIO大小约有16KB,也就是4页
-        for i in 0..4 {
-            <MemoryManager>::map_frame_to_page(
-                PhysFrame::containing_address(PhysAddr::new(addr + i * 4096)),
-                Page::containing_address(VirtAddr::new(header as u64 + i * 4096)),
-                PageTableFlags::PRESENT | PageTableFlags::WRITABLE,
-                &mut *KERNEL_PAGE_TABLE.lock(),
-                &mut *FRAME_ALLOCATOR.lock(),
-            )
-            .unwrap();
-        }
-
-        framework::drivers::pci::enable_device(nvme_device);
-
-        log::info!("OK !");
-
-        let nvme = NvmeInterface::<DmaAllocatorImp, IrqControllerImp>::new(header);
-        log::info!("OK D");
-        for i in 0..5 {
-            let mut read_buf = [0u8; 512];
-            let buff = [i as u8; 512];
-            let write_buf: &[u8] = &[i as u8; 512];
-            nvme.write_block(i, &write_buf);
-            nvme.read_block(i, &mut read_buf);
-            framework::serial_println!("{:?}", read_buf);
-            assert_eq!(read_buf, buff);
+    let mut list = PCI_DEVICE_LINKEDLIST.write();
+    let mut devices = get_pci_device_structure_mut(&mut list, 0x01, 0x08);
+
+    let mut controllers = Vec::new();
+
+    for device in devices.iter_mut() {
+        if let None = device.bar_init() {
+            continue;
+        }
+        if let Ok(bar) = device
+            .as_standard_device()
+            .unwrap()
+            .standard_device_bar
+            .get_bar(0)
+        {
+            if let Some(header) = bar.virtual_address() {
+                let vector = *VECTOR.call_once(|| irq::register(handle_interrupt));
+                let interrupts = vector
+                    .and_then(|vector| irq::enable_msix(device.as_standard_device()?, vector))
+                    .is_some();
+                device.as_mut().enable_master();
+
+                if let Some(controller) = NvmeController::new(header as usize, interrupts) {
+                    controllers.push(Arc::new(controller));
+                }
+            }
         }
     }
+
+    let mut namespaces = NVME_NAMESPACES.lock();
+    for (controller_id, controller) in controllers.iter().enumerate() {
+        namespaces.extend(controller.namespaces(controller_id));
+    }
+
+    log::info!(
+        "NVMe: found {} controllers, {} namespaces",
+        controllers.len(),
+        namespaces.len()
+    );
+    CONTROLLERS.call_once(|| controllers);
+}
+
+pub fn read_block(hd: usize, start_sec: u64, buf: &mut [u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_namespace(hd)?.read(start_sec as usize * 512, buf)
+}
+
+pub fn write_block(hd: usize, start_sec: u64, buf: &[u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_namespace(hd)?.write(start_sec as usize * 512, buf)
+}
+
+pub fn flush(hd: usize) -> Option<()> {
+    find_namespace(hd)?.flush()
+}
+
+pub fn get_hd_num() -> usize {
+    NVME_NAMESPACES.lock().len()
+}
+
+pub fn get_hd_size(hd: usize) -> Option<usize> {
+    Some(find_namespace(hd)?.size())
+}
+
+pub fn get_hd_block_size(hd: usize) -> Option<usize> {
+    Some(find_namespace(hd)?.block_size())
+}
+
+/// Name of the controller and namespace the device is backed by.
+pub fn get_hd_path(hd: usize) -> Option<String> {
+    let namespace = find_namespace(hd)?;
+    Some(format!("nvme{}-ns{}", namespace.controller_id, namespace.id))
 }
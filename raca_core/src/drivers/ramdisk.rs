@@ -0,0 +1,123 @@
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use spin::{Mutex, RwLock};
+
+use crate::fs::get_kernel_cmdline;
+
+const SECTOR_SIZE: usize = 512;
+/// Largest RAM disk that can be created, from the command line or by a
+/// process
+const MAX_SIZE: usize = 1024 * 1024 * 1024;
+
+/// A disk kept entirely in kernel memory. Its contents are lost on reboot.
+pub struct RamDisk {
+    data: RwLock<Vec<u8>>,
+}
+
+impl RamDisk {
+    fn new(size: usize) -> Option<Self> {
+        let size = size.checked_next_multiple_of(SECTOR_SIZE)?;
+        if size == 0 || size > MAX_SIZE {
+            return None;
+        }
+
+        let mut data = Vec::new();
+        data.try_reserve_exact(size).ok()?;
+        data.resize(size, 0);
+
+        Some(Self {
+            data: RwLock::new(data),
+        })
+    }
+
+    fn range(&self, start_sec: u64, len: usize) -> Option<core::ops::Range<usize>> {
+        let start = (start_sec as usize).checked_mul(SECTOR_SIZE)?;
+        let end = start.checked_add(len)?;
+        if end > self.size() {
+            return None;
+        }
+        Some(start..end)
+    }
+
+    pub fn read(&self, start_sec: u64, buf: &mut [u8]) -> Option<()> {
+        let range = self.range(start_sec, buf.len())?;
+        buf.copy_from_slice(&self.data.read()[range]);
+        Some(())
+    }
+
+    pub fn write(&self, start_sec: u64, buf: &[u8]) -> Option<()> {
+        let range = self.range(start_sec, buf.len())?;
+        self.data.write()[range].copy_from_slice(buf);
+        Some(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.data.read().len()
+    }
+}
+
+static RAM_DISKS: Mutex<Vec<Arc<RamDisk>>> = Mutex::new(Vec::new());
+
+fn find_disk(hd: usize) -> Option<Arc<RamDisk>> {
+    RAM_DISKS.lock().get(hd).cloned()
+}
+
+/// Parse a size like `4096`, `512K`, `64M` or `1G`.
+fn parse_size(size: &str) -> Option<usize> {
+    let (number, unit) = match size.as_bytes().last()? {
+        b'K' | b'k' => (&size[..size.len() - 1], 1024),
+        b'M' | b'm' => (&size[..size.len() - 1], 1024 * 1024),
+        b'G' | b'g' => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    number.parse::<usize>().ok()?.checked_mul(unit)
+}
+
+/// Create a RAM disk of `size` bytes, rounded up to whole sectors, and
+/// return its number. At most `MAX_SIZE` bytes can be asked for, and the
+/// creation fails instead of panicking if the memory isn't there.
+pub fn create(size: usize) -> Option<usize> {
+    let disk = RamDisk::new(size)?;
+    let mut ram_disks = RAM_DISKS.lock();
+    ram_disks.push(Arc::new(disk));
+    Some(ram_disks.len() - 1)
+}
+
+/// Create the RAM disks asked for with `ramdisk=<size>[,<size>...]` on the
+/// kernel command line.
+pub fn init() {
+    let cmdline = get_kernel_cmdline();
+    let sizes = cmdline
+        .split_whitespace()
+        .filter_map(|arg| arg.strip_prefix("ramdisk="))
+        .flat_map(|sizes| sizes.split(','));
+
+    for size in sizes {
+        match parse_size(size).and_then(create) {
+            Some(num) => log::info!("ramdisk: created ram{} with {} bytes", num, size),
+            None => log::warn!("ramdisk: cannot create a RAM disk of {}", size),
+        }
+    }
+}
+
+pub fn read_block(hd: usize, start_sec: u64, buf: &mut [u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_disk(hd)?.read(start_sec, buf)
+}
+
+pub fn write_block(hd: usize, start_sec: u64, buf: &[u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_disk(hd)?.write(start_sec, buf)
+}
+
+pub fn get_hd_num() -> usize {
+    RAM_DISKS.lock().len()
+}
+
+pub fn get_hd_size(hd: usize) -> Option<usize> {
+    Some(find_disk(hd)?.size())
+}
+
+pub fn get_hd_path(hd: usize) -> Option<String> {
+    find_disk(hd)?;
+    Some(format!("ram{}", hd))
+}
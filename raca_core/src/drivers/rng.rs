@@ -0,0 +1,98 @@
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+
+use spin::Mutex;
+use x86_64::instructions::{interrupts::without_interrupts, random::RdRand};
+
+use super::clock;
+
+/// Samples from event timing, used when the CPU has no RDRAND. The
+/// constants are only a starting point, [`init`] and every output mix the
+/// uptime in.
+static POOL: Mutex<[u64; 4]> = Mutex::new([
+    0x853c_49e6_748f_ea9b,
+    0xda3e_39cb_94b9_5bdb,
+    0x9e37_79b9_7f4a_7c15,
+    0xbf58_476d_1ce4_e5b9,
+]);
+static POOL_INDEX: AtomicUsize = AtomicUsize::new(0);
+static OUTPUT_COUNTER: AtomicU64 = AtomicU64::new(0);
+/// Event timings mixed into the pool so far
+static SAMPLES: AtomicUsize = AtomicUsize::new(0);
+
+/// Timings the pool needs before it is trusted for values that must differ
+/// between boots, like GUIDs. The initial pool is the same on every boot.
+const SEEDED_SAMPLES: usize = 64;
+
+fn splitmix64(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+/// Mix a hard to predict value, like the time between two interrupts, into
+/// the pool.
+pub fn add_entropy(sample: u64) {
+    mix(sample);
+    SAMPLES.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Mix a value into the pool without counting it as entropy, for data that
+/// may be known to others.
+pub fn mix(sample: u64) {
+    let idx = POOL_INDEX.fetch_add(1, Ordering::Relaxed) % 4;
+    // interrupt handlers add their timing too
+    without_interrupts(|| {
+        let mut pool = POOL.lock();
+        pool[idx] = splitmix64(pool[idx] ^ sample.rotate_left(idx as u32 * 16));
+    });
+}
+
+/// Whether the output differs between boots: the CPU has RDRAND, or enough
+/// timings went into the pool.
+pub fn is_seeded() -> bool {
+    RdRand::new().is_some() || SAMPLES.load(Ordering::Relaxed) >= SEEDED_SAMPLES
+}
+
+/// Move the pool away from its constant start with what the CPU offers.
+pub fn init() {
+    mix(clock::uptime_ms());
+    if let Some(seed) = RdRand::new().and_then(|rdrand| rdrand.get_u64()) {
+        add_entropy(seed);
+    }
+}
+
+/// Not suitable for keys unless enough entropy went into the pool.
+fn pool_u64() -> u64 {
+    let counter = OUTPUT_COUNTER.fetch_add(1, Ordering::Relaxed);
+    without_interrupts(|| {
+        let mut pool = POOL.lock();
+        let mixed = pool[0] ^ pool[1].rotate_left(17) ^ pool[2].rotate_left(31) ^ pool[3];
+        let out = splitmix64(mixed ^ counter ^ clock::uptime_ms());
+        // feed the output back, so earlier outputs can't be recomputed from the pool
+        pool[(counter % 4) as usize] ^= splitmix64(out);
+        out
+    })
+}
+
+pub fn next_u64() -> u64 {
+    RdRand::new()
+        .and_then(|rdrand| rdrand.get_u64())
+        .unwrap_or_else(pool_u64)
+}
+
+pub fn fill_bytes(buf: &mut [u8]) {
+    for chunk in buf.chunks_mut(8) {
+        let bytes = next_u64().to_le_bytes();
+        chunk.copy_from_slice(&bytes[..chunk.len()]);
+    }
+}
+
+/// Like [`fill_bytes`], but fail while the pool [is not seeded](is_seeded).
+pub fn try_fill_bytes(buf: &mut [u8]) -> Option<()> {
+    if !is_seeded() {
+        return None;
+    }
+    fill_bytes(buf);
+    Some(())
+}
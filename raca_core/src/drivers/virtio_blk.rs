@@ -0,0 +1,518 @@
+use alloc::{format, string::String, sync::Arc, vec::Vec};
+use bit_field::BitField;
+use core::{
+    hint::spin_loop,
+    mem::size_of,
+    sync::atomic::{fence, Ordering},
+};
+use framework::{
+    arch::{PciArch, TraitPciArch},
+    drivers::{
+        alloc_for_dma,
+        pci::{get_pci_device_structure_mut, PciDeviceStructureGeneralDevice, PCI_DEVICE_LINKEDLIST},
+    },
+    memory::{addr_to_array, addr_to_mut_ref},
+};
+use spin::Mutex;
+use volatile::Volatile;
+use x86_64::{PhysAddr, VirtAddr};
+
+const PAGE_SIZE: usize = 4096;
+
+const VIRTIO_VENDOR_ID: u16 = 0x1af4;
+/// Transitional and modern device ids of virtio-blk
+const VIRTIO_BLK_DEVICE_IDS: [u16; 2] = [0x1001, 0x1042];
+
+const PCI_CAP_ID_VENDOR: u8 = 0x09;
+
+const VIRTIO_PCI_CAP_COMMON_CFG: u8 = 1;
+const VIRTIO_PCI_CAP_NOTIFY_CFG: u8 = 2;
+const VIRTIO_PCI_CAP_DEVICE_CFG: u8 = 4;
+
+const STATUS_ACKNOWLEDGE: u8 = 1;
+const STATUS_DRIVER: u8 = 2;
+const STATUS_DRIVER_OK: u8 = 4;
+const STATUS_FEATURES_OK: u8 = 8;
+const STATUS_FAILED: u8 = 128;
+
+const VIRTIO_BLK_F_FLUSH: usize = 9;
+const VIRTIO_BLK_F_DISCARD: usize = 13;
+const VIRTIO_F_VERSION_1: usize = 32;
+
+const VIRTIO_BLK_T_IN: u32 = 0;
+const VIRTIO_BLK_T_OUT: u32 = 1;
+const VIRTIO_BLK_T_FLUSH: u32 = 4;
+const VIRTIO_BLK_T_DISCARD: u32 = 11;
+
+const VIRTIO_BLK_S_OK: u8 = 0;
+
+const VIRTQ_DESC_F_NEXT: u16 = 1;
+const VIRTQ_DESC_F_WRITE: u16 = 2;
+
+/// Largest queue we set up, requests only ever use three descriptors
+const MAX_QUEUE_SIZE: u16 = 128;
+/// Size of the bounce buffer used for the data of one request
+const BUFFER_PAGES: usize = 32;
+
+/// Common configuration structure (virtio 1.1, 4.1.4.3)
+#[repr(C)]
+struct VirtioCommonConfig {
+    device_feature_select: Volatile<u32>,
+    device_feature: Volatile<u32>,
+    driver_feature_select: Volatile<u32>,
+    driver_feature: Volatile<u32>,
+    msix_config: Volatile<u16>,
+    num_queues: Volatile<u16>,
+    device_status: Volatile<u8>,
+    config_generation: Volatile<u8>,
+    queue_select: Volatile<u16>,
+    queue_size: Volatile<u16>,
+    queue_msix_vector: Volatile<u16>,
+    queue_enable: Volatile<u16>,
+    queue_notify_off: Volatile<u16>,
+    queue_desc: Volatile<u64>,
+    queue_driver: Volatile<u64>,
+    queue_device: Volatile<u64>,
+}
+
+/// Device configuration layout of virtio-blk (virtio 1.1, 5.2.4)
+#[repr(C)]
+struct VirtioBlkConfig {
+    /// Capacity in 512-byte sectors
+    capacity: Volatile<u64>,
+    size_max: Volatile<u32>,
+    seg_max: Volatile<u32>,
+    geometry: Volatile<u32>,
+    blk_size: Volatile<u32>,
+    topology: Volatile<u64>,
+    writeback: Volatile<u8>,
+    unused0: Volatile<u8>,
+    num_queues: Volatile<u16>,
+    max_discard_sectors: Volatile<u32>,
+    max_discard_seg: Volatile<u32>,
+    discard_sector_alignment: Volatile<u32>,
+}
+
+/// Split virtqueue descriptor (2.6.5)
+#[repr(C)]
+#[derive(Clone, Copy, Default)]
+struct VirtqDesc {
+    addr: u64,
+    len: u32,
+    flags: u16,
+    next: u16,
+}
+
+/// Header of every virtio-blk request
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VirtioBlkRequest {
+    ty: u32,
+    reserved: u32,
+    sector: u64,
+}
+
+/// Payload of a discard request
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct VirtioBlkDiscard {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+/// Location of a virtio structure inside a BAR
+struct VirtioCapability {
+    bar: u8,
+    offset: u32,
+}
+
+/// The single request queue of a virtio-blk device.
+///
+/// Every request is a chain of three descriptors: the request header, the
+/// data in the bounce buffer and the status byte written by the device.
+struct VirtQueue {
+    size: u16,
+    desc: &'static mut [VirtqDesc],
+    avail_idx: &'static mut Volatile<u16>,
+    avail_ring: &'static mut [Volatile<u16>],
+    used_idx: &'static mut Volatile<u16>,
+    notify: &'static mut Volatile<u16>,
+    last_used_idx: u16,
+    /// Page holding the request header at offset 0 and the status at offset 512
+    header: &'static mut [u8],
+    header_pa: PhysAddr,
+    buffer: &'static mut [u8],
+    buffer_pa: PhysAddr,
+}
+
+impl VirtQueue {
+    fn submit(&mut self, request: VirtioBlkRequest, len: usize, device_writes: bool) -> Option<()> {
+        const STATUS_OFFSET: usize = 512;
+
+        self.header[..size_of::<VirtioBlkRequest>()].copy_from_slice(as_bytes(&request));
+        self.header[STATUS_OFFSET] = 0xff;
+
+        self.desc[0] = VirtqDesc {
+            addr: self.header_pa.as_u64(),
+            len: size_of::<VirtioBlkRequest>() as u32,
+            flags: VIRTQ_DESC_F_NEXT,
+            next: if len > 0 { 1 } else { 2 },
+        };
+        if len > 0 {
+            self.desc[1] = VirtqDesc {
+                addr: self.buffer_pa.as_u64(),
+                len: len as u32,
+                flags: if device_writes {
+                    VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE
+                } else {
+                    VIRTQ_DESC_F_NEXT
+                },
+                next: 2,
+            };
+        }
+        self.desc[2] = VirtqDesc {
+            addr: self.header_pa.as_u64() + STATUS_OFFSET as u64,
+            len: 1,
+            flags: VIRTQ_DESC_F_WRITE,
+            next: 0,
+        };
+
+        let avail_idx = self.avail_idx.read();
+        self.avail_ring[(avail_idx % self.size) as usize].write(0);
+        fence(Ordering::SeqCst);
+        self.avail_idx.write(avail_idx.wrapping_add(1));
+        fence(Ordering::SeqCst);
+        self.notify.write(0);
+
+        while self.used_idx.read() == self.last_used_idx {
+            spin_loop();
+        }
+        self.last_used_idx = self.last_used_idx.wrapping_add(1);
+        fence(Ordering::SeqCst);
+
+        if self.header[STATUS_OFFSET] == VIRTIO_BLK_S_OK {
+            Some(())
+        } else {
+            None
+        }
+    }
+}
+
+pub struct VirtioBlk {
+    queue: Mutex<VirtQueue>,
+    capacity: u64,
+    flush: bool,
+    discard: bool,
+    /// Most sectors one discard segment may cover
+    max_discard_sectors: u32,
+    /// Most segments one discard request may carry
+    max_discard_seg: usize,
+}
+
+impl VirtioBlk {
+    fn new(device: &PciDeviceStructureGeneralDevice) -> Option<Self> {
+        let bus_device_function = &device.common_header.bus_device_function;
+
+        let mut common = None;
+        let mut notify = None;
+        let mut device_cfg = None;
+        let mut notify_off_multiplier = 0;
+
+        for capability in device.capabilities()? {
+            if capability.id != PCI_CAP_ID_VENDOR {
+                continue;
+            }
+            let cfg_type = (capability.private_header >> 8) as u8;
+            let info = VirtioCapability {
+                bar: PciArch::read_config(bus_device_function, capability.offset + 4) as u8,
+                offset: PciArch::read_config(bus_device_function, capability.offset + 8),
+            };
+            match cfg_type {
+                VIRTIO_PCI_CAP_COMMON_CFG => common = Some(info),
+                VIRTIO_PCI_CAP_NOTIFY_CFG => {
+                    notify_off_multiplier =
+                        PciArch::read_config(bus_device_function, capability.offset + 16);
+                    notify = Some(info);
+                }
+                VIRTIO_PCI_CAP_DEVICE_CFG => device_cfg = Some(info),
+                _ => {}
+            }
+        }
+
+        let locate = |capability: VirtioCapability| -> Option<u64> {
+            let bar = device
+                .standard_device_bar
+                .get_bar(capability.bar)
+                .ok()?
+                .virtual_address()?;
+            Some(bar as u64 + capability.offset as u64)
+        };
+
+        // legacy-only devices don't have the virtio 1.x capabilities
+        let common = addr_to_mut_ref::<VirtioCommonConfig>(VirtAddr::new(locate(common?)?));
+        let notify_base = locate(notify?)?;
+        let config = addr_to_mut_ref::<VirtioBlkConfig>(VirtAddr::new(locate(device_cfg?)?));
+
+        // ref: virtio 1.1, 3.1.1 Driver Requirements: Device Initialization
+        common.device_status.write(0);
+        while common.device_status.read() != 0 {}
+        common.device_status.write(STATUS_ACKNOWLEDGE);
+        common.device_status.write(STATUS_ACKNOWLEDGE | STATUS_DRIVER);
+
+        let mut features = 0u64;
+        for select in 0..2 {
+            common.device_feature_select.write(select);
+            features |= (common.device_feature.read() as u64) << (select * 32);
+        }
+        if !features.get_bit(VIRTIO_F_VERSION_1) {
+            common.device_status.write(STATUS_FAILED);
+            return None;
+        }
+
+        let mut driver_features = 0u64;
+        driver_features.set_bit(VIRTIO_F_VERSION_1, true);
+        driver_features.set_bit(VIRTIO_BLK_F_FLUSH, features.get_bit(VIRTIO_BLK_F_FLUSH));
+        driver_features.set_bit(VIRTIO_BLK_F_DISCARD, features.get_bit(VIRTIO_BLK_F_DISCARD));
+        for select in 0..2 {
+            common.driver_feature_select.write(select);
+            common
+                .driver_feature
+                .write((driver_features >> (select * 32)) as u32);
+        }
+
+        common
+            .device_status
+            .update(|status| *status |= STATUS_FEATURES_OK);
+        if common.device_status.read() & STATUS_FEATURES_OK == 0 {
+            common.device_status.write(STATUS_FAILED);
+            return None;
+        }
+
+        common.queue_select.write(0);
+        let size = common.queue_size.read().min(MAX_QUEUE_SIZE);
+        if size < 3 {
+            common.device_status.write(STATUS_FAILED);
+            return None;
+        }
+        let (desc_pa, desc_va) = alloc_for_dma((size as usize * size_of::<VirtqDesc>()).div_ceil(PAGE_SIZE));
+        // flags, idx, ring[size], used_event
+        let (avail_pa, avail_va) = alloc_for_dma((6 + 2 * size as usize).div_ceil(PAGE_SIZE));
+        // flags, idx, ring[size] of (id, len), avail_event
+        let (used_pa, used_va) = alloc_for_dma((6 + 8 * size as usize).div_ceil(PAGE_SIZE));
+        let (header_pa, header_va) = alloc_for_dma(1);
+        let (buffer_pa, buffer_va) = alloc_for_dma(BUFFER_PAGES);
+
+        common.queue_size.write(size);
+        common.queue_desc.write(desc_pa.as_u64());
+        common.queue_driver.write(avail_pa.as_u64());
+        common.queue_device.write(used_pa.as_u64());
+        // no MSI-X vector, completions are polled
+        common.queue_msix_vector.write(0xffff);
+
+        let notify_off = common.queue_notify_off.read() as u64 * notify_off_multiplier as u64;
+        common.queue_enable.write(1);
+
+        common
+            .device_status
+            .update(|status| *status |= STATUS_DRIVER_OK);
+
+        let queue = VirtQueue {
+            size,
+            desc: addr_to_array(desc_va, size as usize),
+            avail_idx: addr_to_mut_ref(avail_va + 2u64),
+            avail_ring: addr_to_array(avail_va + 4u64, size as usize),
+            used_idx: addr_to_mut_ref(used_va + 2u64),
+            notify: addr_to_mut_ref(VirtAddr::new(notify_base + notify_off)),
+            last_used_idx: 0,
+            header: addr_to_array(header_va, PAGE_SIZE),
+            header_pa,
+            buffer: addr_to_array(buffer_va, BUFFER_PAGES * PAGE_SIZE),
+            buffer_pa,
+        };
+
+        let discard = driver_features.get_bit(VIRTIO_BLK_F_DISCARD);
+        // the discard limits only exist with VIRTIO_BLK_F_DISCARD, and a
+        // device leaving them at 0 gets no more than one full segment
+        let (max_discard_sectors, max_discard_seg) = if discard {
+            let sectors = config.max_discard_sectors.read();
+            let segments = config.max_discard_seg.read() as usize;
+            (
+                if sectors == 0 { u32::MAX } else { sectors },
+                segments.max(1),
+            )
+        } else {
+            (0, 0)
+        };
+
+        Some(Self {
+            queue: Mutex::new(queue),
+            capacity: config.capacity.read(),
+            flush: driver_features.get_bit(VIRTIO_BLK_F_FLUSH),
+            discard,
+            max_discard_sectors,
+            max_discard_seg,
+        })
+    }
+
+    pub fn read(&self, start_sec: u64, buf: &mut [u8]) -> Option<()> {
+        let mut queue = self.queue.lock();
+        for (idx, chunk) in buf.chunks_mut(BUFFER_PAGES * PAGE_SIZE).enumerate() {
+            let request = VirtioBlkRequest {
+                ty: VIRTIO_BLK_T_IN,
+                reserved: 0,
+                sector: start_sec + (idx * BUFFER_PAGES * PAGE_SIZE / 512) as u64,
+            };
+            queue.submit(request, chunk.len(), true)?;
+            chunk.copy_from_slice(&queue.buffer[..chunk.len()]);
+        }
+        Some(())
+    }
+
+    pub fn write(&self, start_sec: u64, buf: &[u8]) -> Option<()> {
+        let mut queue = self.queue.lock();
+        for (idx, chunk) in buf.chunks(BUFFER_PAGES * PAGE_SIZE).enumerate() {
+            let request = VirtioBlkRequest {
+                ty: VIRTIO_BLK_T_OUT,
+                reserved: 0,
+                sector: start_sec + (idx * BUFFER_PAGES * PAGE_SIZE / 512) as u64,
+            };
+            queue.buffer[..chunk.len()].copy_from_slice(chunk);
+            queue.submit(request, chunk.len(), false)?;
+        }
+        Some(())
+    }
+
+    pub fn flush(&self) -> Option<()> {
+        if !self.flush {
+            // without VIRTIO_BLK_F_FLUSH the device writes through
+            return Some(());
+        }
+        let request = VirtioBlkRequest {
+            ty: VIRTIO_BLK_T_FLUSH,
+            reserved: 0,
+            sector: 0,
+        };
+        self.queue.lock().submit(request, 0, false)
+    }
+
+    /// Discard `count` sectors from `start_sec`, split into as many requests
+    /// as the limits in the device configuration ask for.
+    pub fn discard(&self, start_sec: u64, count: usize) -> Option<()> {
+        if !self.discard {
+            return None;
+        }
+        let end = start_sec.checked_add(count as u64)?;
+        if end > self.capacity {
+            return None;
+        }
+
+        const SEGMENT_SIZE: usize = size_of::<VirtioBlkDiscard>();
+        let max_segments = self
+            .max_discard_seg
+            .min(BUFFER_PAGES * PAGE_SIZE / SEGMENT_SIZE);
+
+        let request = VirtioBlkRequest {
+            ty: VIRTIO_BLK_T_DISCARD,
+            reserved: 0,
+            sector: 0,
+        };
+        let mut queue = self.queue.lock();
+        let mut sector = start_sec;
+        while sector < end {
+            let mut segments = 0;
+            while segments < max_segments && sector < end {
+                let num_sectors = (end - sector).min(self.max_discard_sectors as u64);
+                let segment = VirtioBlkDiscard {
+                    sector,
+                    num_sectors: num_sectors as u32,
+                    flags: 0,
+                };
+                let offset = segments * SEGMENT_SIZE;
+                queue.buffer[offset..offset + SEGMENT_SIZE].copy_from_slice(as_bytes(&segment));
+                sector += num_sectors;
+                segments += 1;
+            }
+            queue.submit(request, segments * SEGMENT_SIZE, false)?;
+        }
+        Some(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.capacity as usize * 512
+    }
+}
+
+fn as_bytes<T: Copy>(value: &T) -> &[u8] {
+    let addr = VirtAddr::from_ptr(value as *const T);
+    addr_to_array(addr, size_of::<T>())
+}
+
+static VIRTIO_BLKS: Mutex<Vec<Arc<VirtioBlk>>> = Mutex::new(Vec::new());
+
+fn find_blk(hd: usize) -> Option<Arc<VirtioBlk>> {
+    VIRTIO_BLKS.lock().get(hd).cloned()
+}
+
+pub fn init() {
+    let mut list = PCI_DEVICE_LINKEDLIST.write();
+    let mut devices = get_pci_device_structure_mut(&mut list, 0x01, 0x00);
+
+    let mut blks = VIRTIO_BLKS.lock();
+
+    for device in devices.iter_mut() {
+        let header = device.common_header();
+        if header.vendor_id != VIRTIO_VENDOR_ID
+            || !VIRTIO_BLK_DEVICE_IDS.contains(&header.device_id)
+        {
+            continue;
+        }
+        if let None = device.bar_init() {
+            continue;
+        }
+        device.as_mut().enable_master();
+
+        if let Some(blk) = device.as_standard_device().and_then(VirtioBlk::new) {
+            log::info!(
+                "virtio-blk: {} sectors, flush: {}, discard: {}",
+                blk.capacity,
+                blk.flush,
+                blk.discard
+            );
+            blks.push(Arc::new(blk));
+        }
+    }
+
+    log::info!("virtio-blk: found {} devices", blks.len());
+}
+
+pub fn read_block(hd: usize, start_sec: u64, buf: &mut [u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_blk(hd)?.read(start_sec, buf)
+}
+
+pub fn write_block(hd: usize, start_sec: u64, buf: &[u8]) -> Option<()> {
+    assert!(buf.len() % 512 == 0);
+    find_blk(hd)?.write(start_sec, buf)
+}
+
+pub fn flush(hd: usize) -> Option<()> {
+    find_blk(hd)?.flush()
+}
+
+pub fn discard(hd: usize, start_sec: u64, count: usize) -> Option<()> {
+    find_blk(hd)?.discard(start_sec, count)
+}
+
+pub fn get_hd_num() -> usize {
+    VIRTIO_BLKS.lock().len()
+}
+
+pub fn get_hd_size(hd: usize) -> Option<usize> {
+    Some(find_blk(hd)?.size())
+}
+
+pub fn get_hd_path(hd: usize) -> Option<String> {
+    find_blk(hd)?;
+    Some(format!("virtio{}", hd))
+}
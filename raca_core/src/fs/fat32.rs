@@ -77,6 +77,14 @@ impl Fat32Volume {
         ref_to_mut(&*inode_ref.read()).virtual_inodes.insert(".".into(), inode_ref.clone());
         inode_ref
     }
+
+    /// Read the volume serial number and the BPB label of the FAT volume on `dev`.
+    pub fn identify(dev: InodeRef) -> Option<(u32, String)> {
+        let io = InodeRefIO::new(dev);
+        let vol = FileSystem::new(io, FsOptions::new()).ok()?;
+        let label = String::from(vol.volume_label().trim_end());
+        Some((vol.volume_id(), label))
+    }
 }
 
 impl Inode for Fat32Volume {
@@ -135,6 +143,8 @@ impl Inode for Fat32Volume {
             InodeTy::File => {
                 self.vol.root_dir().create_file(name.as_str()).ok()?;
             }
+            // FAT has no special files
+            _ => return None,
         }
         self.open(name)
     }
@@ -238,6 +248,8 @@ impl Inode for Fat32Dir {
             InodeTy::File => {
                 self.dir.create_file(name.as_str()).ok()?;
             }
+            // FAT has no special files
+            _ => return None,
         }
         self.open(name)
     }
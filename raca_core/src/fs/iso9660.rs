@@ -0,0 +1,497 @@
+use alloc::{collections::BTreeMap, string::String, sync::Arc, vec, vec::Vec};
+use framework::ref_to_mut;
+use spin::RwLock;
+
+use super::{
+    operation::kernel_open,
+    vfs::inode::{FileInfo, Inode, InodeRef, InodeTy},
+};
+
+const SECTOR_SIZE: usize = 2048;
+const VOLUME_DESCRIPTOR_START: usize = 16;
+/// Volume descriptors looked at, so a set without a terminator can't loop
+/// forever
+const MAX_VOLUME_DESCRIPTORS: usize = 64;
+
+const VD_TYPE_PRIMARY: u8 = 1;
+const VD_TYPE_SUPPLEMENTARY: u8 = 2;
+const VD_TYPE_TERMINATOR: u8 = 255;
+
+const FLAG_DIRECTORY: u8 = 1 << 1;
+
+/// Offset of the root directory record in a volume descriptor
+const ROOT_RECORD_OFFSET: usize = 156;
+const ROOT_RECORD_LEN: usize = 34;
+/// A directory record up to the first byte of its name
+const RECORD_HEADER_LEN: usize = 33;
+
+/// Largest directory that is read, anything past it is left out
+const MAX_DIR_SIZE: usize = 16 * 1024 * 1024;
+/// Continuation areas followed for one name, so a "CE" entry pointing back
+/// at itself can't loop forever
+const MAX_CONTINUATIONS: usize = 16;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum NameEncoding {
+    /// d-characters with a ";1" version suffix
+    Plain,
+    /// UCS-2 big endian names from a Joliet supplementary descriptor
+    Joliet,
+    /// Alternate names from Rock Ridge "NM" entries
+    RockRidge,
+}
+
+#[derive(Clone)]
+struct DirectoryRecord {
+    extent: usize,
+    size: usize,
+    flags: u8,
+    name: String,
+}
+
+impl DirectoryRecord {
+    fn is_dir(&self) -> bool {
+        self.flags & FLAG_DIRECTORY != 0
+    }
+
+    fn ty(&self) -> InodeTy {
+        if self.is_dir() {
+            InodeTy::Dir
+        } else {
+            InodeTy::File
+        }
+    }
+}
+
+/// A mounted ISO9660 volume, shared by all of its directory and file inodes.
+pub struct Iso9660Volume {
+    dev: InodeRef,
+    encoding: NameEncoding,
+    /// Bytes to skip at the start of each System Use area (from the "SP" entry)
+    susp_skip: usize,
+}
+
+impl Iso9660Volume {
+    /// Check whether `dev` carries an ISO9660 volume.
+    pub fn probe(dev: &InodeRef) -> bool {
+        let mut buf = [0; SECTOR_SIZE];
+        read_volume_descriptor(dev, 0, &mut buf)
+    }
+
+    /// Read the creation time stamp and the volume identifier of the primary
+    /// volume descriptor. The time stamp is formatted the way blkid reports it
+    /// as the UUID of ISO9660 volumes.
+    pub fn identify(dev: &InodeRef) -> Option<(String, String)> {
+        let mut buf = [0; SECTOR_SIZE];
+        for idx in 0..MAX_VOLUME_DESCRIPTORS {
+            if !read_volume_descriptor(dev, idx, &mut buf) || buf[0] == VD_TYPE_TERMINATOR {
+                return None;
+            }
+            if buf[0] == VD_TYPE_PRIMARY {
+                break;
+            }
+        }
+        if buf[0] != VD_TYPE_PRIMARY {
+            return None;
+        }
+
+        let label = String::from_utf8_lossy(&buf[40..72]).trim_end().into();
+
+        // "YYYYMMDDHHMMSSCC" followed by the time zone offset
+        let date = &buf[813..829];
+        if !date.iter().all(u8::is_ascii_digit) {
+            return Some((String::new(), label));
+        }
+        let date = core::str::from_utf8(date).unwrap();
+        let uuid = alloc::format!(
+            "{}-{}-{}-{}-{}-{}-{}",
+            &date[0..4],
+            &date[4..6],
+            &date[6..8],
+            &date[8..10],
+            &date[10..12],
+            &date[12..14],
+            &date[14..16]
+        );
+        Some((uuid, label))
+    }
+
+    /// Open the volume on `dev` and return its root directory.
+    pub fn new(dev: InodeRef) -> Option<InodeRef> {
+        if !Self::probe(&dev) {
+            return None;
+        }
+
+        let mut primary_root = None;
+        let mut joliet_root = None;
+
+        let mut buf = [0; SECTOR_SIZE];
+        for idx in 0..MAX_VOLUME_DESCRIPTORS {
+            if !read_volume_descriptor(&dev, idx, &mut buf) {
+                break;
+            }
+
+            let root = &buf[ROOT_RECORD_OFFSET..ROOT_RECORD_OFFSET + ROOT_RECORD_LEN];
+            match buf[0] {
+                VD_TYPE_PRIMARY => primary_root = parse_record(root),
+                VD_TYPE_SUPPLEMENTARY => {
+                    // Joliet is marked by the UCS-2 level 1, 2 or 3 escape sequence
+                    let escape = &buf[88..91];
+                    if escape == b"%/@" || escape == b"%/C" || escape == b"%/E" {
+                        joliet_root = parse_record(root);
+                    }
+                }
+                VD_TYPE_TERMINATOR => break,
+                _ => {}
+            }
+        }
+
+        let primary_root = primary_root?;
+
+        let mut volume = Self {
+            dev,
+            encoding: NameEncoding::Plain,
+            susp_skip: 0,
+        };
+
+        let root = if let Some(susp_skip) = volume.detect_rock_ridge(&primary_root) {
+            volume.encoding = NameEncoding::RockRidge;
+            volume.susp_skip = susp_skip;
+            primary_root
+        } else if let Some(joliet_root) = joliet_root {
+            volume.encoding = NameEncoding::Joliet;
+            joliet_root
+        } else {
+            primary_root
+        };
+
+        Some(Iso9660Dir::new(Arc::new(volume), root))
+    }
+
+    fn read(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.dev.read().read_at(offset, buf)
+    }
+
+    fn read_extent(&self, extent: usize, size: usize) -> Vec<u8> {
+        let mut buf = vec![0; size.div_ceil(SECTOR_SIZE) * SECTOR_SIZE];
+        if !buf.is_empty() {
+            self.read(extent * SECTOR_SIZE, &mut buf);
+        }
+        buf
+    }
+
+    /// Rock Ridge volumes put an "SP" entry into the "." record of the root.
+    fn detect_rock_ridge(&self, root: &DirectoryRecord) -> Option<usize> {
+        let data = self.read_extent(root.extent, SECTOR_SIZE);
+        let len = data[0] as usize;
+        if len <= RECORD_HEADER_LEN {
+            return None;
+        }
+        let system_use = &data[system_use_offset(&data[..len])..len];
+
+        if system_use.len() >= 7
+            && &system_use[0..2] == b"SP"
+            && system_use[4] == 0xbe
+            && system_use[5] == 0xef
+        {
+            Some(system_use[6] as usize)
+        } else {
+            None
+        }
+    }
+
+    fn read_dir(&self, dir: &DirectoryRecord) -> Vec<DirectoryRecord> {
+        let size = dir.size.min(MAX_DIR_SIZE);
+        let data = self.read_extent(dir.extent, size);
+        let mut records = Vec::new();
+
+        let mut offset = 0;
+        while offset < size {
+            let len = data[offset] as usize;
+            if len == 0 {
+                // records never cross a sector boundary, the rest is padding
+                offset = (offset / SECTOR_SIZE + 1) * SECTOR_SIZE;
+                continue;
+            }
+
+            let Some(raw) = data.get(offset..offset + len) else {
+                break;
+            };
+            offset += len;
+
+            let name_len = raw.get(32).copied().unwrap_or(0) as usize;
+            let Some(raw_name) = raw.get(RECORD_HEADER_LEN..RECORD_HEADER_LEN + name_len) else {
+                // too short for its own name, a broken record
+                continue;
+            };
+            // "." and ".." are recorded as 0x00 and 0x01
+            if raw_name == [0] || raw_name == [1] {
+                continue;
+            }
+
+            if let Some(mut record) = parse_record(raw) {
+                match self.encoding {
+                    NameEncoding::Plain => record.name = plain_name(raw_name),
+                    NameEncoding::Joliet => record.name = joliet_name(raw_name),
+                    NameEncoding::RockRidge => {
+                        let system_use = &raw[system_use_offset(raw)..];
+                        record.name = self
+                            .rock_ridge_name(system_use.get(self.susp_skip..).unwrap_or(&[]))
+                            .unwrap_or_else(|| plain_name(raw_name));
+                    }
+                }
+                records.push(record);
+            }
+        }
+
+        records
+    }
+
+    /// Collect the alternate name from the "NM" entries of a System Use area,
+    /// following "CE" continuation areas.
+    fn rock_ridge_name(&self, system_use: &[u8]) -> Option<String> {
+        let mut name = Vec::new();
+        let mut found = false;
+
+        let mut area = system_use.to_vec();
+        for _ in 0..=MAX_CONTINUATIONS {
+            let mut continuation = None;
+            let mut offset = 0;
+
+            while offset + 4 <= area.len() {
+                let signature = &area[offset..offset + 2];
+                let len = area[offset + 2] as usize;
+                if len < 4 || offset + len > area.len() {
+                    break;
+                }
+                let entry = &area[offset..offset + len];
+
+                match signature {
+                    b"NM" if len >= 5 => {
+                        // skip the "." and ".." flavours
+                        if entry[4] & 0b110 == 0 {
+                            name.extend_from_slice(&entry[5..]);
+                            found = true;
+                        }
+                    }
+                    b"CE" if len >= 28 => {
+                        let block = u32::from_le_bytes(entry[4..8].try_into().unwrap()) as usize;
+                        let start = u32::from_le_bytes(entry[12..16].try_into().unwrap()) as usize;
+                        let size = u32::from_le_bytes(entry[20..24].try_into().unwrap()) as usize;
+                        continuation = Some((block, start, size));
+                    }
+                    b"ST" => break,
+                    _ => {}
+                }
+                offset += len;
+            }
+
+            match continuation {
+                // a continuation area lies within one logical block
+                Some((block, start, size)) if start.saturating_add(size) <= SECTOR_SIZE => {
+                    let mut next = vec![0; size];
+                    self.read(block * SECTOR_SIZE + start, &mut next);
+                    area = next;
+                }
+                _ => break,
+            }
+        }
+
+        if found {
+            String::from_utf8(name).ok()
+        } else {
+            None
+        }
+    }
+
+    fn name_matches(&self, record: &DirectoryRecord, name: &str) -> bool {
+        match self.encoding {
+            // plain names are upper case only
+            NameEncoding::Plain => record.name.eq_ignore_ascii_case(name),
+            _ => record.name == name,
+        }
+    }
+}
+
+/// Read volume descriptor `idx` of `dev` into `buf`. False if it is past the
+/// end of the device or not a volume descriptor.
+fn read_volume_descriptor(dev: &InodeRef, idx: usize, buf: &mut [u8; SECTOR_SIZE]) -> bool {
+    let offset = (VOLUME_DESCRIPTOR_START + idx) * SECTOR_SIZE;
+    let dev = dev.read();
+    if offset + SECTOR_SIZE > dev.size() {
+        return false;
+    }
+    dev.read_at(offset, buf) == SECTOR_SIZE && &buf[1..6] == b"CD001"
+}
+
+fn parse_record(raw: &[u8]) -> Option<DirectoryRecord> {
+    if raw.len() < RECORD_HEADER_LEN || raw[0] as usize > raw.len() {
+        return None;
+    }
+    Some(DirectoryRecord {
+        extent: u32::from_le_bytes(raw[2..6].try_into().unwrap()) as usize,
+        size: u32::from_le_bytes(raw[10..14].try_into().unwrap()) as usize,
+        flags: raw[25],
+        name: String::new(),
+    })
+}
+
+/// The System Use area follows the name, padded to an even offset.
+fn system_use_offset(raw: &[u8]) -> usize {
+    let name_len = raw.get(32).copied().unwrap_or(0) as usize;
+    let offset = RECORD_HEADER_LEN + name_len;
+    (offset + offset % 2).min(raw.len())
+}
+
+fn plain_name(raw: &[u8]) -> String {
+    let mut name = String::from_utf8_lossy(raw).into_owned();
+    if let Some(idx) = name.find(';') {
+        name.truncate(idx);
+    }
+    if name.ends_with('.') {
+        name.pop();
+    }
+    name
+}
+
+fn joliet_name(raw: &[u8]) -> String {
+    let units = raw
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]));
+    let mut name: String = char::decode_utf16(units)
+        .map(|ch| ch.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect();
+    if let Some(idx) = name.find(';') {
+        name.truncate(idx);
+    }
+    name
+}
+
+pub struct Iso9660Dir {
+    volume: Arc<Iso9660Volume>,
+    record: DirectoryRecord,
+    path: String,
+    virtual_inodes: BTreeMap<String, InodeRef>,
+}
+
+impl Iso9660Dir {
+    fn new(volume: Arc<Iso9660Volume>, record: DirectoryRecord) -> InodeRef {
+        let inode = Self {
+            volume,
+            record,
+            path: String::new(),
+            virtual_inodes: BTreeMap::new(),
+        };
+        let inode_ref = Arc::new(RwLock::new(inode));
+        ref_to_mut(&*inode_ref.read())
+            .virtual_inodes
+            .insert(".".into(), inode_ref.clone());
+        inode_ref
+    }
+}
+
+impl Inode for Iso9660Dir {
+    fn when_mounted(&mut self, path: String, father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+        if let Some(father) = father {
+            self.virtual_inodes.insert("..".into(), father);
+        }
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn mount(&self, node: InodeRef, name: String) {
+        ref_to_mut(self).virtual_inodes.insert(name, node);
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        if let Some(inode) = self.virtual_inodes.get(&name) {
+            return Some(inode.clone());
+        }
+
+        let self_inode = kernel_open(self.get_path());
+
+        let record = self
+            .volume
+            .read_dir(&self.record)
+            .into_iter()
+            .find(|record| self.volume.name_matches(record, name.as_str()))?;
+
+        if record.is_dir() {
+            let inode = Iso9660Dir::new(self.volume.clone(), record);
+            inode
+                .write()
+                .when_mounted(self.get_path() + name.as_str() + "/", self_inode);
+            Some(inode)
+        } else {
+            let inode = Arc::new(RwLock::new(Iso9660File::new(self.volume.clone(), record)));
+            inode
+                .write()
+                .when_mounted(self.get_path() + name.as_str(), self_inode);
+            Some(inode)
+        }
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        let mut vec = Vec::new();
+        for (name, inode) in self.virtual_inodes.iter() {
+            vec.push(FileInfo::new(name.clone(), inode.read().inode_type()));
+        }
+        for record in self.volume.read_dir(&self.record) {
+            vec.push(FileInfo::new(record.name.clone(), record.ty()));
+        }
+        vec
+    }
+}
+
+pub struct Iso9660File {
+    volume: Arc<Iso9660Volume>,
+    record: DirectoryRecord,
+    path: String,
+}
+
+impl Iso9660File {
+    fn new(volume: Arc<Iso9660Volume>, record: DirectoryRecord) -> Self {
+        Self {
+            volume,
+            record,
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for Iso9660File {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.record.size
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        if offset >= self.record.size {
+            return 0;
+        }
+        let len = buf.len().min(self.record.size - offset);
+        self.volume
+            .read(self.record.extent * SECTOR_SIZE + offset, &mut buf[..len]);
+        len
+    }
+}
@@ -1,5 +1,10 @@
-use alloc::string::ToString;
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
 use fat32::Fat32Volume;
+use iso9660::Iso9660Volume;
 use limine::request::KernelFileRequest;
 use spin::{Lazy, Mutex};
 use uuid::Uuid;
@@ -11,6 +16,7 @@ use vfs::{
 
 //mod ext2;
 mod fat32;
+mod iso9660;
 pub mod operation;
 pub mod vfs;
 
@@ -25,6 +31,132 @@ pub fn get_root_partition_uuid() -> Uuid {
     Uuid::from(kernel_file_response.file().gpt_partition_id().unwrap())
 }
 
+/// The command line the bootloader passed along with the kernel.
+pub fn get_kernel_cmdline() -> &'static str {
+    KERNEL_FILE_REQUEST
+        .get_response()
+        .and_then(|response| core::str::from_utf8(response.file().cmdline()).ok())
+        .unwrap_or("")
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FileSystemType {
+    /// Pick the filesystem by probing the device
+    Auto = 0,
+    Fat32 = 1,
+    Iso9660 = 2,
+}
+
+impl FileSystemType {
+    pub fn from_id(id: usize) -> Option<Self> {
+        match id {
+            0 => Some(Self::Auto),
+            1 => Some(Self::Fat32),
+            2 => Some(Self::Iso9660),
+            _ => None,
+        }
+    }
+
+    /// Name as shown in `/proc/mounts`
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Auto => "auto",
+            Self::Fat32 => "vfat",
+            Self::Iso9660 => "iso9660",
+        }
+    }
+}
+
+/// A line of `/proc/mounts`
+#[derive(Clone)]
+pub struct MountEntry {
+    pub source: String,
+    pub target: String,
+    pub fs_type: &'static str,
+    /// The node the filesystem reads from, for disk-backed filesystems
+    pub device: Option<InodeRef>,
+}
+
+pub static MOUNTS: Mutex<Vec<MountEntry>> = Mutex::new(Vec::new());
+
+/// Note a mount for `/proc/mounts`. Paths are taken as the VFS reports them,
+/// with the trailing slash dropped.
+pub fn record_mount(
+    source: String,
+    target: String,
+    fs_type: &'static str,
+    device: Option<InodeRef>,
+) {
+    fn trim(mut path: String) -> String {
+        while path.len() > 1 && path.ends_with('/') {
+            path.pop();
+        }
+        path
+    }
+
+    MOUNTS.lock().push(MountEntry {
+        source: trim(source),
+        target: trim(target),
+        fs_type,
+        device,
+    });
+}
+
+/// What a filesystem records about itself, used for persistent device names
+pub struct VolumeInfo {
+    pub fs_type: FileSystemType,
+    pub uuid: Option<String>,
+    pub label: Option<String>,
+}
+
+/// Find out which filesystem `dev` carries, along with its UUID and label.
+pub fn probe_volume(dev: &InodeRef) -> Option<VolumeInfo> {
+    fn non_empty(s: String) -> Option<String> {
+        if s.is_empty() {
+            None
+        } else {
+            Some(s)
+        }
+    }
+
+    if let Some((uuid, label)) = Iso9660Volume::identify(dev) {
+        return Some(VolumeInfo {
+            fs_type: FileSystemType::Iso9660,
+            uuid: non_empty(uuid),
+            label: non_empty(label),
+        });
+    }
+
+    if let Some((volume_id, label)) = Fat32Volume::identify(dev.clone()) {
+        let uuid = format!("{:04X}-{:04X}", volume_id >> 16, volume_id & 0xffff);
+        return Some(VolumeInfo {
+            fs_type: FileSystemType::Fat32,
+            uuid: Some(uuid),
+            label: non_empty(label).filter(|label| label != "NO NAME"),
+        });
+    }
+
+    None
+}
+
+/// Settle the filesystem type to open `dev` with. Anything that isn't
+/// ISO9660 is taken to be FAT32.
+pub fn resolve_fs_type(dev: &InodeRef, ty: FileSystemType) -> FileSystemType {
+    match ty {
+        FileSystemType::Auto if Iso9660Volume::probe(dev) => FileSystemType::Iso9660,
+        FileSystemType::Auto => FileSystemType::Fat32,
+        ty => ty,
+    }
+}
+
+/// Open the filesystem on `dev` and return its root directory.
+pub fn open_volume(dev: InodeRef, ty: FileSystemType) -> Option<InodeRef> {
+    match resolve_fs_type(&dev, ty) {
+        FileSystemType::Iso9660 => Iso9660Volume::new(dev),
+        _ => Some(Fat32Volume::new(dev)),
+    }
+}
+
 pub fn init() {
     ROOT.lock().write().when_mounted("/".to_string(), None);
 
@@ -40,4 +172,20 @@ pub fn init() {
     root_fs.write().when_mounted("/".to_string(), None);
     dev_fs.write().when_umounted();
     mount_to(dev_fs.clone(), root_fs.clone(), "dev".to_string());
+
+    let proc_fs = vfs::proc::ProcFS::new();
+    mount_to(proc_fs, root_fs.clone(), "proc".to_string());
+
+    let tmp_fs = vfs::tmpfs::TmpDir::new();
+    mount_to(tmp_fs, root_fs.clone(), "tmp".to_string());
+
+    record_mount(
+        root_partition.read().get_path(),
+        "/".to_string(),
+        "vfat",
+        Some(root_partition.clone()),
+    );
+    record_mount("devfs".to_string(), "/dev".to_string(), "devfs", None);
+    record_mount("proc".to_string(), "/proc".to_string(), "proc", None);
+    record_mount("tmpfs".to_string(), "/tmp".to_string(), "tmpfs", None);
 }
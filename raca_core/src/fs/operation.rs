@@ -4,15 +4,23 @@ use alloc::{collections::BTreeMap, string::String, sync::Arc, vec::Vec};
 use framework::{ref_to_mut, task::process::ProcessId};
 use spin::{Mutex, RwLock};
 
-use crate::user::get_current_process_id;
+use crate::user::{
+    get_current_process_id,
+    mmap::{PROT_READ, PROT_WRITE},
+};
 
 use super::{
-    fat32::Fat32Volume,
+    open_volume, probe_volume, record_mount, resolve_fs_type,
     vfs::{
+        dev::{
+            gpt_editor::GptTable, is_disk_mounted, is_partitioned_disk, loop_device,
+            partition::PartitionInfo, persistent::resolve_spec, rescan_disk,
+        },
         inode::{mount_to, FileInfo, InodeRef, InodeTy},
-        pipe::Pipe,
+        ipc, pipe, shm,
+        socket::{PassedFile, Socket},
     },
-    ROOT,
+    FileSystemType, ROOT,
 };
 
 static FILE_DESCRIPTOR_MANAGERS: Mutex<BTreeMap<ProcessId, Arc<FileDescriptorManager>>> =
@@ -73,11 +81,22 @@ fn get_file_descriptor_manager<'a>() -> Option<Arc<FileDescriptorManager>> {
     FILE_DESCRIPTOR_MANAGERS.lock().get_mut(&pid).cloned()
 }
 
+pub fn has_file_descriptor_manager(pid: ProcessId) -> bool {
+    FILE_DESCRIPTOR_MANAGERS.lock().contains_key(&pid)
+}
+
 pub fn init_file_descriptor_manager(pid: ProcessId) {
     let mut file_descriptor_managers = FILE_DESCRIPTOR_MANAGERS.lock();
     file_descriptor_managers.insert(pid, Arc::new(FileDescriptorManager::new(BTreeMap::new())));
 }
 
+/// Close every file of process `pid`, once it exits.
+pub fn release_file_descriptor_manager(pid: ProcessId) {
+    let file_descriptor_manager = FILE_DESCRIPTOR_MANAGERS.lock().remove(&pid);
+    // closing pipe ends wakes other threads, don't hold the table meanwhile
+    drop(file_descriptor_manager);
+}
+
 pub fn init_file_descriptor_manager_with_stdin_stdout(
     pid: ProcessId,
     stdin: InodeRef,
@@ -112,6 +131,25 @@ fn get_inode_by_path(path: String) -> Option<InodeRef> {
     Some(node.clone())
 }
 
+/// The files process `pid` has open, by descriptor.
+pub fn file_descriptors_of(pid: ProcessId) -> Vec<(FileDescriptor, InodeRef)> {
+    let Some(file_descriptor_manager) = FILE_DESCRIPTOR_MANAGERS.lock().get(&pid).cloned() else {
+        return Vec::new();
+    };
+    file_descriptor_manager
+        .file_descriptors
+        .iter()
+        .map(|(fd, (inode, _, _))| (*fd, inode.clone()))
+        .collect()
+}
+
+/// The working directory of process `pid`.
+pub fn cwd_of(pid: ProcessId) -> Option<InodeRef> {
+    let file_descriptor_manager = FILE_DESCRIPTOR_MANAGERS.lock().get(&pid).cloned()?;
+    let cwd = file_descriptor_manager.cwd.lock().clone();
+    Some(cwd)
+}
+
 pub fn kernel_open(path: String) -> Option<InodeRef> {
     get_inode_by_path(path)
 }
@@ -139,6 +177,9 @@ pub fn open(path: String, open_mode: OpenMode) -> Option<usize> {
         ))?
     };
 
+    let end = inode.read().open_end(matches!(open_mode, OpenMode::Write));
+    let inode = end.unwrap_or(inode);
+
     let file_descriptor = current_file_descriptor_manager.add_inode(inode, open_mode);
 
     Some(file_descriptor)
@@ -214,13 +255,11 @@ pub fn open_pipe(buffer: &mut [usize]) -> Option<()> {
 
     let current_file_descriptor_manager = get_file_descriptor_manager()?;
 
-    let inode = Arc::new(RwLock::new(Pipe::new()));
+    let (reader, writer) = pipe::pipe();
 
-    let file_descriptor_read =
-        current_file_descriptor_manager.add_inode(inode.clone(), OpenMode::Read);
+    let file_descriptor_read = current_file_descriptor_manager.add_inode(reader, OpenMode::Read);
 
-    let file_descriptor_write =
-        current_file_descriptor_manager.add_inode(inode.clone(), OpenMode::Write);
+    let file_descriptor_write = current_file_descriptor_manager.add_inode(writer, OpenMode::Write);
 
     buffer[0] = file_descriptor_read;
     buffer[1] = file_descriptor_write;
@@ -301,6 +340,122 @@ pub fn create(path: String, ty: InodeTy) -> Option<FileDescriptor> {
     }
 }
 
+/// Open the message queue `/dev/mq/<name>`, creating it if it doesn't exist
+/// and `max_message_size` is given.
+pub fn mq_open(name: String, max_message_size: usize, capacity: usize) -> Option<FileDescriptor> {
+    let queue = ipc::open_queue(name, max_message_size, capacity)?;
+    Some(get_file_descriptor_manager()?.add_inode(queue, OpenMode::Write))
+}
+
+pub fn mq_unlink(name: String) -> Option<()> {
+    ipc::unlink_queue(name)
+}
+
+/// Open the shared memory object `/dev/shm/<name>`, creating it with `size`
+/// bytes if it doesn't exist and `size` is given.
+pub fn shm_open(name: String, size: usize) -> Option<FileDescriptor> {
+    let object = shm::open_object(name, size)?;
+    Some(get_file_descriptor_manager()?.add_inode(object, OpenMode::Write))
+}
+
+pub fn shm_unlink(name: String) -> Option<()> {
+    shm::unlink_object(name)
+}
+
+/// Create a named pipe at `path`. The directory has to support it, which
+/// the in-memory one at `/tmp` does.
+pub fn mkfifo(path: String) -> Option<()> {
+    let path = if path.starts_with("/") {
+        path
+    } else {
+        alloc::format!("{}{}", get_file_descriptor_manager()?.get_cwd(), path)
+    };
+    let (parent_path, name) = path.trim_end_matches('/').rsplit_once('/')?;
+    let parent = get_inode_by_path(alloc::format!("{}/", parent_path))?;
+    parent.read().create(name.into(), InodeTy::Fifo)?;
+    Some(())
+}
+
+/// Make a new, unbound socket.
+pub fn socket() -> Option<FileDescriptor> {
+    let socket = Arc::new(RwLock::new(Socket::new()));
+    Some(get_file_descriptor_manager()?.add_inode(socket, OpenMode::Write))
+}
+
+/// Bind the socket `fd` to a new socket file at `path`.
+pub fn bind(fd: FileDescriptor, path: String) -> Option<()> {
+    let path = if path.starts_with("/") {
+        path
+    } else {
+        alloc::format!("{}{}", get_file_descriptor_manager()?.get_cwd(), path)
+    };
+    let (parent_path, name) = path.trim_end_matches('/').rsplit_once('/')?;
+    let parent = get_inode_by_path(alloc::format!("{}/", parent_path))?;
+
+    let socket = get_inode_by_fd(fd)?;
+    let socket = socket.read();
+    socket.as_socket()?.bind(parent, name.into())
+}
+
+pub fn listen(fd: FileDescriptor, backlog: usize) -> Option<()> {
+    get_inode_by_fd(fd)?.read().as_socket()?.listen(backlog)
+}
+
+/// Wait for a connection on the listening socket `fd` and open it.
+pub fn accept(fd: FileDescriptor) -> Option<FileDescriptor> {
+    let connection = {
+        let socket = get_inode_by_fd(fd)?;
+        let socket = socket.read();
+        socket.as_socket()?.accept()?
+    };
+    Some(get_file_descriptor_manager()?.add_inode(connection, OpenMode::Write))
+}
+
+/// Connect the socket `fd` to the one bound at `path`.
+pub fn connect(fd: FileDescriptor, path: String) -> Option<()> {
+    let path = if path.starts_with("/") {
+        path
+    } else {
+        alloc::format!("{}{}", get_file_descriptor_manager()?.get_cwd(), path)
+    };
+    let file = get_inode_by_path(path)?;
+
+    let socket = get_inode_by_fd(fd)?;
+    let socket = socket.read();
+    socket.as_socket()?.connect(file)
+}
+
+/// Pass the file `fd` over the connected socket `socket_fd`. The other side
+/// gets it opened the same way.
+pub fn send_fd(socket_fd: FileDescriptor, fd: FileDescriptor) -> Option<()> {
+    let file = {
+        let current_file_descriptor_manager = get_file_descriptor_manager()?;
+        let (inode, mode, _) = current_file_descriptor_manager.file_descriptors.get(&fd)?;
+        PassedFile {
+            inode: inode.clone(),
+            writable: matches!(mode, OpenMode::Write),
+        }
+    };
+    let socket = get_inode_by_fd(socket_fd)?;
+    let socket = socket.read();
+    socket.as_socket()?.send_file(file)
+}
+
+/// Open the oldest file passed over the connected socket `socket_fd`.
+pub fn receive_fd(socket_fd: FileDescriptor) -> Option<FileDescriptor> {
+    let file = {
+        let socket = get_inode_by_fd(socket_fd)?;
+        let socket = socket.read();
+        socket.as_socket()?.receive_file()?
+    };
+    let mode = if file.writable {
+        OpenMode::Write
+    } else {
+        OpenMode::Read
+    };
+    Some(get_file_descriptor_manager()?.add_inode(file.inode, mode))
+}
+
 pub fn get_type(fd: FileDescriptor) -> Option<InodeTy> {
     if let Some(current_file_descriptor_manager) = get_file_descriptor_manager() {
         let (inode, _, _) = current_file_descriptor_manager.file_descriptors.get(&fd)?;
@@ -310,8 +465,133 @@ pub fn get_type(fd: FileDescriptor) -> Option<InodeTy> {
     }
 }
 
-pub fn mount(to: String, partition_path: String) -> Option<()> {
-    let partition_inode = get_inode_by_path(partition_path)?;
+pub fn ioctl(fd: FileDescriptor, cmd: usize, arg: usize) -> Option<usize> {
+    get_inode_by_fd(fd)?.read().ioctl(cmd, arg)
+}
+
+/// Map `len` bytes of the node behind `fd` into the current process. A
+/// `prot` of 0 maps it writable if the descriptor was opened for writing,
+/// asking for [`PROT_WRITE`] on a read only descriptor fails.
+pub fn mmap(fd: FileDescriptor, offset: usize, len: usize, prot: usize) -> Option<usize> {
+    let (inode, writable) = {
+        let current_file_descriptor_manager = get_file_descriptor_manager()?;
+        let (inode, mode, _) = current_file_descriptor_manager.file_descriptors.get(&fd)?;
+        (inode.clone(), matches!(mode, OpenMode::Write))
+    };
+
+    let prot = match prot {
+        0 if writable => PROT_READ | PROT_WRITE,
+        0 => PROT_READ,
+        prot if prot & PROT_WRITE != 0 && !writable => return None,
+        prot => prot,
+    };
+    let addr = crate::user::mmap::map(inode, offset, len, prot)?;
+    Some(addr.as_u64() as usize)
+}
+
+pub fn partition_info(fd: FileDescriptor) -> Option<PartitionInfo> {
+    get_inode_by_fd(fd)?.read().partition_info()
+}
+
+/// Read the GPT of the whole-disk node behind `fd`.
+pub fn read_gpt(fd: FileDescriptor) -> Option<GptTable> {
+    let disk = get_inode_by_fd(fd)?;
+    if !is_partitioned_disk(&disk) {
+        return None;
+    }
+    GptTable::load(disk)
+}
+
+/// Change the GPT of the whole-disk node behind `fd` and refresh its
+/// partition nodes. With `new_table` set, an empty table replaces whatever
+/// the disk held before.
+pub fn edit_gpt(
+    fd: FileDescriptor,
+    new_table: bool,
+    edit: impl FnOnce(&mut GptTable) -> Option<usize>,
+) -> Option<usize> {
+    let disk = {
+        let current_file_descriptor_manager = get_file_descriptor_manager()?;
+        let (inode, mode, _) = current_file_descriptor_manager.file_descriptors.get(&fd)?;
+        if !matches!(mode, OpenMode::Write) {
+            return None;
+        }
+        inode.clone()
+    };
+    // the filesystems on it would keep using the old partition bounds
+    if !is_partitioned_disk(&disk) || is_disk_mounted(&disk) {
+        return None;
+    }
+
+    let mut table = if new_table {
+        GptTable::create(disk.clone())?
+    } else {
+        GptTable::load(disk.clone())?
+    };
+
+    let ret = edit(&mut table)?;
+    table.write()?;
+    rescan_disk(&disk)?;
+    Some(ret)
+}
+
+/// Attach the file at `path` to a loop device and return its number.
+pub fn loop_attach(path: String) -> Option<usize> {
+    let path = if path.starts_with("/") {
+        path
+    } else {
+        alloc::format!("{}{}", get_file_descriptor_manager()?.get_cwd(), path)
+    };
+    loop_device::attach(get_inode_by_path(path)?)
+}
+
+pub fn loop_detach(id: usize) -> Option<()> {
+    loop_device::detach(id)
+}
+
+/// Open the filesystem on the node at `path`, returning its root directory,
+/// the device node it reads from and its type.
+///
+/// A regular file outside `/dev` is attached to a loop device first. If the
+/// image holds a partition table, the first partition carrying a filesystem
+/// is used. Mounts can't be undone, so the loop device stays attached until
+/// reboot; `losetup -d` refuses to detach it while it is mounted.
+fn open_partition_volume(
+    path: &str,
+    inode: InodeRef,
+    fs_type: FileSystemType,
+) -> Option<(InodeRef, InodeRef, FileSystemType)> {
+    if path.starts_with("/dev/") || inode.read().inode_type() != InodeTy::File {
+        let fs_type = resolve_fs_type(&inode, fs_type);
+        return Some((open_volume(inode.clone(), fs_type)?, inode, fs_type));
+    }
+
+    let id = loop_device::attach(inode)?;
+    // only nodes that carry a filesystem are candidates
+    let try_open = |dev: InodeRef| {
+        let fs_type = match fs_type {
+            FileSystemType::Auto => probe_volume(&dev)?.fs_type,
+            fs_type => {
+                probe_volume(&dev)?;
+                fs_type
+            }
+        };
+        Some((open_volume(dev.clone(), fs_type)?, dev, fs_type))
+    };
+
+    let volume = get_inode_by_path(alloc::format!("/dev/loop{}", id))
+        .and_then(try_open)
+        .or_else(|| loop_device::partitions(id).find_map(try_open));
+
+    if volume.is_none() {
+        loop_device::detach(id);
+    }
+    volume
+}
+
+pub fn mount(to: String, partition_path: String, fs_type: FileSystemType) -> Option<()> {
+    let partition_path = resolve_spec(partition_path);
+    let partition_inode = get_inode_by_path(partition_path.clone())?;
     let to_father_path = {
         let mut path = to.clone();
         if path.ends_with("/") {
@@ -338,7 +618,14 @@ pub fn mount(to: String, partition_path: String) -> Option<()> {
         name.chars().rev().collect()
     };
 
-    let volumne = Fat32Volume::new(partition_inode.clone());
-    mount_to(volumne, to_father, to_name);
+    let (volumne, device, fs_type) =
+        open_partition_volume(&partition_path, partition_inode.clone(), fs_type)?;
+    mount_to(volumne.clone(), to_father, to_name);
+    record_mount(
+        partition_path,
+        volumne.read().get_path(),
+        fs_type.name(),
+        Some(device),
+    );
     Some(())
 }
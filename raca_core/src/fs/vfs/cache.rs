@@ -63,6 +63,13 @@ impl<C: Cache, B: BlockDeviceInterface> CacheManager<C, B> {
         }
     }
 
+    /// Forget the cached blocks in `start_block..start_block + count` without
+    /// writing them back.
+    pub fn drop_cache(&mut self, start_block: usize, count: usize) {
+        self.caches
+            .retain(|block_id, _| !(start_block..start_block + count).contains(block_id));
+    }
+
     pub fn read_from_cache(&mut self, start_block: usize, buf: &mut [u8]) {
         let block_num = buf.len() / 512;
 
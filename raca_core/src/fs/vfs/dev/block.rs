@@ -1,5 +1,6 @@
 use alloc::{string::String, vec::Vec};
-use framework::ref_to_mut;
+use framework::{memory::addr_to_mut_ref, ref_to_mut};
+use x86_64::VirtAddr;
 
 use crate::{
     drivers::block::HD_LIST,
@@ -9,6 +10,10 @@ use crate::{
     },
 };
 
+/// Discard the byte range given by the `[offset, len]` pair of `u64` at
+/// `arg`. Both have to be multiples of 512.
+pub const BLKDISCARD: usize = 0x1277;
+
 struct BlockDevice {
     id: usize,
 }
@@ -21,11 +26,15 @@ impl BlockDevice {
 
 impl BlockDeviceInterface for BlockDevice {
     fn read(&self, block_id: usize, buf: &mut [u8]) {
-        HD_LIST.lock()[self.id].read_block(block_id, buf);
+        // the list must not stay locked while the device works, which may
+        // sleep
+        let device = HD_LIST.lock()[self.id].clone();
+        device.read_block(block_id, buf);
     }
 
     fn write(&self, block_id: usize, buf: &[u8]) {
-        HD_LIST.lock()[self.id].write_block(block_id, buf);
+        let device = HD_LIST.lock()[self.id].clone();
+        device.write_block(block_id, buf);
     }
 }
 
@@ -123,4 +132,32 @@ impl Inode for BlockInode {
 
         buf.len()
     }
+
+    fn flush(&self) {
+        ref_to_mut(self).cache_manager.flush_cache();
+        let device = HD_LIST.lock()[self.hd].clone();
+        device.flush();
+    }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            BLKDISCARD => {
+                let range: &mut [u64; 2] = addr_to_mut_ref(VirtAddr::new(arg as u64));
+                let [offset, len] = *range;
+                let (offset, len) = (offset as usize, len as usize);
+                if offset % 512 != 0 || len % 512 != 0 || offset.checked_add(len)? > self.size() {
+                    return None;
+                }
+
+                // the cache writes back every block it holds on the next
+                // flush, which would bring the discarded data back
+                let (start_sec, count) = (offset / 512, len / 512);
+                ref_to_mut(self).cache_manager.drop_cache(start_sec, count);
+                let device = HD_LIST.lock()[self.hd].clone();
+                device.discard(start_sec, count)?;
+                Some(1)
+            }
+            _ => None,
+        }
+    }
 }
@@ -0,0 +1,77 @@
+use alloc::string::String;
+
+use crate::{
+    drivers::rng,
+    fs::vfs::inode::{Inode, InodeRef},
+};
+
+/// Devices that only differ in how they answer reads and writes.
+#[derive(Clone, Copy)]
+pub enum CharDeviceKind {
+    /// Reads return end of file, writes are swallowed
+    Null,
+    /// Reads return zeros, writes are swallowed
+    Zero,
+    /// Reads return zeros, writes fail as if the disk were full
+    Full,
+    /// Reads return random bytes, writes are mixed into the entropy pool
+    Random,
+}
+
+pub struct CharDevice {
+    kind: CharDeviceKind,
+    path: String,
+}
+
+impl CharDevice {
+    pub fn new(kind: CharDeviceKind) -> Self {
+        Self {
+            kind,
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for CharDevice {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        match self.kind {
+            CharDeviceKind::Null => 0,
+            CharDeviceKind::Zero | CharDeviceKind::Full => {
+                buf.fill(0);
+                buf.len()
+            }
+            CharDeviceKind::Random => {
+                rng::fill_bytes(buf);
+                buf.len()
+            }
+        }
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        match self.kind {
+            CharDeviceKind::Null | CharDeviceKind::Zero => buf.len(),
+            CharDeviceKind::Full => 0,
+            CharDeviceKind::Random => {
+                for chunk in buf.chunks(8) {
+                    let mut sample = [0; 8];
+                    sample[..chunk.len()].copy_from_slice(chunk);
+                    rng::mix(u64::from_le_bytes(sample));
+                }
+                buf.len()
+            }
+        }
+    }
+
+    fn flush(&self) {}
+}
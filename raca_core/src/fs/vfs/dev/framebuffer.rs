@@ -0,0 +1,113 @@
+use alloc::string::String;
+use framework::{
+    memory::{addr_to_array, write_for_syscall, KERNEL_PAGE_TABLE},
+    ref_to_mut,
+};
+use x86_64::{structures::paging::Translate, PhysAddr, VirtAddr};
+
+use crate::fs::vfs::inode::{Inode, InodeRef};
+
+/// Fill a [`FramebufferInfo`] at the user address in `arg`.
+pub const FBIOGET_INFO: usize = 0x4600;
+
+/// 32 bits per pixel, `0x00RRGGBB` in native byte order
+pub const PIXEL_FORMAT_XRGB8888: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct FramebufferInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one line to the next
+    pub pitch: u32,
+    pub bpp: u32,
+    pub pixel_format: u32,
+    /// Bytes covered by the framebuffer, rounded up to whole pages
+    pub size: u32,
+}
+
+/// The boot framebuffer, as `/dev/fbN`.
+pub struct FramebufferInode {
+    buffer: &'static mut [u8],
+    info: FramebufferInfo,
+    path: String,
+}
+
+impl FramebufferInode {
+    pub fn new() -> Self {
+        let display = framework::drivers::display::Display::new();
+        let frame_buffer = display.get_frame_buffer();
+        let address = VirtAddr::new(frame_buffer.as_ptr() as u64);
+        let size = frame_buffer.len() * size_of::<u32>();
+
+        let info = FramebufferInfo {
+            width: display.width() as u32,
+            height: display.height() as u32,
+            pitch: display.pitch() as u32,
+            bpp: 32,
+            pixel_format: PIXEL_FORMAT_XRGB8888,
+            size: size.next_multiple_of(4096) as u32,
+        };
+
+        Self {
+            buffer: addr_to_array(address, size),
+            info,
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for FramebufferInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.buffer.len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let Some(src) = self.buffer.get(offset..) else {
+            return 0;
+        };
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        len
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let Some(dst) = ref_to_mut(self).buffer.get_mut(offset..) else {
+            return 0;
+        };
+        let len = buf.len().min(dst.len());
+        dst[..len].copy_from_slice(&buf[..len]);
+        len
+    }
+
+    fn flush(&self) {}
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            FBIOGET_INFO => {
+                write_for_syscall(VirtAddr::new(arg as u64), &[self.info]);
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn mmap(&self, offset: usize) -> Option<PhysAddr> {
+        if offset >= self.info.size as usize {
+            return None;
+        }
+        let address = VirtAddr::new(self.buffer.as_ptr() as u64 + offset as u64);
+        KERNEL_PAGE_TABLE.lock().translate_addr(address)
+    }
+}
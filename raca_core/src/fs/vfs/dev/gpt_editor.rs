@@ -0,0 +1,417 @@
+use alloc::{string::String, vec, vec::Vec};
+use uuid::Uuid;
+
+use crate::{drivers::rng, fs::vfs::inode::InodeRef};
+
+const SECTOR_SIZE: usize = 512;
+
+const HEADER_SIGNATURE: &[u8; 8] = b"EFI PART";
+const HEADER_REVISION: u32 = 0x0001_0000;
+const HEADER_SIZE: usize = 92;
+
+/// Layout used for new tables, the same one every common tool writes
+const DEFAULT_ENTRY_SIZE: usize = 128;
+const DEFAULT_NUM_ENTRIES: usize = 128;
+
+const MIN_ENTRY_SIZE: usize = 128;
+/// Keeps a corrupted header from asking for a huge entry array
+const MAX_ENTRY_ARRAY_SIZE: usize = 1024 * 1024;
+const NAME_OFFSET: usize = 56;
+const NAME_CHARS: usize = 36;
+
+/// New partitions start on 1 MiB boundaries
+const ALIGNMENT: u64 = 2048;
+
+/// GUIDs are stored with their first three fields little endian. Swapping
+/// them converts between that and the order they are printed in.
+fn swap_guid(guid: [u8; 16]) -> [u8; 16] {
+    let mut swapped = guid;
+    swapped[0..4].reverse();
+    swapped[4..6].reverse();
+    swapped[6..8].reverse();
+    swapped
+}
+
+/// A new version 4 GUID, `None` if the random numbers could repeat those of
+/// an earlier boot.
+fn random_guid() -> Option<Uuid> {
+    let mut bytes = [0; 16];
+    if rng::try_fill_bytes(&mut bytes).is_none() {
+        log::warn!("gpt: no RDRAND and too little entropy yet to make a GUID");
+        return None;
+    }
+    // version 4, variant 1
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    Some(Uuid::from_bytes(bytes))
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xedb8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// A partition as the editor hands it in and out
+#[derive(Clone, Debug)]
+pub struct GptEntry {
+    pub type_guid: Uuid,
+    pub unique_guid: Uuid,
+    pub first_lba: u64,
+    pub last_lba: u64,
+    pub attributes: u64,
+    pub name: String,
+}
+
+impl GptEntry {
+    fn parse(raw: &[u8]) -> Option<Self> {
+        let type_guid = swap_guid(raw[0..16].try_into().unwrap());
+        if type_guid == [0; 16] {
+            return None;
+        }
+
+        let name = raw[NAME_OFFSET..NAME_OFFSET + NAME_CHARS * 2]
+            .chunks_exact(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .take_while(|c| *c != 0);
+
+        Some(Self {
+            type_guid: Uuid::from_bytes(type_guid),
+            unique_guid: Uuid::from_bytes(swap_guid(raw[16..32].try_into().unwrap())),
+            first_lba: read_u64(raw, 32),
+            last_lba: read_u64(raw, 40),
+            attributes: read_u64(raw, 48),
+            name: char::decode_utf16(name)
+                .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+                .collect(),
+        })
+    }
+
+    fn serialize(&self, raw: &mut [u8]) {
+        raw.fill(0);
+        raw[0..16].copy_from_slice(&swap_guid(*self.type_guid.as_bytes()));
+        raw[16..32].copy_from_slice(&swap_guid(*self.unique_guid.as_bytes()));
+        raw[32..40].copy_from_slice(&self.first_lba.to_le_bytes());
+        raw[40..48].copy_from_slice(&self.last_lba.to_le_bytes());
+        raw[48..56].copy_from_slice(&self.attributes.to_le_bytes());
+
+        let name = raw[NAME_OFFSET..NAME_OFFSET + NAME_CHARS * 2].chunks_exact_mut(2);
+        for (dst, c) in name.zip(self.name.encode_utf16()) {
+            dst.copy_from_slice(&c.to_le_bytes());
+        }
+    }
+
+    fn overlaps(&self, first_lba: u64, last_lba: u64) -> bool {
+        self.first_lba <= last_lba && first_lba <= self.last_lba
+    }
+}
+
+/// An in-memory copy of a GUID partition table.
+///
+/// Nothing reaches the disk before `write`, which then updates the primary
+/// and the backup table together.
+pub struct GptTable {
+    disk: InodeRef,
+    disk_guid: Uuid,
+    /// LBA of the backup header, the last sector of the disk
+    backup_lba: u64,
+    primary_array_lba: u64,
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    entry_size: usize,
+    entries: Vec<Option<GptEntry>>,
+    /// Extra bytes of each entry beyond the fields known here
+    raw_entries: Vec<u8>,
+    /// Write a protective MBR as well, set for new tables
+    new_table: bool,
+}
+
+impl GptTable {
+    fn entry_array_sectors(entry_size: usize, num_entries: usize) -> u64 {
+        (entry_size * num_entries).div_ceil(SECTOR_SIZE) as u64
+    }
+
+    /// Start an empty table covering the whole disk.
+    pub fn create(disk: InodeRef) -> Option<Self> {
+        let sectors = (disk.read().size() / SECTOR_SIZE) as u64;
+        let array_sectors = Self::entry_array_sectors(DEFAULT_ENTRY_SIZE, DEFAULT_NUM_ENTRIES);
+
+        // MBR, two headers, two entry arrays and at least one sector of data
+        if sectors < 3 + 2 * array_sectors + 1 {
+            return None;
+        }
+
+        Some(Self {
+            disk,
+            disk_guid: random_guid()?,
+            backup_lba: sectors - 1,
+            primary_array_lba: 2,
+            first_usable_lba: 2 + array_sectors,
+            last_usable_lba: sectors - 2 - array_sectors,
+            entry_size: DEFAULT_ENTRY_SIZE,
+            entries: vec![None; DEFAULT_NUM_ENTRIES],
+            raw_entries: vec![0; DEFAULT_ENTRY_SIZE * DEFAULT_NUM_ENTRIES],
+            new_table: true,
+        })
+    }
+
+    /// Read the primary table of `disk`.
+    pub fn load(disk: InodeRef) -> Option<Self> {
+        let mut header = [0; SECTOR_SIZE];
+        disk.read().read_at(SECTOR_SIZE, &mut header);
+
+        if &header[0..8] != HEADER_SIGNATURE {
+            return None;
+        }
+        let header_size = read_u32(&header, 12) as usize;
+        if header_size < HEADER_SIZE || header_size > SECTOR_SIZE {
+            return None;
+        }
+        let mut check = header;
+        check[16..20].fill(0);
+        if crc32(&check[..header_size]) != read_u32(&header, 16) {
+            log::warn!("GPT: primary header checksum mismatch");
+            return None;
+        }
+
+        let entry_lba = read_u64(&header, 72);
+        let num_entries = read_u32(&header, 80) as usize;
+        let entry_size = read_u32(&header, 84) as usize;
+        if entry_size < MIN_ENTRY_SIZE
+            || !entry_size.is_power_of_two()
+            || entry_size * num_entries > MAX_ENTRY_ARRAY_SIZE
+        {
+            return None;
+        }
+
+        // the layout `write` keeps: the primary array before the usable
+        // sectors, the backup array after them and the backup header in the
+        // last sector
+        let sectors = (disk.read().size() / SECTOR_SIZE) as u64;
+        let array_sectors = Self::entry_array_sectors(entry_size, num_entries);
+        let backup_lba = read_u64(&header, 32);
+        let first_usable_lba = read_u64(&header, 40);
+        let last_usable_lba = read_u64(&header, 48);
+        let backup_array_lba = backup_lba.checked_sub(array_sectors)?;
+        if backup_lba.checked_add(1) != Some(sectors)
+            || entry_lba < 2
+            || entry_lba.checked_add(array_sectors)? > first_usable_lba
+            || first_usable_lba > last_usable_lba
+            || last_usable_lba >= backup_array_lba
+        {
+            log::warn!("GPT: primary header doesn't fit the disk");
+            return None;
+        }
+
+        let mut raw_entries = vec![0; entry_size * num_entries];
+        disk.read()
+            .read_at(entry_lba as usize * SECTOR_SIZE, &mut raw_entries);
+        if crc32(&raw_entries) != read_u32(&header, 88) {
+            log::warn!("GPT: partition entry array checksum mismatch");
+            return None;
+        }
+
+        let entries = raw_entries
+            .chunks_exact(entry_size)
+            .map(GptEntry::parse)
+            .collect();
+
+        Some(Self {
+            disk,
+            disk_guid: Uuid::from_bytes(swap_guid(header[56..72].try_into().unwrap())),
+            backup_lba,
+            primary_array_lba: entry_lba,
+            first_usable_lba,
+            last_usable_lba,
+            entry_size,
+            entries,
+            raw_entries,
+            new_table: false,
+        })
+    }
+
+    pub fn usable_range(&self) -> (u64, u64) {
+        (self.first_usable_lba, self.last_usable_lba)
+    }
+
+    pub fn num_entries(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn entry(&self, index: usize) -> Option<&GptEntry> {
+        self.entries.get(index)?.as_ref()
+    }
+
+    fn is_free(&self, first_lba: u64, last_lba: u64, ignore: Option<usize>) -> bool {
+        first_lba <= last_lba
+            && first_lba >= self.first_usable_lba
+            && last_lba <= self.last_usable_lba
+            && self.entries.iter().enumerate().all(|(idx, entry)| {
+                Some(idx) == ignore
+                    || entry
+                        .as_ref()
+                        .map_or(true, |entry| !entry.overlaps(first_lba, last_lba))
+            })
+    }
+
+    /// First aligned sector from which `sectors` free sectors follow.
+    /// A `sectors` of 0 asks for the largest free range instead.
+    pub fn find_free(&self, sectors: u64) -> Option<(u64, u64)> {
+        let mut used: Vec<(u64, u64)> = self
+            .entries
+            .iter()
+            .flatten()
+            .map(|entry| (entry.first_lba, entry.last_lba))
+            .collect();
+        used.sort();
+
+        let mut best: Option<(u64, u64)> = None;
+        let mut start = self.first_usable_lba;
+        for (first, last) in used.into_iter().chain([(self.last_usable_lba + 1, 0)]) {
+            let aligned = start.next_multiple_of(ALIGNMENT);
+            if aligned < first {
+                let free = (aligned, first - 1);
+                if sectors != 0 && free.1 - free.0 + 1 >= sectors {
+                    return Some((free.0, free.0 + sectors - 1));
+                }
+                if best.map_or(true, |best| free.1 - free.0 > best.1 - best.0) {
+                    best = Some(free);
+                }
+            }
+            start = start.max(last + 1);
+        }
+
+        if sectors == 0 {
+            best
+        } else {
+            None
+        }
+    }
+
+    /// Put a new partition into the first unused slot and return its index.
+    pub fn add(&mut self, mut entry: GptEntry) -> Option<usize> {
+        if entry.type_guid.is_nil() || !self.is_free(entry.first_lba, entry.last_lba, None) {
+            return None;
+        }
+        let index = self.entries.iter().position(Option::is_none)?;
+        if entry.unique_guid.is_nil() {
+            entry.unique_guid = random_guid()?;
+        }
+        self.entries[index] = Some(entry);
+        Some(index)
+    }
+
+    pub fn delete(&mut self, index: usize) -> Option<GptEntry> {
+        self.entries.get_mut(index)?.take()
+    }
+
+    /// Move the end of a partition. The data is left as it is, so the
+    /// filesystem inside has to be resized on its own.
+    pub fn resize(&mut self, index: usize, last_lba: u64) -> Option<()> {
+        let first_lba = self.entry(index)?.first_lba;
+        if !self.is_free(first_lba, last_lba, Some(index)) {
+            return None;
+        }
+        self.entries[index].as_mut()?.last_lba = last_lba;
+        Some(())
+    }
+
+    fn header(
+        &self,
+        my_lba: u64,
+        alternate_lba: u64,
+        entry_lba: u64,
+        array_crc: u32,
+    ) -> [u8; SECTOR_SIZE] {
+        let mut header = [0; SECTOR_SIZE];
+        header[0..8].copy_from_slice(HEADER_SIGNATURE);
+        header[8..12].copy_from_slice(&HEADER_REVISION.to_le_bytes());
+        header[12..16].copy_from_slice(&(HEADER_SIZE as u32).to_le_bytes());
+        header[24..32].copy_from_slice(&my_lba.to_le_bytes());
+        header[32..40].copy_from_slice(&alternate_lba.to_le_bytes());
+        header[40..48].copy_from_slice(&self.first_usable_lba.to_le_bytes());
+        header[48..56].copy_from_slice(&self.last_usable_lba.to_le_bytes());
+        header[56..72].copy_from_slice(&swap_guid(*self.disk_guid.as_bytes()));
+        header[72..80].copy_from_slice(&entry_lba.to_le_bytes());
+        header[80..84].copy_from_slice(&(self.entries.len() as u32).to_le_bytes());
+        header[84..88].copy_from_slice(&(self.entry_size as u32).to_le_bytes());
+        header[88..92].copy_from_slice(&array_crc.to_le_bytes());
+
+        let crc = crc32(&header[..HEADER_SIZE]);
+        header[16..20].copy_from_slice(&crc.to_le_bytes());
+        header
+    }
+
+    fn protective_mbr(&self) -> [u8; SECTOR_SIZE] {
+        let mut mbr = [0; SECTOR_SIZE];
+        let entry = &mut mbr[446..462];
+        // CHS fields as written by other tools, they are ignored anyway
+        entry[2] = 0x02;
+        entry[4] = 0xee;
+        entry[5..8].fill(0xff);
+        entry[8..12].copy_from_slice(&1u32.to_le_bytes());
+        let sectors = self.backup_lba.min(u32::MAX as u64) as u32;
+        entry[12..16].copy_from_slice(&sectors.to_le_bytes());
+        mbr[510] = 0x55;
+        mbr[511] = 0xaa;
+        mbr
+    }
+
+    /// Write both copies of the table. The backup goes first, so an
+    /// interrupted write leaves a table the primary can be restored from.
+    pub fn write(&mut self) -> Option<()> {
+        for (entry, raw) in self
+            .entries
+            .iter()
+            .zip(self.raw_entries.chunks_exact_mut(self.entry_size))
+        {
+            match entry {
+                Some(entry) => entry.serialize(&mut raw[..MIN_ENTRY_SIZE]),
+                None => raw.fill(0),
+            }
+        }
+        let array_crc = crc32(&self.raw_entries);
+
+        let array_sectors = Self::entry_array_sectors(self.entry_size, self.entries.len());
+        let primary_array_lba = self.primary_array_lba;
+        let backup_array_lba = self.backup_lba.checked_sub(array_sectors)?;
+
+        let primary = self.header(1, self.backup_lba, primary_array_lba, array_crc);
+        let backup = self.header(self.backup_lba, 1, backup_array_lba, array_crc);
+
+        let disk = self.disk.read();
+        let write = |lba: u64, buf: &[u8]| {
+            if disk.write_at(lba as usize * SECTOR_SIZE, buf) == buf.len() {
+                Some(())
+            } else {
+                None
+            }
+        };
+
+        write(backup_array_lba, &self.raw_entries)?;
+        write(self.backup_lba, &backup)?;
+        write(primary_array_lba, &self.raw_entries)?;
+        write(1, &primary)?;
+        if self.new_table {
+            write(0, &self.protective_mbr())?;
+        }
+        disk.flush();
+
+        self.new_table = false;
+        Some(())
+    }
+}
@@ -4,7 +4,10 @@ use crate::alloc::string::ToString;
 use crate::fs::{
     get_root_partition_uuid,
     vfs::{
-        dev::{partition::PartitionInode, ROOT_PARTITION},
+        dev::{
+            partition::{PartitionInfo, PartitionInode, PartitionType},
+            persistent, ROOT_PARTITION,
+        },
         inode::{mount_to, InodeRef},
     },
 };
@@ -60,9 +63,11 @@ impl BlockIo for InodeRefIO {
     }
 }
 
+/// Publish the partitions of a GPT disk as `{name_prefix}{slot}`.
 pub fn parse_gpt_disk(
-    disk_id: usize,
+    name_prefix: &str,
     disk: InodeRef,
+    disk_path: Option<&str>,
     dev_fs: InodeRef,
 ) -> Result<(), DiskError<usize>> {
     let io = InodeRefIO::new(disk.clone());
@@ -75,6 +80,9 @@ pub fn parse_gpt_disk(
     let buf = buf.leak();
 
     let header = gpt.read_gpt_header(Lba(1), buf)?;
+    if !header.is_signature_valid() {
+        return Err(DiskError::Io(0));
+    }
 
     let mut buf = Vec::new();
     for _ in 0..512 * 8 * 100 {
@@ -93,32 +101,51 @@ pub fn parse_gpt_disk(
         buf,
     )?;
 
-    let id_to_alpha = [
-        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r",
-        "s", "t", "u", "v", "w", "x", "y", "z",
-    ];
-
     let root_partition_uuid = get_root_partition_uuid();
 
     for (partition_id, part) in part_iter.enumerate() {
         if let Ok(part) = part {
+            // unused entries may be followed by used ones
             if !part.is_used() {
-                break;
+                continue;
             }
-            let start_offset = part.starting_lba.to_u64() as usize * 512;
-            let size = part.ending_lba.to_u64() as usize * 512;
 
-            let partition = PartitionInode::new(start_offset, size, disk.clone());
+            let type_guid = part.partition_type_guid.0;
+            let type_uuid = uuid::Uuid::from_str(type_guid.to_string().as_str()).unwrap();
+
+            let first_lba = part.starting_lba.to_u64();
+            let last_lba = part.ending_lba.to_u64();
+            if last_lba < first_lba {
+                log::warn!("GPT: partition {} ends before it starts", partition_id);
+                continue;
+            }
+
+            let partition = PartitionInode::new(
+                disk.clone(),
+                PartitionInfo {
+                    partition_type: PartitionType::Gpt(type_uuid),
+                    name: part.name.to_string(),
+                    attributes: part.attributes.0.to_u64(),
+                    first_lba,
+                    last_lba,
+                },
+            );
             let partition = Arc::new(RwLock::new(partition));
 
-            let alpha = id_to_alpha[disk_id];
-            let partition_name = format!("hd{}{}", alpha, partition_id);
+            let partition_name = format!("{}{}", name_prefix, partition_id);
 
             mount_to(partition.clone(), dev_fs.clone(), partition_name.clone());
 
             let guid = part.clone().unique_partition_guid;
             let uuid = uuid::Uuid::from_str(guid.to_string().as_str()).unwrap();
 
+            let partition_path = disk_path.map(|path| format!("{}-part{}", path, partition_id));
+            persistent::register(
+                partition.clone(),
+                partition_path.as_deref(),
+                Some(uuid.to_string()),
+            );
+
             if root_partition_uuid == uuid {
                 *ROOT_PARTITION.lock() = Some(partition.clone());
             }
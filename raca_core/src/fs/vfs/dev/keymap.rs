@@ -0,0 +1,137 @@
+use core::sync::atomic::{AtomicU8, Ordering};
+use pc_keyboard::{
+    layouts::{self, AnyLayout},
+    DecodedKey, HandleControl, Keyboard, ScancodeSet, ScancodeSet1, ScancodeSet2,
+};
+
+use crate::fs::get_kernel_cmdline;
+
+/// Return the layout of the keyboard plus one.
+pub const KBD_GET_LAYOUT: usize = 0x4b80;
+/// Switch the keyboard to layout `arg`.
+pub const KBD_SET_LAYOUT: usize = 0x4b81;
+/// Return the scancode set the keyboard is decoded with, 1 or 2.
+pub const KBD_GET_SCANCODE_SET: usize = 0x4b82;
+/// Decode the keyboard with scancode set `arg`, for controllers that don't
+/// translate to set 1.
+pub const KBD_SET_SCANCODE_SET: usize = 0x4b83;
+
+/// Layouts by number, with the names used on the kernel command line
+const LAYOUTS: [&str; 4] = ["us", "uk", "de", "fr"];
+
+static LAYOUT: AtomicU8 = AtomicU8::new(0);
+static SCANCODE_SET: AtomicU8 = AtomicU8::new(1);
+
+fn layout(num: u8) -> AnyLayout {
+    match num {
+        1 => AnyLayout::Uk105Key(layouts::Uk105Key),
+        2 => AnyLayout::De105Key(layouts::De105Key),
+        3 => AnyLayout::Azerty(layouts::Azerty),
+        _ => AnyLayout::Us104Key(layouts::Us104Key),
+    }
+}
+
+/// Pick the layout and scancode set given with `keymap=<layout>` and
+/// `scancode_set=<1|2>` on the kernel command line.
+pub fn init() {
+    for arg in get_kernel_cmdline().split_whitespace() {
+        if let Some(name) = arg.strip_prefix("keymap=") {
+            match LAYOUTS.iter().position(|layout| *layout == name) {
+                Some(num) => LAYOUT.store(num as u8, Ordering::SeqCst),
+                None => log::warn!("keymap: unknown keyboard layout {}", name),
+            }
+        } else if let Some(set) = arg.strip_prefix("scancode_set=") {
+            match set {
+                "1" => SCANCODE_SET.store(1, Ordering::SeqCst),
+                "2" => SCANCODE_SET.store(2, Ordering::SeqCst),
+                _ => log::warn!("keymap: unknown scancode set {}", set),
+            }
+        }
+    }
+}
+
+pub fn ioctl(cmd: usize, arg: usize) -> Option<usize> {
+    match cmd {
+        KBD_GET_LAYOUT => Some(LAYOUT.load(Ordering::SeqCst) as usize + 1),
+        KBD_SET_LAYOUT => {
+            if arg >= LAYOUTS.len() {
+                return None;
+            }
+            LAYOUT.store(arg as u8, Ordering::SeqCst);
+            Some(1)
+        }
+        KBD_GET_SCANCODE_SET => Some(SCANCODE_SET.load(Ordering::SeqCst) as usize),
+        KBD_SET_SCANCODE_SET => {
+            if arg != 1 && arg != 2 {
+                return None;
+            }
+            SCANCODE_SET.store(arg as u8, Ordering::SeqCst);
+            Some(1)
+        }
+        _ => None,
+    }
+}
+
+enum Decoder {
+    Set1(Keyboard<AnyLayout, ScancodeSet1>),
+    Set2(Keyboard<AnyLayout, ScancodeSet2>),
+}
+
+/// Turns scancodes into keys with the layout and scancode set currently
+/// chosen, following changes to them.
+pub struct KeyboardDecoder {
+    decoder: Decoder,
+    layout: u8,
+    scancode_set: u8,
+}
+
+impl KeyboardDecoder {
+    pub fn new() -> Self {
+        let layout = LAYOUT.load(Ordering::SeqCst);
+        let scancode_set = SCANCODE_SET.load(Ordering::SeqCst);
+
+        // Ctrl+letter comes through as the matching control character
+        let handle_ctrl = HandleControl::MapLettersToUnicode;
+        let decoder = match scancode_set {
+            2 => Decoder::Set2(Keyboard::new(
+                ScancodeSet2::new(),
+                self::layout(layout),
+                handle_ctrl,
+            )),
+            _ => Decoder::Set1(Keyboard::new(
+                ScancodeSet1::new(),
+                self::layout(layout),
+                handle_ctrl,
+            )),
+        };
+
+        Self {
+            decoder,
+            layout,
+            scancode_set,
+        }
+    }
+
+    /// Feed a scancode byte, returning the key it completes, if any.
+    pub fn add_byte(&mut self, scan_code: u8) -> Option<DecodedKey> {
+        // a switch takes effect between keys, dropping held modifiers
+        if self.layout != LAYOUT.load(Ordering::SeqCst)
+            || self.scancode_set != SCANCODE_SET.load(Ordering::SeqCst)
+        {
+            *self = Self::new();
+        }
+
+        match &mut self.decoder {
+            Decoder::Set1(keyboard) => decode(keyboard, scan_code),
+            Decoder::Set2(keyboard) => decode(keyboard, scan_code),
+        }
+    }
+}
+
+fn decode<S: ScancodeSet>(
+    keyboard: &mut Keyboard<AnyLayout, S>,
+    scan_code: u8,
+) -> Option<DecodedKey> {
+    let key_event = keyboard.add_byte(scan_code).ok()??;
+    keyboard.process_keyevent(key_event)
+}
@@ -0,0 +1,84 @@
+use alloc::string::String;
+use log::{Level, LevelFilter};
+
+use crate::{
+    fs::vfs::inode::{Inode, InodeRef},
+    kmsg,
+};
+
+/// Returns the recorded level plus one, 0 being reserved for errors.
+pub const KMSG_GET_LEVEL: usize = 1;
+/// Record messages down to the level in `arg`, 0 (off) to 5 (trace).
+pub const KMSG_SET_LEVEL: usize = 2;
+/// Empty the buffer.
+pub const KMSG_CLEAR: usize = 3;
+
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::Off,
+    LevelFilter::Error,
+    LevelFilter::Warn,
+    LevelFilter::Info,
+    LevelFilter::Debug,
+    LevelFilter::Trace,
+];
+
+/// `/dev/kmsg`: reads return the kernel log buffer, writes add to it.
+pub struct KmsgInode {
+    path: String,
+}
+
+impl KmsgInode {
+    pub fn new() -> Self {
+        Self {
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for KmsgInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        kmsg::size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        kmsg::read(offset, buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        match core::str::from_utf8(buf) {
+            Ok(text) => {
+                kmsg::append(Level::Info, text);
+                buf.len()
+            }
+            Err(_) => 0,
+        }
+    }
+
+    fn flush(&self) {}
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            KMSG_GET_LEVEL => Some(kmsg::level() as usize + 1),
+            KMSG_SET_LEVEL => {
+                kmsg::set_level(*LEVELS.get(arg)?);
+                Some(1)
+            }
+            KMSG_CLEAR => {
+                kmsg::clear();
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,398 @@
+use alloc::{collections::VecDeque, vec::Vec};
+use core::sync::atomic::{AtomicU64, Ordering};
+use framework::{
+    memory::{addr_to_mut_ref, write_for_syscall},
+    task::process::ProcessId,
+};
+use spin::Mutex;
+use x86_64::VirtAddr;
+
+use crate::user::{
+    get_current_process_id, is_user_process,
+    signal::{self, INTERRUPTED, SIGINT, SIGQUIT, SIGTSTP, SIGWINCH},
+    wait_queue::WaitQueue,
+};
+
+/// Fill a [`Termios`] at `arg`.
+pub const TCGETS: usize = 0x5401;
+/// Apply the [`Termios`] at `arg`.
+pub const TCSETS: usize = 0x5402;
+/// Write the pid of the foreground job to the `u64` at `arg`, 0 if none.
+pub const TIOCGPGRP: usize = 0x540f;
+/// Make the process `arg` and everything it starts the foreground job, 0
+/// for none. The caller has to be `arg` or have started it, and can only
+/// take the terminal from a job of its own.
+pub const TIOCSPGRP: usize = 0x5410;
+/// Fill a [`WindowSize`] at `arg`.
+pub const TIOCGWINSZ: usize = 0x5413;
+/// Apply the [`WindowSize`] at `arg`, telling the foreground job with
+/// `SIGWINCH` if it changed.
+pub const TIOCSWINSZ: usize = 0x5414;
+
+/// `lflag`: the interrupt, quit and suspend characters raise signals
+pub const ISIG: u32 = 0o1;
+/// `lflag`: input is edited and handed out a line at a time
+pub const ICANON: u32 = 0o2;
+/// `lflag`: typed characters are echoed
+pub const ECHO: u32 = 0o10;
+/// `lflag`: erasing takes the character off the screen as well
+pub const ECHOE: u32 = 0o20;
+/// `lflag`: control characters, like Ctrl+A, are echoed as `^X`
+pub const ECHOCTL: u32 = 0o1000;
+
+/// Index into `cc` of each special character, a 0 there turns it off
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VSUSP: usize = 5;
+pub const NCCS: usize = 8;
+
+/// Longest line canonical mode keeps, further input is dropped
+const MAX_LINE: usize = 4096;
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct Termios {
+    pub lflag: u32,
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// Cooked mode with the usual control characters
+    pub const fn new() -> Self {
+        let mut cc = [0; NCCS];
+        cc[VINTR] = 0x03; // Ctrl+C
+        cc[VQUIT] = 0x1c; // Ctrl+backslash
+        cc[VERASE] = 0x08;
+        cc[VKILL] = 0x15; // Ctrl+U
+        cc[VEOF] = 0x04; // Ctrl+D
+        cc[VSUSP] = 0x1a; // Ctrl+Z
+        Self {
+            lflag: ISIG | ICANON | ECHO | ECHOE | ECHOCTL,
+            cc,
+        }
+    }
+
+    fn is_special(&self, byte: u8, idx: usize) -> bool {
+        byte != 0 && self.cc[idx] == byte
+    }
+
+    /// Whether `byte` is echoed as `^X` in canonical mode
+    fn echoes_as_caret(&self, byte: u8) -> bool {
+        self.lflag & ECHOCTL != 0 && byte < 0x20 && byte != b'\t'
+    }
+}
+
+/// How far canonical mode is into an escape sequence, like the one an arrow
+/// key sends. There is no line editing that would use them, so they are
+/// dropped rather than ending up in the line.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Escape {
+    None,
+    /// Got the escape character
+    Start,
+    /// Got `ESC [` or `ESC O`, waiting for the final byte
+    Sequence,
+}
+
+/// Size of the terminal in character cells, and in pixels where known.
+#[repr(C)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+    pub x_pixels: u16,
+    pub y_pixels: u16,
+}
+
+impl WindowSize {
+    /// Size not known, like for a serial line
+    pub const fn unknown() -> Self {
+        Self {
+            rows: 0,
+            cols: 0,
+            x_pixels: 0,
+            y_pixels: 0,
+        }
+    }
+}
+
+/// Sits between a terminal device and its readers. In canonical mode it
+/// collects input into lines, doing the editing and echo itself, and turns
+/// control characters into signals for the foreground job. In raw mode every
+/// byte goes to the reader as it comes.
+pub struct LineDiscipline {
+    termios: Mutex<Termios>,
+    /// The line being typed in canonical mode
+    line: Mutex<Vec<u8>>,
+    escape: Mutex<Escape>,
+    /// Input ready to be read. In canonical mode each entry is a line, an
+    /// empty one meaning end of file.
+    input: Mutex<VecDeque<Vec<u8>>>,
+    readable: WaitQueue,
+    foreground: AtomicU64,
+    window_size: Mutex<WindowSize>,
+    /// Puts echoed bytes on the device
+    echo: fn(&[u8]),
+}
+
+impl LineDiscipline {
+    pub const fn new(echo: fn(&[u8])) -> Self {
+        Self {
+            termios: Mutex::new(Termios::new()),
+            line: Mutex::new(Vec::new()),
+            escape: Mutex::new(Escape::None),
+            input: Mutex::new(VecDeque::new()),
+            readable: WaitQueue::new(),
+            foreground: AtomicU64::new(0),
+            window_size: Mutex::new(WindowSize::unknown()),
+            echo,
+        }
+    }
+
+    fn push_input(&self, bytes: Vec<u8>) {
+        self.input.lock().push_back(bytes);
+        self.readable.wake_all();
+    }
+
+    /// Feed a character typed on the device.
+    pub fn receive_char(&self, ch: char) {
+        let mut buf = [0; 4];
+        for &byte in ch.encode_utf8(&mut buf).as_bytes() {
+            self.receive(byte);
+        }
+    }
+
+    /// Feed a byte that came in from the device.
+    pub fn receive(&self, byte: u8) {
+        let termios = *self.termios.lock();
+        let echo = termios.lflag & ECHO != 0;
+
+        if termios.lflag & ISIG != 0 {
+            let sig = match byte {
+                byte if termios.is_special(byte, VINTR) => Some(SIGINT),
+                byte if termios.is_special(byte, VQUIT) => Some(SIGQUIT),
+                byte if termios.is_special(byte, VSUSP) => Some(SIGTSTP),
+                _ => None,
+            };
+            if let Some(sig) = sig {
+                self.line.lock().clear();
+                *self.escape.lock() = Escape::None;
+                if echo {
+                    (self.echo)(&[b'^', byte ^ 0x40, b'\n']);
+                }
+                self.signal_foreground(sig);
+                return;
+            }
+        }
+
+        if termios.lflag & ICANON == 0 {
+            if echo {
+                (self.echo)(&[byte]);
+            }
+            self.push_input(Vec::from([byte]));
+            return;
+        }
+
+        {
+            let mut escape = self.escape.lock();
+            match (*escape, byte) {
+                (Escape::None, 0x1b) => {
+                    *escape = Escape::Start;
+                    return;
+                }
+                (Escape::Start, b'[' | b'O') => {
+                    *escape = Escape::Sequence;
+                    return;
+                }
+                // not a sequence, the byte counts on its own
+                (Escape::Start, _) => *escape = Escape::None,
+                (Escape::Sequence, 0x40..=0x7e) => {
+                    *escape = Escape::None;
+                    return;
+                }
+                (Escape::Sequence, _) => return,
+                (Escape::None, _) => {}
+            }
+        }
+
+        let erase_echo = |erased: u8| {
+            if echo && termios.lflag & ECHOE != 0 {
+                (self.echo)(b"\x08 \x08");
+                if termios.echoes_as_caret(erased) {
+                    (self.echo)(b"\x08 \x08");
+                }
+            }
+        };
+
+        let mut line = self.line.lock();
+        match byte {
+            byte if termios.is_special(byte, VERASE) || byte == 0x7f => {
+                if let Some(erased) = erase_char(&mut line) {
+                    erase_echo(erased);
+                }
+            }
+            byte if termios.is_special(byte, VKILL) => {
+                while let Some(erased) = erase_char(&mut line) {
+                    erase_echo(erased);
+                }
+            }
+            byte if termios.is_special(byte, VEOF) => {
+                // an empty line reads as end of file
+                self.push_input(core::mem::take(&mut *line));
+            }
+            b'\n' => {
+                if echo {
+                    (self.echo)(b"\n");
+                }
+                line.push(b'\n');
+                self.push_input(core::mem::take(&mut *line));
+            }
+            byte => {
+                if line.len() < MAX_LINE {
+                    if echo && termios.echoes_as_caret(byte) {
+                        (self.echo)(&[b'^', byte ^ 0x40]);
+                    } else if echo {
+                        (self.echo)(&[byte]);
+                    }
+                    line.push(byte);
+                }
+            }
+        }
+    }
+
+    fn signal_foreground(&self, sig: usize) {
+        let foreground = self.foreground.load(Ordering::SeqCst);
+        if foreground != 0 {
+            signal::send_group(ProcessId(foreground), sig);
+        }
+    }
+
+    /// Set the size of the terminal, telling the foreground job if it
+    /// changed.
+    pub fn set_window_size(&self, size: WindowSize) {
+        let changed = core::mem::replace(&mut *self.window_size.lock(), size) != size;
+        if changed {
+            self.signal_foreground(SIGWINCH);
+        }
+    }
+
+    /// Block until there is input. In canonical mode this hands out at most
+    /// one line, and 0 at end of file. [`INTERRUPTED`] if a signal came
+    /// first.
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+        let canonical = self.termios.lock().lflag & ICANON != 0;
+
+        let read = self.readable.wait_until(|| {
+            let mut input = self.input.lock();
+            if input.front()?.is_empty() {
+                input.pop_front();
+                return Some(0);
+            }
+
+            let mut read = 0;
+            while read < buf.len() {
+                let Some(chunk) = input.front_mut() else {
+                    break;
+                };
+                if chunk.is_empty() {
+                    // end of file is for the next read
+                    break;
+                }
+                let len = chunk.len().min(buf.len() - read);
+                buf[read..read + len].copy_from_slice(&chunk[..len]);
+                chunk.drain(..len);
+                read += len;
+
+                if chunk.is_empty() {
+                    input.pop_front();
+                    if canonical {
+                        break;
+                    }
+                }
+            }
+            Some(read)
+        });
+        read.unwrap_or(INTERRUPTED)
+    }
+
+    pub fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            TCGETS => {
+                let termios = *self.termios.lock();
+                write_for_syscall(VirtAddr::new(arg as u64), &[termios]);
+                Some(1)
+            }
+            TCSETS => {
+                let termios: &mut Termios = addr_to_mut_ref(VirtAddr::new(arg as u64));
+                let termios = *termios;
+                let was_canonical = {
+                    let mut current = self.termios.lock();
+                    let was_canonical = current.lflag & ICANON != 0;
+                    *current = termios;
+                    was_canonical
+                };
+                // a half typed line goes to the reader as it is when leaving
+                // canonical mode
+                if was_canonical && termios.lflag & ICANON == 0 {
+                    *self.escape.lock() = Escape::None;
+                    let line = core::mem::take(&mut *self.line.lock());
+                    if !line.is_empty() {
+                        self.push_input(line);
+                    }
+                }
+                Some(1)
+            }
+            TIOCGPGRP => {
+                let foreground = self.foreground.load(Ordering::SeqCst);
+                write_for_syscall(VirtAddr::new(arg as u64), &[foreground]);
+                Some(1)
+            }
+            TIOCSPGRP => {
+                // a process can take the terminal from a job it started, or
+                // one that is gone, and hand it to itself or such a job
+                let caller = get_current_process_id();
+                let current = ProcessId(self.foreground.load(Ordering::SeqCst));
+                let releases =
+                    signal::send(current, 0).is_none() || signal::is_descendant(current, caller);
+                let pid = ProcessId(arg as u64);
+                let takes =
+                    arg == 0 || (is_user_process(pid) && signal::is_descendant(pid, caller));
+                if !releases || !takes {
+                    return None;
+                }
+                self.foreground.store(arg as u64, Ordering::SeqCst);
+                Some(1)
+            }
+            TIOCGWINSZ => {
+                let size = *self.window_size.lock();
+                write_for_syscall(VirtAddr::new(arg as u64), &[size]);
+                Some(1)
+            }
+            TIOCSWINSZ => {
+                let size: &mut WindowSize = addr_to_mut_ref(VirtAddr::new(arg as u64));
+                self.set_window_size(*size);
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Take the last character, which may be several bytes long, off `line`.
+/// Returns its first byte.
+fn erase_char(line: &mut Vec<u8>) -> Option<u8> {
+    let mut erased = None;
+    while let Some(byte) = line.pop() {
+        erased = Some(byte);
+        if byte & 0xc0 != 0x80 {
+            break;
+        }
+    }
+    erased
+}
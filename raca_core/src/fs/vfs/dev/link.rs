@@ -0,0 +1,68 @@
+use alloc::{string::String, vec::Vec};
+
+use crate::fs::vfs::inode::{FileInfo, Inode, InodeRef, InodeTy};
+
+use super::partition::PartitionInfo;
+
+/// A second name for a device node that is already mounted somewhere else.
+///
+/// Mounting the device itself twice would overwrite its path, so the alias
+/// keeps its own path and forwards everything else to the target.
+pub struct LinkInode {
+    target: InodeRef,
+    path: String,
+}
+
+impl LinkInode {
+    pub fn new(target: InodeRef) -> Self {
+        Self {
+            target,
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for LinkInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.target.read().size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.target.read().read_at(offset, buf)
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.target.read().write_at(offset, buf)
+    }
+
+    fn flush(&self) {
+        self.target.read().flush();
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        self.target.read().open(name)
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        self.target.read().list()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        self.target.read().inode_type()
+    }
+
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        self.target.read().partition_info()
+    }
+}
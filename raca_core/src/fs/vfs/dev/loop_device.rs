@@ -0,0 +1,160 @@
+use alloc::{collections::BTreeMap, format, string::String, sync::Arc, vec::Vec};
+use core::ops::Range;
+use spin::{Mutex, RwLock};
+
+use crate::fs::vfs::inode::{Inode, InodeRef, InodeTy};
+
+use super::{
+    is_disk_mounted, provide_partitioned_disk, remove_partitioned_disk, PartitionedDisk, DEV_FS,
+};
+
+const SECTOR_SIZE: usize = 512;
+/// Largest request passed on to the file. A power of two, so that once an
+/// access is aligned to it, none of its pieces cross a cluster of the
+/// filesystem the file lives on, whatever the cluster size.
+const BATCH_SIZE: usize = 4096;
+
+/// Attached loop devices by number
+static LOOP_DEVICES: Mutex<BTreeMap<usize, InodeRef>> = Mutex::new(BTreeMap::new());
+
+/// A block device backed by a regular file.
+pub struct LoopInode {
+    file: InodeRef,
+    path: String,
+}
+
+impl LoopInode {
+    pub fn new(file: InodeRef) -> Self {
+        Self {
+            file,
+            path: String::new(),
+        }
+    }
+
+    /// Split an access into requests to the file that don't cross a cluster
+    /// of its filesystem: single sectors up to the first `BATCH_SIZE`
+    /// boundary, then up to `BATCH_SIZE` bytes at a time.
+    fn for_each_chunk(
+        &self,
+        offset: usize,
+        len: usize,
+        mut f: impl FnMut(usize, Range<usize>) -> usize,
+    ) -> usize {
+        let len = len.min(self.size().saturating_sub(offset));
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let chunk = if pos % BATCH_SIZE == 0 {
+                BATCH_SIZE
+            } else {
+                SECTOR_SIZE - pos % SECTOR_SIZE
+            };
+            let chunk = chunk.min(len - done);
+            let transferred = f(pos, done..done + chunk);
+            done += transferred;
+            if transferred < chunk {
+                break;
+            }
+        }
+        done
+    }
+}
+
+impl Inode for LoopInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.file.read().size()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let file = self.file.read();
+        self.for_each_chunk(offset, buf.len(), |pos, range| {
+            file.read_at(pos, &mut buf[range])
+        })
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let file = self.file.read();
+        self.for_each_chunk(offset, buf.len(), |pos, range| {
+            file.write_at(pos, &buf[range])
+        })
+    }
+
+    fn flush(&self) {
+        // file inodes write through on every write_at
+    }
+}
+
+/// Attach `file` to the first free `/dev/loopN` and scan it for partitions,
+/// which show up as `/dev/loopNpM`. Returns N.
+pub fn attach(file: InodeRef) -> Option<usize> {
+    if file.read().inode_type() != InodeTy::File {
+        return None;
+    }
+    let dev_fs = DEV_FS.lock().clone()?;
+
+    let (id, inode) = {
+        let mut loop_devices = LOOP_DEVICES.lock();
+        let id = (0..).find(|id| !loop_devices.contains_key(id))?;
+        let inode: InodeRef = Arc::new(RwLock::new(LoopInode::new(file)));
+        loop_devices.insert(id, inode.clone());
+        (id, inode)
+    };
+
+    provide_partitioned_disk(
+        PartitionedDisk {
+            name: format!("loop{}", id),
+            partition_prefix: format!("loop{}p", id),
+            inode,
+            bus_path: None,
+        },
+        dev_fs,
+    );
+
+    Some(id)
+}
+
+/// Remove `/dev/loopN` and its partitions. Fails while anything is mounted
+/// from them.
+pub fn detach(id: usize) -> Option<()> {
+    let inode = {
+        let mut loop_devices = LOOP_DEVICES.lock();
+        if is_disk_mounted(loop_devices.get(&id)?) {
+            return None;
+        }
+        loop_devices.remove(&id)?
+    };
+    remove_partitioned_disk(&inode)
+}
+
+/// The partitions of a loop device, in the order they were found.
+pub fn partitions(id: usize) -> impl Iterator<Item = InodeRef> {
+    let dev_fs = DEV_FS.lock().clone();
+    let prefix = format!("loop{}p", id);
+
+    let mut names: Vec<(usize, String)> = dev_fs
+        .as_ref()
+        .map(|dev_fs| dev_fs.read().list())
+        .unwrap_or_default()
+        .into_iter()
+        .filter_map(|info| {
+            let id = info.name.strip_prefix(prefix.as_str())?.parse().ok()?;
+            Some((id, info.name))
+        })
+        .collect();
+    names.sort();
+
+    names
+        .into_iter()
+        .filter_map(move |(_, name)| dev_fs.as_ref()?.read().open(name))
+}
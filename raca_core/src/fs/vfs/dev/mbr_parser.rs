@@ -0,0 +1,180 @@
+use crate::fs::vfs::{
+    dev::{
+        partition::{PartitionInfo, PartitionInode, PartitionType},
+        persistent,
+    },
+    inode::{mount_to, InodeRef},
+};
+
+use alloc::{format, string::String, sync::Arc};
+use spin::RwLock;
+
+const SECTOR_SIZE: usize = 512;
+const DISK_SIGNATURE_OFFSET: usize = 440;
+const PARTITION_TABLE_OFFSET: usize = 446;
+const PARTITION_ENTRY_SIZE: usize = 16;
+
+const TYPE_EMPTY: u8 = 0x00;
+const TYPE_GPT_PROTECTIVE: u8 = 0xee;
+const EXTENDED_TYPES: [u8; 3] = [0x05, 0x0f, 0x85];
+
+/// Logical partitions are numbered after the four primary slots
+const FIRST_LOGICAL_ID: usize = 4;
+/// Guard against loops in a corrupted EBR chain
+const MAX_LOGICAL_PARTITIONS: usize = 128;
+
+#[derive(Clone, Copy)]
+struct MbrEntry {
+    status: u8,
+    ty: u8,
+    start_lba: usize,
+    sectors: usize,
+}
+
+fn read_table(disk: &InodeRef, lba: usize) -> Option<[MbrEntry; 4]> {
+    let mut sector = [0; SECTOR_SIZE];
+    disk.read().read_at(lba * SECTOR_SIZE, &mut sector);
+
+    if sector[510] != 0x55 || sector[511] != 0xaa {
+        return None;
+    }
+
+    let mut entries = [MbrEntry {
+        status: 0,
+        ty: TYPE_EMPTY,
+        start_lba: 0,
+        sectors: 0,
+    }; 4];
+    for (idx, entry) in entries.iter_mut().enumerate() {
+        let raw = &sector[PARTITION_TABLE_OFFSET + idx * PARTITION_ENTRY_SIZE..]
+            [..PARTITION_ENTRY_SIZE];
+        entry.status = raw[0];
+        entry.ty = raw[4];
+        entry.start_lba = u32::from_le_bytes(raw[8..12].try_into().unwrap()) as usize;
+        entry.sectors = u32::from_le_bytes(raw[12..16].try_into().unwrap()) as usize;
+    }
+    Some(entries)
+}
+
+/// The disk signature, from which partition UUIDs are made up the way
+/// Linux does for DOS partition tables.
+fn read_signature(disk: &InodeRef) -> u32 {
+    let mut signature = [0; 4];
+    disk.read().read_at(DISK_SIGNATURE_OFFSET, &mut signature);
+    u32::from_le_bytes(signature)
+}
+
+struct MbrDisk<'a> {
+    name_prefix: &'a str,
+    inode: InodeRef,
+    path: Option<&'a str>,
+    signature: u32,
+}
+
+fn provide_partition(
+    disk: &MbrDisk,
+    partition_id: usize,
+    entry: &MbrEntry,
+    start_lba: usize,
+    dev_fs: InodeRef,
+) {
+    let partition = PartitionInode::new(
+        disk.inode.clone(),
+        PartitionInfo {
+            partition_type: PartitionType::Mbr(entry.ty),
+            name: String::new(),
+            attributes: entry.status as u64,
+            first_lba: start_lba as u64,
+            last_lba: (start_lba + entry.sectors - 1) as u64,
+        },
+    );
+    let partition = Arc::new(RwLock::new(partition));
+
+    let partition_name = format!("{}{}", disk.name_prefix, partition_id);
+
+    mount_to(partition.clone(), dev_fs, partition_name);
+
+    let partition_path = disk
+        .path
+        .map(|path| format!("{}-part{}", path, partition_id));
+    persistent::register(
+        partition,
+        partition_path.as_deref(),
+        Some(format!("{:08x}-{:02x}", disk.signature, partition_id + 1)),
+    );
+}
+
+/// Scan a classic DOS partition table, including the logical partitions
+/// chained behind an extended partition. Partitions are published as
+/// `{name_prefix}{number}`.
+pub fn parse_mbr_disk(
+    name_prefix: &str,
+    disk: InodeRef,
+    disk_path: Option<&str>,
+    dev_fs: InodeRef,
+) -> Option<()> {
+    let primary = read_table(&disk, 0)?;
+
+    if primary.iter().any(|entry| entry.ty == TYPE_GPT_PROTECTIVE) {
+        // the GPT behind it is broken, the protective entry is no partition
+        return None;
+    }
+
+    let mbr_disk = MbrDisk {
+        name_prefix,
+        inode: disk.clone(),
+        path: disk_path,
+        signature: read_signature(&disk),
+    };
+
+    let mut logical_id = FIRST_LOGICAL_ID;
+
+    for (partition_id, entry) in primary.iter().enumerate() {
+        if entry.ty == TYPE_EMPTY || entry.sectors == 0 {
+            continue;
+        }
+
+        if !EXTENDED_TYPES.contains(&entry.ty) {
+            provide_partition(
+                &mbr_disk,
+                partition_id,
+                entry,
+                entry.start_lba,
+                dev_fs.clone(),
+            );
+            continue;
+        }
+
+        // Every EBR holds one logical partition relative to itself and a link
+        // to the next EBR relative to the start of the extended partition.
+        let extended_start = entry.start_lba;
+        let mut ebr_lba = extended_start;
+
+        for _ in 0..MAX_LOGICAL_PARTITIONS {
+            let ebr = match read_table(&disk, ebr_lba) {
+                Some(ebr) => ebr,
+                None => break,
+            };
+
+            let logical = &ebr[0];
+            if logical.ty != TYPE_EMPTY && logical.sectors != 0 {
+                provide_partition(
+                    &mbr_disk,
+                    logical_id,
+                    logical,
+                    ebr_lba + logical.start_lba,
+                    dev_fs.clone(),
+                );
+                logical_id += 1;
+            }
+
+            let next = &ebr[1];
+            if !EXTENDED_TYPES.contains(&next.ty) || next.start_lba == 0 {
+                break;
+            }
+            ebr_lba = extended_start + next.start_lba;
+        }
+    }
+
+    Some(())
+}
@@ -1,8 +1,21 @@
-use alloc::{format, string::ToString, sync::Arc};
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec,
+    vec::Vec,
+};
+use chardev::{CharDevice, CharDeviceKind};
+use framebuffer::FramebufferInode;
+use kmsg::KmsgInode;
+use serial::SerialInode;
 use spin::{Mutex, RwLock};
 use terminal::Terminal;
 
-use crate::{drivers::block::HD_LIST, fs::ROOT};
+use crate::{
+    drivers::block::{BlockDeviceType, HD_LIST},
+    fs::{MOUNTS, ROOT},
+};
 
 use super::{
     inode::{mount_to, InodeRef},
@@ -10,45 +23,252 @@ use super::{
 };
 
 pub mod block;
+pub mod chardev;
+pub mod framebuffer;
+pub mod gpt_editor;
 pub mod gpt_parser;
+pub mod keymap;
+pub mod kmsg;
+pub mod line_discipline;
+pub mod link;
+pub mod loop_device;
+pub mod mbr_parser;
 pub mod partition;
+pub mod persistent;
+pub mod serial;
 pub mod terminal;
 //pub mod tty;
 
 pub static ROOT_PARTITION: Mutex<Option<InodeRef>> = Mutex::new(None);
 
-fn provide_hard_disk(hd: usize, dev_fs: InodeRef) {
-    let id_to_alpha = [
-        "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r",
-        "s", "t", "u", "v", "w", "x", "y", "z",
-    ];
+/// A whole-disk node whose partitions can be scanned again
+struct PartitionedDisk {
+    /// Node name of the disk, e.g. `hda` or `loop0`
+    name: String,
+    /// Partition nodes are named this followed by their number
+    partition_prefix: String,
+    inode: InodeRef,
+    bus_path: Option<String>,
+}
+
+static PARTITIONED_DISKS: Mutex<Vec<PartitionedDisk>> = Mutex::new(Vec::new());
+static DEV_FS: Mutex<Option<InodeRef>> = Mutex::new(None);
+
+const ID_TO_ALPHA: [&str; 26] = [
+    "a", "b", "c", "d", "e", "f", "g", "h", "i", "j", "k", "l", "m", "n", "o", "p", "q", "r", "s",
+    "t", "u", "v", "w", "x", "y", "z",
+];
+
+/// Publish a whole-disk node along with the partitions on it.
+fn provide_partitioned_disk(disk: PartitionedDisk, dev_fs: InodeRef) {
+    mount_to(disk.inode.clone(), dev_fs.clone(), disk.name.clone());
+    persistent::register(disk.inode.clone(), disk.bus_path.as_deref(), None);
+
+    scan_partitions(&disk, dev_fs);
+
+    PARTITIONED_DISKS.lock().push(disk);
+}
+
+fn provide_hard_disk(hd: usize, disk_id: usize, dev_fs: InodeRef) {
+    let name = format!("hd{}", ID_TO_ALPHA[disk_id]);
+    let disk = PartitionedDisk {
+        partition_prefix: name.clone(),
+        name,
+        inode: Arc::new(RwLock::new(block::BlockInode::new(hd))),
+        bus_path: Some(HD_LIST.lock()[hd].bus_path()),
+    };
+    provide_partitioned_disk(disk, dev_fs);
+}
 
+fn scan_partitions(disk: &PartitionedDisk, dev_fs: InodeRef) {
+    let prefix = disk.partition_prefix.as_str();
+    let bus_path = disk.bus_path.as_deref();
+
+    if let Err(_) = gpt_parser::parse_gpt_disk(prefix, disk.inode.clone(), bus_path, dev_fs.clone())
+    {
+        if let None = mbr_parser::parse_mbr_disk(prefix, disk.inode.clone(), bus_path, dev_fs) {
+            log::warn!("{}: no partition table found", disk.name);
+        }
+    }
+}
+
+/// The partition nodes of a disk, with their names in `/dev`.
+fn partition_nodes(disk: &PartitionedDisk, dev_fs: &InodeRef) -> Vec<(String, InodeRef)> {
+    let mut nodes = Vec::new();
+    for info in dev_fs.read().list() {
+        let is_partition = info
+            .name
+            .strip_prefix(disk.partition_prefix.as_str())
+            .is_some_and(|id| !id.is_empty() && id.bytes().all(|c| c.is_ascii_digit()));
+        if !is_partition {
+            continue;
+        }
+        if let Some(partition) = dev_fs.read().open(info.name.clone()) {
+            nodes.push((info.name, partition));
+        }
+    }
+    nodes
+}
+
+fn remove_partitions(disk: &PartitionedDisk, dev_fs: &InodeRef) {
+    for (name, partition) in partition_nodes(disk, dev_fs) {
+        persistent::unregister(&partition);
+        dev_fs.read().umount(name);
+    }
+}
+
+/// Whether `disk` is a whole-disk node that can carry a partition table.
+pub fn is_partitioned_disk(disk: &InodeRef) -> bool {
+    PARTITIONED_DISKS
+        .lock()
+        .iter()
+        .any(|entry| Arc::ptr_eq(&entry.inode, disk))
+}
+
+/// Whether a mounted filesystem reads from `disk` or one of its partitions.
+pub fn is_disk_mounted(disk: &InodeRef) -> bool {
+    let Some(dev_fs) = DEV_FS.lock().clone() else {
+        return false;
+    };
+
+    let mut nodes = vec![disk.clone()];
+    if let Some(entry) = PARTITIONED_DISKS
+        .lock()
+        .iter()
+        .find(|entry| Arc::ptr_eq(&entry.inode, disk))
+    {
+        for (_, partition) in partition_nodes(entry, &dev_fs) {
+            nodes.push(partition);
+        }
+    }
+
+    MOUNTS
+        .lock()
+        .iter()
+        .filter_map(|mount| mount.device.as_ref())
+        .map(persistent::link_target)
+        .any(|device| nodes.iter().any(|node| Arc::ptr_eq(node, &device)))
+}
+
+/// Drop the partition nodes of a disk and read its partition table again,
+/// after the table has been changed.
+pub fn rescan_disk(disk: &InodeRef) -> Option<()> {
+    let dev_fs = DEV_FS.lock().clone()?;
+    let disks = PARTITIONED_DISKS.lock();
+    let disk = disks.iter().find(|entry| Arc::ptr_eq(&entry.inode, disk))?;
+
+    remove_partitions(disk, &dev_fs);
+    scan_partitions(disk, dev_fs);
+    Some(())
+}
+
+/// Take a whole-disk node and its partitions out of `/dev`.
+fn remove_partitioned_disk(disk: &InodeRef) -> Option<()> {
+    let dev_fs = DEV_FS.lock().clone()?;
+    let disk = {
+        let mut disks = PARTITIONED_DISKS.lock();
+        let idx = disks
+            .iter()
+            .position(|entry| Arc::ptr_eq(&entry.inode, disk))?;
+        disks.remove(idx)
+    };
+
+    remove_partitions(&disk, &dev_fs);
+    persistent::unregister(&disk.inode);
+    dev_fs.read().umount(disk.name);
+    Some(())
+}
+
+fn provide_ram_disk(hd: usize, dev_fs: InodeRef) {
+    // the driver names RAM disks by their own number, not by discovery order
+    let name = HD_LIST.lock()[hd].bus_path();
+    let disk = PartitionedDisk {
+        partition_prefix: format!("{}p", name),
+        name,
+        inode: Arc::new(RwLock::new(block::BlockInode::new(hd))),
+        bus_path: None,
+    };
+    provide_partitioned_disk(disk, dev_fs);
+}
+
+/// Create `/dev/ramN` with `size` bytes and return N.
+pub fn create_ram_disk(size: usize) -> Option<usize> {
+    let dev_fs = DEV_FS.lock().clone()?;
+    let (hd, num) = crate::drivers::block::add_ram_disk(size)?;
+    provide_ram_disk(hd, dev_fs);
+    Some(num)
+}
+
+fn provide_cdrom(hd: usize, cdrom_id: usize, dev_fs: InodeRef) {
     let block_i = Arc::new(RwLock::new(block::BlockInode::new(hd)));
     mount_to(
         block_i.clone(),
         dev_fs.clone(),
-        format!("hd{}", id_to_alpha[hd]),
+        format!("cd{}", ID_TO_ALPHA[cdrom_id]),
     );
 
-    let _ = gpt_parser::parse_gpt_disk(hd, block_i.clone(), dev_fs.clone());
+    let disk_path = HD_LIST.lock()[hd].bus_path();
+    persistent::register(block_i, Some(&disk_path), None);
 }
 
 fn provide_hard_disks(dev_fs: InodeRef) {
     let hd_num = HD_LIST.lock().len();
+    let mut disk_id = 0;
+    let mut cdrom_id = 0;
     for hd in 0..hd_num {
-        provide_hard_disk(hd, dev_fs.clone());
+        let device_type = HD_LIST.lock()[hd].device_type();
+        match device_type {
+            BlockDeviceType::HardDisk => {
+                provide_hard_disk(hd, disk_id, dev_fs.clone());
+                disk_id += 1;
+            }
+            BlockDeviceType::CdRom => {
+                provide_cdrom(hd, cdrom_id, dev_fs.clone());
+                cdrom_id += 1;
+            }
+            BlockDeviceType::RamDisk => provide_ram_disk(hd, dev_fs.clone()),
+        }
     }
 }
 
 pub fn init() {
     crate::drivers::ahci::init();
+    crate::drivers::nvme::init();
+    crate::drivers::virtio_blk::init();
+    crate::drivers::ramdisk::init();
     crate::drivers::block::init();
 
     let dev_fs = RootFS::new();
     mount_to(dev_fs.clone(), ROOT.lock().clone(), "dev".to_string());
+    *DEV_FS.lock() = Some(dev_fs.clone());
 
+    keymap::init();
     let terminal = Arc::new(RwLock::new(Terminal::new()));
     mount_to(terminal.clone(), dev_fs.clone(), "terminal".to_string());
 
+    let serial = Arc::new(RwLock::new(SerialInode::new()));
+    mount_to(serial, dev_fs.clone(), "ttyS0".to_string());
+
+    let char_devices = [
+        ("null", CharDeviceKind::Null),
+        ("zero", CharDeviceKind::Zero),
+        ("full", CharDeviceKind::Full),
+        ("random", CharDeviceKind::Random),
+        ("urandom", CharDeviceKind::Random),
+    ];
+    for (name, kind) in char_devices {
+        let device = Arc::new(RwLock::new(CharDevice::new(kind)));
+        mount_to(device, dev_fs.clone(), name.to_string());
+    }
+
+    let kmsg = Arc::new(RwLock::new(KmsgInode::new()));
+    mount_to(kmsg, dev_fs.clone(), "kmsg".to_string());
+
+    let fb = Arc::new(RwLock::new(FramebufferInode::new()));
+    mount_to(fb, dev_fs.clone(), "fb0".to_string());
+
+    super::ipc::init(dev_fs.clone());
+    super::shm::init(dev_fs.clone());
+    persistent::init(dev_fs.clone());
     provide_hard_disks(dev_fs.clone());
 }
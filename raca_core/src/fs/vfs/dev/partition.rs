@@ -1,23 +1,57 @@
 use alloc::string::String;
+use uuid::Uuid;
 
 use crate::fs::vfs::inode::{Inode, InodeRef};
 
+/// Type id of a partition as recorded in the partition table
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PartitionType {
+    /// Partition type GUID of a GPT entry
+    Gpt(Uuid),
+    /// System id byte of an MBR entry
+    Mbr(u8),
+}
+
+/// What the partition table says about a partition
+#[derive(Clone, Debug)]
+pub struct PartitionInfo {
+    pub partition_type: PartitionType,
+    /// GPT partition name, empty for MBR partitions
+    pub name: String,
+    /// GPT attribute bits, or the status byte of an MBR entry
+    pub attributes: u64,
+    pub first_lba: u64,
+    /// Last sector of the partition, inclusive
+    pub last_lba: u64,
+}
+
 pub struct PartitionInode {
     offset: usize,
     size: usize,
     drive: InodeRef,
+    info: PartitionInfo,
     path: String,
 }
 
 impl PartitionInode {
-    pub fn new(offset: usize, size: usize, drive: InodeRef) -> Self {
+    pub fn new(drive: InodeRef, info: PartitionInfo) -> Self {
         Self {
-            offset,
-            size,
+            offset: info.first_lba as usize * 512,
+            size: (info.last_lba - info.first_lba + 1) as usize * 512,
             drive,
+            info,
             path: String::new(),
         }
     }
+
+    pub fn partition_type(&self) -> PartitionType {
+        self.info.partition_type
+    }
+
+    /// Clamp an access to the end of the partition.
+    fn bounded_len(&self, offset: usize, len: usize) -> usize {
+        len.min(self.size.saturating_sub(offset))
+    }
 }
 
 impl Inode for PartitionInode {
@@ -41,16 +75,28 @@ impl Inode for PartitionInode {
     }
 
     fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let len = self.bounded_len(offset, buf.len());
+        if len == 0 {
+            return 0;
+        }
         let offset = self.offset + offset;
-        self.drive.read().read_at(offset, buf)
+        self.drive.read().read_at(offset, &mut buf[..len])
     }
 
     fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let len = self.bounded_len(offset, buf.len());
+        if len == 0 {
+            return 0;
+        }
         let offset = self.offset + offset;
-        self.drive.read().write_at(offset, buf)
+        self.drive.read().write_at(offset, &buf[..len])
     }
 
     fn flush(&self) {
         self.drive.read().flush();
     }
+
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        Some(self.info.clone())
+    }
 }
@@ -0,0 +1,215 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::Arc,
+    vec::Vec,
+};
+use spin::{Mutex, RwLock};
+
+use crate::fs::{
+    probe_volume,
+    vfs::{
+        inode::{mount_to, FileInfo, Inode, InodeRef, InodeTy},
+        root::RootFS,
+    },
+};
+
+use super::link::LinkInode;
+
+/// The directories under `/dev/disk` that hold names which survive changes
+/// in discovery order.
+struct DiskLinks {
+    by_uuid: InodeRef,
+    by_partuuid: InodeRef,
+    by_label: InodeRef,
+    by_path: InodeRef,
+    /// Every alias as (directory, name, target), so they can be removed again
+    links: Vec<(InodeRef, String, InodeRef)>,
+    /// Nodes whose filesystem hasn't been looked at yet
+    pending: Vec<InodeRef>,
+}
+
+static DISK_LINKS: Mutex<Option<DiskLinks>> = Mutex::new(None);
+
+const DISK_LINKS_PATH: &str = "/dev/disk";
+
+pub fn init(dev_fs: InodeRef) {
+    let disk = RootFS::new();
+    mount_to(disk.clone(), dev_fs, "disk".to_string());
+
+    let make_dir = |name: &str, probed: bool| {
+        let dir = RootFS::new();
+        if probed {
+            let probed_dir = Arc::new(RwLock::new(ProbedDir { dir: dir.clone() }));
+            mount_to(probed_dir, disk.clone(), name.to_string());
+        } else {
+            mount_to(dir.clone(), disk.clone(), name.to_string());
+        }
+        dir
+    };
+
+    *DISK_LINKS.lock() = Some(DiskLinks {
+        by_uuid: make_dir("by-uuid", true),
+        by_partuuid: make_dir("by-partuuid", false),
+        by_label: make_dir("by-label", true),
+        by_path: make_dir("by-path", false),
+        links: Vec::new(),
+        pending: Vec::new(),
+    });
+}
+
+/// A directory whose aliases come from the filesystems on the disks.
+/// Reading a filesystem means disk I/O, so the nodes are only probed once
+/// somebody looks inside.
+struct ProbedDir {
+    dir: InodeRef,
+}
+
+impl Inode for ProbedDir {
+    fn when_mounted(&mut self, path: String, father: Option<InodeRef>) {
+        self.dir.write().when_mounted(path, father);
+    }
+
+    fn when_umounted(&mut self) {
+        self.dir.write().when_umounted();
+    }
+
+    fn get_path(&self) -> String {
+        self.dir.read().get_path()
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        probe_pending();
+        self.dir.read().open(name)
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        probe_pending();
+        self.dir.read().list()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+}
+
+/// Labels may contain characters that can't appear in a file name.
+fn link_name(name: &str) -> String {
+    name.chars()
+        .map(|c| if c == '/' || c == ' ' { '_' } else { c })
+        .collect()
+}
+
+fn add_link(dir: &InodeRef, name: &str, target: InodeRef) {
+    let name = link_name(name);
+    // the first device keeps a name if two of them claim it
+    if name.is_empty() || dir.read().open(name.clone()).is_some() {
+        return;
+    }
+    let link = Arc::new(RwLock::new(LinkInode::new(target.clone())));
+    mount_to(link, dir.clone(), name.clone());
+
+    if let Some(links) = DISK_LINKS.lock().as_mut() {
+        links.links.push((dir.clone(), name, target));
+    }
+}
+
+/// Publish the persistent names of a disk or partition.
+///
+/// `bus_path` names where the device is attached and `partuuid` comes from
+/// the partition table. The filesystem UUID and label are probed later, see
+/// [`ProbedDir`].
+pub fn register(node: InodeRef, bus_path: Option<&str>, partuuid: Option<String>) {
+    let (by_partuuid, by_path) = match DISK_LINKS.lock().as_mut() {
+        Some(links) => {
+            links.pending.push(node.clone());
+            (links.by_partuuid.clone(), links.by_path.clone())
+        }
+        None => return,
+    };
+
+    if let Some(bus_path) = bus_path {
+        add_link(&by_path, bus_path, node.clone());
+    }
+
+    if let Some(partuuid) = partuuid {
+        add_link(&by_partuuid, &partuuid, node);
+    }
+}
+
+/// Add the UUID and label aliases of the nodes registered since the last
+/// call.
+fn probe_pending() {
+    let (by_uuid, by_label, pending) = match DISK_LINKS.lock().as_mut() {
+        Some(links) => (
+            links.by_uuid.clone(),
+            links.by_label.clone(),
+            core::mem::take(&mut links.pending),
+        ),
+        None => return,
+    };
+
+    for node in pending {
+        if let Some(info) = probe_volume(&node) {
+            if let Some(uuid) = info.uuid {
+                add_link(&by_uuid, &uuid, node.clone());
+            }
+            if let Some(label) = info.label {
+                add_link(&by_label, &label, node);
+            }
+        }
+    }
+}
+
+/// Remove every alias of a node that is going away.
+pub fn unregister(node: &InodeRef) {
+    let mut removed = Vec::new();
+    if let Some(links) = DISK_LINKS.lock().as_mut() {
+        links.pending.retain(|pending| !Arc::ptr_eq(pending, node));
+        links.links.retain(|(dir, name, target)| {
+            if Arc::ptr_eq(target, node) {
+                removed.push((dir.clone(), name.clone()));
+                false
+            } else {
+                true
+            }
+        });
+    }
+
+    for (dir, name) in removed {
+        dir.read().umount(name);
+    }
+}
+
+/// The node an alias under `/dev/disk` stands for, or `node` itself if it
+/// isn't one.
+pub fn link_target(node: &InodeRef) -> InodeRef {
+    let links = DISK_LINKS.lock();
+    links
+        .as_ref()
+        .and_then(|links| {
+            links.links.iter().find_map(|(dir, name, target)| {
+                let link = dir.read().open(name.clone())?;
+                Arc::ptr_eq(&link, node).then(|| target.clone())
+            })
+        })
+        .unwrap_or_else(|| node.clone())
+}
+
+/// Turn a `UUID=`, `PARTUUID=` or `LABEL=` specifier into the path of the
+/// matching alias. Anything else is returned unchanged.
+pub fn resolve_spec(spec: String) -> String {
+    let dirs = [
+        ("UUID=", "by-uuid"),
+        ("PARTUUID=", "by-partuuid"),
+        ("LABEL=", "by-label"),
+    ];
+
+    for (prefix, dir) in dirs {
+        if let Some(value) = spec.strip_prefix(prefix) {
+            return format!("{}/{}/{}", DISK_LINKS_PATH, dir, link_name(value));
+        }
+    }
+
+    spec
+}
@@ -0,0 +1,85 @@
+use alloc::string::String;
+use framework::drivers::serial::SERIAL;
+
+use crate::fs::vfs::inode::{Inode, InodeRef};
+
+use super::line_discipline::LineDiscipline;
+
+fn send(bytes: &[u8]) {
+    let mut serial = SERIAL.lock();
+    for &byte in bytes {
+        if byte == b'\n' {
+            serial.send_raw(b'\r');
+        }
+        serial.send_raw(byte);
+    }
+}
+
+static LINE_DISCIPLINE: LineDiscipline = LineDiscipline::new(send);
+
+/// Drain the UART receive register into the line discipline as bytes come
+/// in, so nothing is lost while no reader is waiting.
+///
+/// The framework doesn't route IRQ 4 and keeps the UART interrupts off, so
+/// the port is polled. The thread gives up the CPU whenever the receive
+/// register is empty.
+pub fn serial_receive_thread() {
+    loop {
+        let received = SERIAL.lock().try_receive();
+        match received {
+            Ok(byte) => {
+                let byte = match byte {
+                    // terminals send CR for enter and DEL for backspace
+                    b'\r' => b'\n',
+                    0x7f => 8,
+                    byte => byte,
+                };
+                LINE_DISCIPLINE.receive(byte);
+            }
+            Err(_) => framework::task::schedule(),
+        }
+    }
+}
+
+/// The first serial port, as `/dev/ttyS0`.
+pub struct SerialInode {
+    path: String,
+}
+
+impl SerialInode {
+    pub fn new() -> Self {
+        Self {
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for SerialInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    /// Block for input, a whole line at a time unless the port is in raw
+    /// mode.
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        LINE_DISCIPLINE.read(buf)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        send(buf);
+        buf.len()
+    }
+
+    fn flush(&self) {}
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        LINE_DISCIPLINE.ioctl(cmd, arg)
+    }
+}
@@ -1,45 +1,106 @@
-use crate::fs::vfs::inode::Inode;
-use crate::user::get_current_thread;
+use crate::{
+    drivers::{clock, rng},
+    fs::vfs::inode::Inode,
+};
 use alloc::string::String;
-use alloc::sync::{Arc, Weak};
-use alloc::vec::Vec;
-use crossbeam_queue::ArrayQueue;
 use framework::drivers::keyboard::get_scancode;
-use framework::task::thread::ThreadState;
-use framework::task::Thread;
-use pc_keyboard::{layouts, DecodedKey, HandleControl, KeyCode, Keyboard, ScancodeSet1};
-use spin::{Lazy, Mutex, RwLock};
+use pc_keyboard::{DecodedKey, KeyCode};
 
-static BYTES: Lazy<ArrayQueue<char>> = Lazy::new(|| ArrayQueue::new(1024));
-static WAIT_LIST: Mutex<Vec<Weak<RwLock<Thread>>>> = Mutex::new(Vec::new());
+use super::{
+    keymap::{self, KeyboardDecoder, KBD_GET_LAYOUT, KBD_SET_SCANCODE_SET},
+    line_discipline::{LineDiscipline, WindowSize},
+};
+
+fn echo(bytes: &[u8]) {
+    if let Ok(s) = core::str::from_utf8(bytes) {
+        framework::print!("{}", s);
+    }
+}
+
+static LINE_DISCIPLINE: LineDiscipline = LineDiscipline::new(echo);
+
+/// Work out the size of the terminal in rows and columns from the screen
+/// and the size of one character cell of the console font, in pixels.
+pub fn set_cell_size(cell_width: usize, cell_height: usize) {
+    if cell_width == 0 || cell_height == 0 {
+        return;
+    }
+    let display = framework::drivers::display::Display::new();
+    let (width, height) = (display.width(), display.height());
+
+    LINE_DISCIPLINE.set_window_size(WindowSize {
+        rows: (height / cell_height).min(u16::MAX as usize) as u16,
+        cols: (width / cell_width).min(u16::MAX as usize) as u16,
+        x_pixels: width.min(u16::MAX as usize) as u16,
+        y_pixels: height.min(u16::MAX as usize) as u16,
+    });
+}
+
+/// What an xterm sends for the keys that don't stand for a character.
+fn escape_sequence(key: KeyCode) -> Option<&'static str> {
+    let sequence = match key {
+        KeyCode::ArrowUp => "\x1b[A",
+        KeyCode::ArrowDown => "\x1b[B",
+        KeyCode::ArrowRight => "\x1b[C",
+        KeyCode::ArrowLeft => "\x1b[D",
+        KeyCode::Home => "\x1b[H",
+        KeyCode::End => "\x1b[F",
+        KeyCode::Insert => "\x1b[2~",
+        KeyCode::Delete => "\x1b[3~",
+        KeyCode::PageUp => "\x1b[5~",
+        KeyCode::PageDown => "\x1b[6~",
+        KeyCode::F1 => "\x1bOP",
+        KeyCode::F2 => "\x1bOQ",
+        KeyCode::F3 => "\x1bOR",
+        KeyCode::F4 => "\x1bOS",
+        KeyCode::F5 => "\x1b[15~",
+        KeyCode::F6 => "\x1b[17~",
+        KeyCode::F7 => "\x1b[18~",
+        KeyCode::F8 => "\x1b[19~",
+        KeyCode::F9 => "\x1b[20~",
+        KeyCode::F10 => "\x1b[21~",
+        KeyCode::F11 => "\x1b[23~",
+        KeyCode::F12 => "\x1b[24~",
+        _ => return None,
+    };
+    Some(sequence)
+}
 
 pub fn keyboard_parse_thread() {
     fn push_char(ch: char) {
-        BYTES.push(ch).expect("Buffer full");
-        for thread in WAIT_LIST.lock().iter() {
-            thread.upgrade().unwrap().write().state = ThreadState::Ready;
-        }
-        WAIT_LIST.lock().clear();
+        LINE_DISCIPLINE.receive_char(ch);
+    }
+
+    fn push_str(s: &str) {
+        s.chars().for_each(push_char);
     }
 
-    let mut keyboard = Keyboard::new(
-        ScancodeSet1::new(),
-        layouts::Us104Key,
-        HandleControl::Ignore,
-    );
+    let mut keyboard = KeyboardDecoder::new();
 
     loop {
         if let Some(scan_code) = get_scancode() {
-            if let Ok(Some(key_event)) = keyboard.add_byte(scan_code) {
-                if let Some(key) = keyboard.process_keyevent(key_event) {
-                    match key {
-                        DecodedKey::RawKey(raw_key) => match raw_key {
-                            KeyCode::Backspace => push_char(8 as char),
-                            KeyCode::Oem7 => push_char('\\'),
-                            _ => {}
-                        },
-                        DecodedKey::Unicode(ch) => push_char(ch),
+            // the keyboard interrupt belongs to the framework, the scancode
+            // is picked up shortly after it
+            let sample = clock::uptime_ms() ^ ((scan_code as u64) << 56);
+            rng::add_entropy(sample);
+
+            if let Some(key) = keyboard.add_byte(scan_code) {
+                match key {
+                    DecodedKey::RawKey(raw_key) => match raw_key {
+                        KeyCode::Backspace => push_char(8 as char),
+                        KeyCode::Oem7 => push_char('\\'),
+                        key => {
+                            if let Some(sequence) = escape_sequence(key) {
+                                push_str(sequence);
+                            }
+                        }
+                    },
+                    // the layouts give Delete as DEL, which would erase like
+                    // backspace does
+                    DecodedKey::Unicode('\x7f') => {
+                        push_str(escape_sequence(KeyCode::Delete).unwrap())
                     }
+                    DecodedKey::Unicode(ch) => push_char(ch),
                 }
             }
         }
@@ -76,25 +137,10 @@ impl Inode for Terminal {
         self.path.clone()
     }
 
+    /// Block for input, a whole line at a time unless the terminal is in
+    /// raw mode.
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
-        let mut write = 0;
-        while let Some(byte) = BYTES.pop() {
-            if write >= buf.len() {
-                break;
-            }
-            buf[write] = byte as u8;
-            write += 1;
-        }
-        if write < buf.len() {
-            let current_thread = get_current_thread();
-            WAIT_LIST.lock().push(Arc::downgrade(&current_thread));
-            current_thread.write().state = ThreadState::Waiting;
-            //x86_64::instructions::interrupts::enable();
-            //schedule();
-            //while current_thread.read().state == ThreadState::Waiting {}
-            //x86_64::instructions::interrupts::disable();
-        }
-        write
+        LINE_DISCIPLINE.read(buf)
     }
 
     fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
@@ -104,4 +150,11 @@ impl Inode for Terminal {
         }
         0
     }
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            KBD_GET_LAYOUT..=KBD_SET_SCANCODE_SET => keymap::ioctl(cmd, arg),
+            _ => LINE_DISCIPLINE.ioctl(cmd, arg),
+        }
+    }
 }
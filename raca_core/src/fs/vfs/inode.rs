@@ -1,5 +1,8 @@
 use alloc::{string::String, sync::Arc, vec::Vec};
 use spin::RwLock;
+use x86_64::PhysAddr;
+
+use super::{dev::partition::PartitionInfo, socket::Socket};
 
 pub type InodeRef = Arc<RwLock<dyn Inode>>;
 
@@ -7,6 +10,8 @@ pub type InodeRef = Arc<RwLock<dyn Inode>>;
 pub enum InodeTy {
     Dir = 0,
     File = 1,
+    Fifo = 2,
+    Socket = 3,
 }
 
 #[repr(C)]
@@ -34,6 +39,9 @@ pub trait Inode: Sync + Send {
     fn mount(&self, _node: InodeRef, _name: String) {
         unimplemented!()
     }
+    /// Remove the node mounted as `name`. Does nothing in directories that
+    /// can't hold mounted nodes.
+    fn umount(&self, _name: String) {}
 
     fn read_at(&self, _offset: usize, _buf: &mut [u8]) -> usize {
         0
@@ -48,8 +56,15 @@ pub trait Inode: Sync + Send {
     fn open(&self, _name: String) -> Option<InodeRef> {
         unimplemented!()
     }
+    /// What a file descriptor opened on this node refers to, for nodes like
+    /// FIFOs that hand every opener its own end. `None` means the node itself.
+    fn open_end(&self, _write: bool) -> Option<InodeRef> {
+        None
+    }
+    /// Make a new node of type `ty` named `name`. `None` if the directory
+    /// can't hold that type, or can't create nodes at all.
     fn create(&self, _name: String, _ty: InodeTy) -> Option<InodeRef> {
-        unimplemented!()
+        None
     }
     fn list(&self) -> Vec<FileInfo> {
         Vec::new()
@@ -58,6 +73,28 @@ pub trait Inode: Sync + Send {
     fn inode_type(&self) -> InodeTy {
         InodeTy::File
     }
+
+    /// Partition table entry of a partition device node.
+    fn partition_info(&self) -> Option<PartitionInfo> {
+        None
+    }
+
+    /// Device specific request. What `cmd` means and whether `arg` points
+    /// into user memory is up to the device.
+    fn ioctl(&self, _cmd: usize, _arg: usize) -> Option<usize> {
+        None
+    }
+
+    /// Physical page holding byte `offset` of the node, for nodes that can be
+    /// mapped into a process.
+    fn mmap(&self, _offset: usize) -> Option<PhysAddr> {
+        None
+    }
+
+    /// The socket behind a descriptor made by the `socket` syscall.
+    fn as_socket(&self) -> Option<&Socket> {
+        None
+    }
 }
 
 pub fn mount_to(node: InodeRef, to: InodeRef, name: String) {
@@ -1,26 +1,248 @@
-use alloc::vec::Vec;
+use alloc::{collections::VecDeque, string::String, sync::Arc, vec, vec::Vec};
+use framework::memory::{addr_to_mut_ref, write_for_syscall};
+use spin::{Mutex, RwLock};
+use x86_64::VirtAddr;
 
-use super::inode::Inode;
+use crate::user::{get_current_process, get_current_process_id, wait_queue::WaitQueue};
 
-pub struct IpcNode {
-    queue: Vec<u8>,
+use super::{
+    inode::{mount_to, Inode, InodeRef},
+    root::RootFS,
+};
+
+/// Send the message described by a [`MqRequest`] at `arg`.
+pub const MQ_SEND: usize = 1;
+/// Receive into the buffer of a [`MqRequest`] at `arg`. Returns the message
+/// length plus one and fills in the sender.
+pub const MQ_RECEIVE: usize = 2;
+/// Fill a [`MqAttr`] at `arg`.
+pub const MQ_GET_ATTR: usize = 3;
+
+/// Waiting forever
+pub const NO_TIMEOUT: u64 = u64::MAX;
+
+/// Largest message size a queue can be created with
+pub const MAX_MQ_MESSAGE_SIZE: usize = 64 * 1024;
+/// Most messages a queue can be created to hold
+pub const MAX_MQ_CAPACITY: usize = 256;
+
+#[repr(C)]
+pub struct MqRequest {
+    pub buf_addr: u64,
+    pub len: u64,
+    /// Milliseconds to wait for room or for a message, [`NO_TIMEOUT`] to wait
+    /// as long as it takes
+    pub timeout_ms: u64,
+    /// Pid of the sender, filled in on receive
+    pub sender: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy)]
+pub struct MqAttr {
+    pub max_message_size: u64,
+    pub capacity: u64,
+    /// Messages currently queued
+    pub messages: u64,
+}
+
+struct Message {
+    sender: u64,
+    data: Vec<u8>,
+}
+
+/// A queue of whole messages, bounded in size and count.
+pub struct MessageQueue {
+    messages: Mutex<VecDeque<Message>>,
+    max_message_size: usize,
+    capacity: usize,
+    not_empty: WaitQueue,
+    not_full: WaitQueue,
+}
+
+impl MessageQueue {
+    pub fn new(max_message_size: usize, capacity: usize) -> Arc<Self> {
+        Arc::new(Self {
+            messages: Mutex::new(VecDeque::new()),
+            max_message_size,
+            capacity,
+            not_empty: WaitQueue::new(),
+            not_full: WaitQueue::new(),
+        })
+    }
+
+    /// Queue `data`, waiting up to `timeout_ms` for room.
+    pub fn send(&self, data: &[u8], timeout_ms: Option<u64>) -> Option<()> {
+        if data.len() > self.max_message_size {
+            return None;
+        }
+        let sender = get_current_process_id().0;
+
+        let mut message = Some(Message {
+            sender,
+            data: data.to_vec(),
+        });
+        self.not_full.wait_timeout(timeout_ms, || {
+            let mut messages = self.messages.lock();
+            if messages.len() >= self.capacity {
+                return None;
+            }
+            messages.push_back(message.take()?);
+            Some(())
+        })?;
+
+        self.not_empty.wake_all();
+        Some(())
+    }
+
+    /// Take the oldest message, waiting up to `timeout_ms` for one. A message
+    /// longer than `buf` is cut short. Returns the length and the sender.
+    pub fn receive(&self, buf: &mut [u8], timeout_ms: Option<u64>) -> Option<(usize, u64)> {
+        let message = self
+            .not_empty
+            .wait_timeout(timeout_ms, || self.messages.lock().pop_front())?;
+        self.not_full.wake_all();
+
+        let len = buf.len().min(message.data.len());
+        buf[..len].copy_from_slice(&message.data[..len]);
+        Some((len, message.sender))
+    }
+
+    fn attr(&self) -> MqAttr {
+        MqAttr {
+            max_message_size: self.max_message_size as u64,
+            capacity: self.capacity as u64,
+            messages: self.messages.lock().len() as u64,
+        }
+    }
+}
+
+/// A message queue as a node under `/dev/mq`. Plain writes send one message
+/// and plain reads receive one, both waiting as long as needed.
+pub struct MqInode {
+    queue: Arc<MessageQueue>,
+    path: String,
+}
+
+impl MqInode {
+    pub fn new(queue: Arc<MessageQueue>) -> Self {
+        Self {
+            queue,
+            path: String::new(),
+        }
+    }
 }
 
-impl Inode for IpcNode {
-    fn when_mounted(&self, _path: alloc::string::String, _father: Option<super::inode::InodeRef>) {
-        unimplemented!()
+impl Inode for MqInode {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {
+        self.path.clear();
     }
 
-    fn when_umounted(&self) {
-        unimplemented!()
+    fn get_path(&self) -> String {
+        self.path.clone()
     }
 
-    fn get_path(&self) -> alloc::string::String {
-        "".into()
+    fn size(&self) -> usize {
+        self.queue.messages.lock().len()
     }
 
-    fn write_at(&self, _offset: usize, _buf: &[u8]) {
-        ;
-    }    
+    fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
+        self.queue.receive(buf, None).map_or(0, |(len, _)| len)
+    }
+
+    fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
+        self.queue.send(buf, None).map_or(0, |_| buf.len())
+    }
+
+    fn flush(&self) {}
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        if cmd == MQ_GET_ATTR {
+            write_for_syscall(VirtAddr::new(arg as u64), &[self.queue.attr()]);
+            return Some(1);
+        }
+
+        let request: &mut MqRequest = addr_to_mut_ref(VirtAddr::new(arg as u64));
+        let timeout_ms = (request.timeout_ms != NO_TIMEOUT).then_some(request.timeout_ms);
+        let buf_addr = VirtAddr::new(request.buf_addr);
+        let len = request.len as usize;
+
+        match cmd {
+            MQ_SEND => {
+                if len > self.queue.max_message_size {
+                    return None;
+                }
+                let mut buf = vec![0; len];
+                get_current_process()
+                    .read()
+                    .page_table
+                    .read(buf_addr, len, &mut buf)
+                    .ok()?;
+                self.queue.send(&buf, timeout_ms)?;
+                Some(1)
+            }
+            MQ_RECEIVE => {
+                let mut buf = vec![0; len.min(self.queue.max_message_size)];
+                let (len, sender) = self.queue.receive(&mut buf, timeout_ms)?;
+                write_for_syscall(buf_addr, &buf[..len]);
+                request.sender = sender;
+                Some(len + 1)
+            }
+            _ => None,
+        }
+    }
 }
 
+static MQ_DIR: Mutex<Option<InodeRef>> = Mutex::new(None);
+
+/// Create `/dev/mq`.
+pub fn init(dev_fs: InodeRef) {
+    let mq_dir = RootFS::new();
+    mount_to(mq_dir.clone(), dev_fs, "mq".into());
+    *MQ_DIR.lock() = Some(mq_dir);
+}
+
+/// Look up the queue `/dev/mq/<name>`, creating it with the given limits if
+/// there is none and `max_message_size` is not zero. The limits may not be
+/// above [`MAX_MQ_MESSAGE_SIZE`] and [`MAX_MQ_CAPACITY`].
+pub fn open_queue(name: String, max_message_size: usize, capacity: usize) -> Option<InodeRef> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return None;
+    }
+    // held until the new queue is in place, so two creators can't race
+    let mq_dir_guard = MQ_DIR.lock();
+    let mq_dir = mq_dir_guard.as_ref()?;
+
+    if let Some(queue) = mq_dir.read().open(name.clone()) {
+        return Some(queue);
+    }
+    if max_message_size == 0 || capacity == 0 {
+        return None;
+    }
+    if max_message_size > MAX_MQ_MESSAGE_SIZE || capacity > MAX_MQ_CAPACITY {
+        return None;
+    }
+
+    let queue: InodeRef = Arc::new(RwLock::new(MqInode::new(MessageQueue::new(
+        max_message_size,
+        capacity,
+    ))));
+    mount_to(queue.clone(), mq_dir.clone(), name);
+    Some(queue)
+}
+
+/// Remove `/dev/mq/<name>`. Processes that have it open keep using it.
+pub fn unlink_queue(name: String) -> Option<()> {
+    if name == "." || name == ".." {
+        return None;
+    }
+    let mq_dir = MQ_DIR.lock().clone()?;
+    mq_dir.read().open(name.clone())?;
+    mq_dir.read().umount(name);
+    Some(())
+}
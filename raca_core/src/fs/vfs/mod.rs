@@ -1,6 +1,10 @@
 pub mod cache;
 pub mod dev;
 pub mod inode;
-//pub mod ipc;
+pub mod ipc;
 pub mod pipe;
+pub mod proc;
 pub mod root;
+pub mod shm;
+pub mod socket;
+pub mod tmpfs;
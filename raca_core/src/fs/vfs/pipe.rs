@@ -1,38 +1,139 @@
-use alloc::{
-    collections::vec_deque::VecDeque,
-    string::String,
-    sync::Weak,
-    vec::Vec,
-};
-use framework::{
-    ref_to_mut,
-    task::{thread::ThreadState, Thread},
-};
-use spin::RwLock;
-
-use super::inode::Inode;
+use alloc::{collections::vec_deque::VecDeque, string::String, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::{Mutex, RwLock};
 
+use crate::user::{signal::INTERRUPTED, wait_queue::WaitQueue};
+
+use super::inode::{Inode, InodeRef, InodeTy};
+
+/// Bytes a pipe holds before writers have to wait
+pub const PIPE_CAPACITY: usize = 64 * 1024;
+
+/// What a write returns when no reader is left to take any of it
+pub const BROKEN_PIPE: usize = usize::MAX;
+
+/// The buffer shared by the two ends of a pipe.
 pub struct Pipe {
-    buffer: VecDeque<u8>,
-    path: String,
-    reader_requier: Vec<Weak<RwLock<Thread>>>,
+    buffer: Mutex<VecDeque<u8>>,
+    /// Open read ends, when this drops to zero writes fail
+    readers: AtomicUsize,
+    /// Open write ends, when this drops to zero reads return end of file
+    writers: AtomicUsize,
+    readable: WaitQueue,
+    writable: WaitQueue,
+    /// Opens of a FIFO waiting for the other side to show up
+    peer_opened: WaitQueue,
 }
 
 impl Pipe {
-    pub fn new() -> Self {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self {
+            buffer: Mutex::new(VecDeque::new()),
+            readers: AtomicUsize::new(0),
+            writers: AtomicUsize::new(0),
+            readable: WaitQueue::new(),
+            writable: WaitQueue::new(),
+            peer_opened: WaitQueue::new(),
+        })
+    }
+
+    pub fn has_readers(&self) -> bool {
+        self.readers.load(Ordering::SeqCst) > 0
+    }
+
+    pub fn has_writers(&self) -> bool {
+        self.writers.load(Ordering::SeqCst) > 0
+    }
+
+    /// Block until there is data or no writer is left, then take as much as
+    /// fits into `buf`. Returns 0 at end of file, and [`INTERRUPTED`] if a
+    /// signal came first.
+    fn read(&self, buf: &mut [u8]) -> usize {
+        if buf.is_empty() {
+            return 0;
+        }
+
+        let len = self.readable.wait_until(|| {
+            let mut buffer = self.buffer.lock();
+            if buffer.is_empty() {
+                return (!self.has_writers()).then_some(0);
+            }
+            let len = buf.len().min(buffer.len());
+            for (dst, src) in buf.iter_mut().zip(buffer.drain(..len)) {
+                *dst = src;
+            }
+            Some(len)
+        });
+        let Some(len) = len else {
+            return INTERRUPTED;
+        };
+
+        self.writable.wake_all();
+        len
+    }
+
+    /// Write all of `buf`, waiting for readers to make room as needed.
+    /// Returns how much got in before the last reader went away or a signal
+    /// came in. If nothing did, that is [`BROKEN_PIPE`] or [`INTERRUPTED`].
+    fn write(&self, buf: &[u8]) -> usize {
+        let mut written = 0;
+        while written < buf.len() {
+            let chunk = self.writable.wait_until(|| {
+                if !self.has_readers() {
+                    return Some(None);
+                }
+                let mut buffer = self.buffer.lock();
+                let space = PIPE_CAPACITY - buffer.len();
+                if space == 0 {
+                    return None;
+                }
+                let len = space.min(buf.len() - written);
+                buffer.extend(&buf[written..written + len]);
+                Some(Some(len))
+            });
+
+            match chunk {
+                Some(Some(len)) => written += len,
+                Some(None) if written == 0 => return BROKEN_PIPE,
+                None if written == 0 => return INTERRUPTED,
+                _ => break,
+            }
+            self.readable.wake_all();
+        }
+        written
+    }
+}
+
+/// The read end of a pipe. Dropping the last reference to it, when every
+/// descriptor for it is closed, counts as closing the end.
+pub struct PipeReader {
+    pipe: Arc<Pipe>,
+    path: String,
+}
+
+impl PipeReader {
+    pub fn new(pipe: Arc<Pipe>) -> Self {
+        pipe.readers.fetch_add(1, Ordering::SeqCst);
         Self {
-            buffer: VecDeque::new(),
+            pipe,
             path: String::new(),
-            reader_requier: Vec::new(),
         }
     }
 }
 
-impl Inode for Pipe {
-    fn when_mounted(&mut self, path: alloc::string::String, _father: Option<super::inode::InodeRef>) {
+impl Drop for PipeReader {
+    fn drop(&mut self) {
+        self.pipe.readers.fetch_sub(1, Ordering::SeqCst);
+        self.pipe.writable.wake_all();
+    }
+}
+
+impl Inode for PipeReader {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
         self.path.clear();
         self.path.push_str(path.as_str());
     }
+
     fn when_umounted(&mut self) {
         self.path.clear();
     }
@@ -42,26 +143,127 @@ impl Inode for Pipe {
     }
 
     fn size(&self) -> usize {
-        self.buffer.len()
+        self.pipe.buffer.lock().len()
     }
 
     fn read_at(&self, _offset: usize, buf: &mut [u8]) -> usize {
-        let mut write = 0;
-        while let Some(data) = ref_to_mut(self).buffer.pop_back() {
-            buf[write] = data;
-            write += 1;
+        self.pipe.read(buf)
+    }
+
+    fn flush(&self) {}
+}
+
+/// The write end of a pipe, see [`PipeReader`].
+pub struct PipeWriter {
+    pipe: Arc<Pipe>,
+    path: String,
+}
+
+impl PipeWriter {
+    pub fn new(pipe: Arc<Pipe>) -> Self {
+        pipe.writers.fetch_add(1, Ordering::SeqCst);
+        Self {
+            pipe,
+            path: String::new(),
         }
-        write
+    }
+}
+
+impl Drop for PipeWriter {
+    fn drop(&mut self) {
+        self.pipe.writers.fetch_sub(1, Ordering::SeqCst);
+        self.pipe.readable.wake_all();
+    }
+}
+
+impl Inode for PipeWriter {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {
+        self.path.clear();
+    }
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.pipe.buffer.lock().len()
     }
 
     fn write_at(&self, _offset: usize, buf: &[u8]) -> usize {
-        for &data in buf.iter() {
-            ref_to_mut(self).buffer.push_front(data);
-        }
-        for thread in self.reader_requier.iter() {
-            thread.upgrade().unwrap().write().state = ThreadState::Ready;
+        self.pipe.write(buf)
+    }
+
+    fn flush(&self) {}
+}
+
+/// A new pipe as its read and write end.
+pub fn pipe() -> (InodeRef, InodeRef) {
+    let pipe = Pipe::new();
+    let reader = Arc::new(RwLock::new(PipeReader::new(pipe.clone())));
+    let writer = Arc::new(RwLock::new(PipeWriter::new(pipe)));
+    (reader, writer)
+}
+
+/// A named pipe. Every open gets a fresh end of the one shared [`Pipe`], and
+/// blocks until the other side has been opened as well.
+pub struct Fifo {
+    pipe: Arc<Pipe>,
+    path: String,
+}
+
+impl Fifo {
+    pub fn new() -> Self {
+        Self {
+            pipe: Pipe::new(),
+            path: String::new(),
         }
-        ref_to_mut(self).reader_requier.clear();
-        buf.len()
+    }
+}
+
+impl Inode for Fifo {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {
+        self.path.clear();
+    }
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.pipe.buffer.lock().len()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Fifo
+    }
+
+    fn open_end(&self, write: bool) -> Option<InodeRef> {
+        let pipe = self.pipe.clone();
+        let end: InodeRef = if write {
+            Arc::new(RwLock::new(PipeWriter::new(pipe.clone())))
+        } else {
+            Arc::new(RwLock::new(PipeReader::new(pipe.clone())))
+        };
+        pipe.peer_opened.wake_all();
+
+        pipe.peer_opened.wait_until(|| {
+            let peer = if write {
+                pipe.has_readers()
+            } else {
+                pipe.has_writers()
+            };
+            peer.then_some(())
+        })?;
+        Some(end)
     }
 }
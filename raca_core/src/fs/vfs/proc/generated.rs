@@ -0,0 +1,65 @@
+use alloc::{boxed::Box, string::String};
+use spin::Once;
+
+use crate::fs::vfs::inode::{Inode, InodeRef};
+
+type Generator = Box<dyn Fn() -> Option<String> + Send + Sync>;
+
+/// A read-only file whose text is produced when it is first looked at.
+///
+/// Every lookup of a `/proc` path makes a new node, so each opener gets a
+/// consistent snapshot and reading it in pieces doesn't generate the text
+/// over and over.
+pub struct GeneratedFile {
+    generate: Generator,
+    text: Once<Option<String>>,
+    path: String,
+}
+
+impl GeneratedFile {
+    pub fn new(
+        path: String,
+        generate: impl Fn() -> Option<String> + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            generate: Box::new(generate),
+            text: Once::new(),
+            path,
+        }
+    }
+
+    fn text(&self) -> Option<&String> {
+        self.text.call_once(|| (self.generate)()).as_ref()
+    }
+}
+
+impl Inode for GeneratedFile {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.text().map_or(0, |text| text.len())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let Some(text) = self.text() else {
+            return 0;
+        };
+        let Some(src) = text.as_bytes().get(offset..) else {
+            return 0;
+        };
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        len
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,139 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use core::fmt::Write;
+use framework::task::{process::PROCESSES, scheduler::SCHEDULERS, Process};
+use generated::GeneratedFile;
+use process::ProcessDir;
+use spin::RwLock;
+
+use crate::{drivers::clock, fs::MOUNTS};
+
+use super::inode::{FileInfo, Inode, InodeRef, InodeTy};
+
+pub mod generated;
+pub mod process;
+
+const FILES: [(&str, fn() -> String); 4] = [
+    ("cpuinfo", cpuinfo),
+    ("meminfo", meminfo),
+    ("mounts", mounts),
+    ("uptime", uptime),
+];
+
+fn find_process(pid: u64) -> Option<Arc<RwLock<Process>>> {
+    PROCESSES
+        .read()
+        .iter()
+        .find(|process| process.read().id.0 == pid)
+        .cloned()
+}
+
+fn cpuinfo() -> String {
+    let mut text = String::new();
+    for (processor, lapic_id) in SCHEDULERS.lock().keys().enumerate() {
+        writeln!(
+            text,
+            "processor\t: {}\napicid\t\t: {}\n",
+            processor, lapic_id
+        )
+        .unwrap();
+    }
+    text
+}
+
+fn meminfo() -> String {
+    let (total, free) = framework::memory::memory_usage();
+    format!(
+        "MemTotal:\t{} kB\nMemFree:\t{} kB\nMemUsed:\t{} kB\n",
+        total / 1024,
+        free / 1024,
+        (total - free) / 1024,
+    )
+}
+
+fn mounts() -> String {
+    let mut text = String::new();
+    for mount in MOUNTS.lock().iter() {
+        writeln!(text, "{} {} {}", mount.source, mount.target, mount.fs_type).unwrap();
+    }
+    text
+}
+
+fn uptime() -> String {
+    let ms = clock::uptime_ms();
+    format!("{}.{:02}\n", ms / 1000, ms % 1000 / 10)
+}
+
+/// `/proc`, generated from the process table and kernel state when read
+pub struct ProcFS {
+    this: Weak<RwLock<ProcFS>>,
+    father: Option<InodeRef>,
+    path: String,
+}
+
+impl ProcFS {
+    pub fn new() -> InodeRef {
+        Arc::new_cyclic(|this| {
+            RwLock::new(Self {
+                this: this.clone(),
+                father: None,
+                path: String::new(),
+            })
+        })
+    }
+}
+
+impl Inode for ProcFS {
+    fn when_mounted(&mut self, path: String, father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+        self.father = father;
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        let mut list: Vec<FileInfo> = FILES
+            .iter()
+            .map(|(name, _)| FileInfo::new(name.to_string(), InodeTy::File))
+            .collect();
+        for process in PROCESSES.read().iter() {
+            let pid = process.read().id.0;
+            list.push(FileInfo::new(pid.to_string(), InodeTy::Dir));
+        }
+        list
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        let this: InodeRef = self.this.upgrade()?;
+        match name.as_str() {
+            "." => Some(this),
+            ".." => self.father.clone(),
+            _ => {
+                if let Some((_, generate)) = FILES.iter().find(|(file, _)| *file == name) {
+                    let generate = *generate;
+                    let path = format!("{}{}", self.path, name);
+                    return Some(Arc::new(RwLock::new(GeneratedFile::new(path, move || {
+                        Some(generate())
+                    }))));
+                }
+
+                let process = find_process(name.parse().ok()?)?;
+                let path = format!("{}{}/", self.path, name);
+                Some(Arc::new(RwLock::new(ProcessDir::new(&process, this, path))))
+            }
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use framework::task::{process::ProcessId, thread::ThreadState, Process};
+use spin::RwLock;
+
+use crate::{
+    fs::{
+        operation::{cwd_of, file_descriptors_of},
+        vfs::{
+            dev::link::LinkInode,
+            inode::{FileInfo, Inode, InodeRef, InodeTy},
+        },
+    },
+    user::cmdline_of,
+};
+
+use super::generated::GeneratedFile;
+
+const ENTRIES: [(&str, InodeTy); 4] = [
+    ("cmdline", InodeTy::File),
+    ("cwd", InodeTy::Dir),
+    ("fd", InodeTy::Dir),
+    ("status", InodeTy::File),
+];
+
+/// The words of the command line, each followed by a NUL. Processes started
+/// without one show their name.
+fn cmdline(process: &Process) -> String {
+    let cmdline = cmdline_of(process.id).unwrap_or_else(|| process.name.to_string());
+    let mut text = String::new();
+    for word in cmdline.split_whitespace() {
+        text.push_str(word);
+        text.push('\0');
+    }
+    text
+}
+
+fn status(process: &Process) -> String {
+    let running = process.threads.iter().any(|thread| {
+        let thread = thread.read();
+        thread.state != ThreadState::Blocked && thread.state != ThreadState::Waiting
+    });
+    let ppid = process
+        .father
+        .as_ref()
+        .and_then(|father| father.upgrade())
+        .map_or(0, |father| father.read().id.0);
+
+    format!(
+        "Name:\t{}\nState:\t{}\nPid:\t{}\nPPid:\t{}\nThreads:\t{}\n",
+        process.name,
+        if running {
+            "R (running)"
+        } else {
+            "S (sleeping)"
+        },
+        process.id.0,
+        ppid,
+        process.threads.len(),
+    )
+}
+
+/// `/proc/<pid>`
+pub struct ProcessDir {
+    process: Weak<RwLock<Process>>,
+    pid: ProcessId,
+    parent: InodeRef,
+    path: String,
+}
+
+impl ProcessDir {
+    pub fn new(process: &Arc<RwLock<Process>>, parent: InodeRef, path: String) -> Self {
+        Self {
+            process: Arc::downgrade(process),
+            pid: process.read().id,
+            parent,
+            path,
+        }
+    }
+
+    fn file(
+        &self,
+        name: &str,
+        generate: impl Fn(&Process) -> String + Send + Sync + 'static,
+    ) -> InodeRef {
+        let process = self.process.clone();
+        Arc::new(RwLock::new(GeneratedFile::new(
+            format!("{}{}", self.path, name),
+            move || Some(generate(&*process.upgrade()?.read())),
+        )))
+    }
+}
+
+impl Inode for ProcessDir {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        ENTRIES
+            .iter()
+            .map(|(name, ty)| FileInfo::new(name.to_string(), *ty))
+            .collect()
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        match name.as_str() {
+            ".." => Some(self.parent.clone()),
+            "cmdline" => Some(self.file("cmdline", cmdline)),
+            "status" => Some(self.file("status", status)),
+            "fd" => Some(Arc::new(RwLock::new(FdDir {
+                pid: self.pid,
+                path: format!("{}fd/", self.path),
+            }))),
+            "cwd" => {
+                let mut link = LinkInode::new(cwd_of(self.pid)?);
+                link.when_mounted(format!("{}cwd/", self.path), None);
+                Some(Arc::new(RwLock::new(link)))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// `/proc/<pid>/fd`, the open files of a process named by descriptor
+pub struct FdDir {
+    pid: ProcessId,
+    path: String,
+}
+
+impl Inode for FdDir {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        file_descriptors_of(self.pid)
+            .into_iter()
+            .map(|(fd, inode)| FileInfo::new(fd.to_string(), inode.read().inode_type()))
+            .collect()
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        let fd: usize = name.parse().ok()?;
+        let (_, inode) = file_descriptors_of(self.pid)
+            .into_iter()
+            .find(|(open_fd, _)| *open_fd == fd)?;
+
+        let mut link = LinkInode::new(inode);
+        link.when_mounted(format!("{}{}/", self.path, fd), None);
+        Some(Arc::new(RwLock::new(link)))
+    }
+}
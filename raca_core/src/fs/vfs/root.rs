@@ -44,6 +44,15 @@ impl Inode for RootFS {
         ref_to_mut(self).nodes.insert(name, node);
     }
 
+    fn umount(&self, name: String) {
+        if name == "." || name == ".." {
+            return;
+        }
+        if let Some(node) = ref_to_mut(self).nodes.remove(&name) {
+            node.write().when_umounted();
+        }
+    }
+
     fn get_path(&self) -> String {
         self.path.clone()
     }
@@ -0,0 +1,183 @@
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
+use framework::memory::KERNEL_PAGE_TABLE;
+use spin::{Mutex, RwLock};
+use x86_64::{structures::paging::Translate, PhysAddr, VirtAddr};
+
+use super::{
+    inode::{mount_to, Inode, InodeRef},
+    root::RootFS,
+};
+
+/// Grow the object to `arg` bytes. Objects never shrink, since their pages
+/// may still be mapped somewhere.
+pub const SHM_SET_SIZE: usize = 1;
+
+/// Largest size a shared memory object can have
+pub const MAX_SHM_SIZE: usize = 64 * 1024 * 1024;
+
+const PAGE_SIZE: usize = 4096;
+
+#[repr(C, align(4096))]
+struct ShmPage([u8; PAGE_SIZE]);
+
+struct ShmData {
+    /// Boxed one by one so growing never moves a page that is mapped
+    pages: Vec<Box<ShmPage>>,
+    size: usize,
+}
+
+/// A named piece of memory under `/dev/shm` that processes map to share it.
+///
+/// The pages belong to the object, so they are freed once the name is
+/// unlinked and the last descriptor and mapping of it are gone.
+pub struct SharedMemory {
+    data: Mutex<ShmData>,
+    path: String,
+}
+
+impl SharedMemory {
+    pub fn new() -> Self {
+        Self {
+            data: Mutex::new(ShmData {
+                pages: Vec::new(),
+                size: 0,
+            }),
+            path: String::new(),
+        }
+    }
+
+    /// Make the object `size` bytes large, zero filling the new part.
+    pub fn grow(&self, size: usize) -> Option<()> {
+        if size > MAX_SHM_SIZE {
+            return None;
+        }
+        let mut data = self.data.lock();
+        if size < data.size {
+            return None;
+        }
+
+        let pages = size.div_ceil(PAGE_SIZE);
+        let missing = pages.saturating_sub(data.pages.len());
+        data.pages.try_reserve_exact(missing).ok()?;
+        while data.pages.len() < pages {
+            data.pages.push(Box::new(ShmPage([0; PAGE_SIZE])));
+        }
+        data.size = size;
+        Some(())
+    }
+}
+
+impl Inode for SharedMemory {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {
+        self.path.clear();
+    }
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.data.lock().size
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let data = self.data.lock();
+        let len = buf.len().min(data.size.saturating_sub(offset));
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let start = pos % PAGE_SIZE;
+            let chunk = (PAGE_SIZE - start).min(len - done);
+            let page = &data.pages[pos / PAGE_SIZE].0;
+            buf[done..done + chunk].copy_from_slice(&page[start..start + chunk]);
+            done += chunk;
+        }
+        len
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let mut data = self.data.lock();
+        let len = buf.len().min(data.size.saturating_sub(offset));
+
+        let mut done = 0;
+        while done < len {
+            let pos = offset + done;
+            let start = pos % PAGE_SIZE;
+            let chunk = (PAGE_SIZE - start).min(len - done);
+            let page = &mut data.pages[pos / PAGE_SIZE].0;
+            page[start..start + chunk].copy_from_slice(&buf[done..done + chunk]);
+            done += chunk;
+        }
+        len
+    }
+
+    fn flush(&self) {}
+
+    fn ioctl(&self, cmd: usize, arg: usize) -> Option<usize> {
+        match cmd {
+            SHM_SET_SIZE => {
+                self.grow(arg)?;
+                Some(1)
+            }
+            _ => None,
+        }
+    }
+
+    fn mmap(&self, offset: usize) -> Option<PhysAddr> {
+        let data = self.data.lock();
+        let page = data.pages.get(offset / PAGE_SIZE)?;
+        let address = VirtAddr::new(page.0.as_ptr() as u64);
+        KERNEL_PAGE_TABLE.lock().translate_addr(address)
+    }
+}
+
+static SHM_DIR: Mutex<Option<InodeRef>> = Mutex::new(None);
+
+/// Create `/dev/shm`.
+pub fn init(dev_fs: InodeRef) {
+    let shm_dir = RootFS::new();
+    mount_to(shm_dir.clone(), dev_fs, "shm".into());
+    *SHM_DIR.lock() = Some(shm_dir);
+}
+
+/// Look up the object `/dev/shm/<name>`, creating it with `size` bytes if
+/// there is none and `size` is not zero.
+pub fn open_object(name: String, size: usize) -> Option<InodeRef> {
+    if name.is_empty() || name.contains('/') || name == "." || name == ".." {
+        return None;
+    }
+    // held until the new object is in place, so two creators can't race
+    let shm_dir_guard = SHM_DIR.lock();
+    let shm_dir = shm_dir_guard.as_ref()?;
+
+    if let Some(object) = shm_dir.read().open(name.clone()) {
+        return Some(object);
+    }
+    if size == 0 {
+        return None;
+    }
+
+    let object = SharedMemory::new();
+    object.grow(size)?;
+    let object: InodeRef = Arc::new(RwLock::new(object));
+    mount_to(object.clone(), shm_dir.clone(), name);
+    Some(object)
+}
+
+/// Remove `/dev/shm/<name>`. The memory stays until nobody has it open or
+/// mapped any more.
+pub fn unlink_object(name: String) -> Option<()> {
+    if name == "." || name == ".." {
+        return None;
+    }
+    let shm_dir = SHM_DIR.lock().clone()?;
+    shm_dir.read().open(name.clone())?;
+    shm_dir.read().umount(name);
+    Some(())
+}
@@ -0,0 +1,292 @@
+use alloc::{
+    collections::{BTreeMap, VecDeque},
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicUsize, Ordering};
+use spin::{Mutex, RwLock};
+
+use crate::user::wait_queue::WaitQueue;
+
+use super::{
+    inode::{Inode, InodeRef, InodeTy},
+    pipe::{Pipe, PipeReader, PipeWriter},
+};
+
+/// Most connections a listener keeps waiting to be accepted
+pub const MAX_BACKLOG: usize = 128;
+
+/// Listeners by the path of their socket file
+static BOUND: Mutex<BTreeMap<String, Weak<Listener>>> = Mutex::new(BTreeMap::new());
+
+/// An open file handed over a connection, together with whether it was open
+/// for writing.
+pub struct PassedFile {
+    pub inode: InodeRef,
+    pub writable: bool,
+}
+
+type FileQueue = Arc<Mutex<VecDeque<PassedFile>>>;
+
+/// One side of a connection: a pipe in each direction, plus the files the
+/// two sides passed each other.
+struct Connection {
+    reader: PipeReader,
+    writer: PipeWriter,
+    received_files: FileQueue,
+    peer_files: FileQueue,
+}
+
+impl Connection {
+    /// A new connection as its client and its server side.
+    fn pair() -> (Self, Self) {
+        let to_server = Pipe::new();
+        let to_client = Pipe::new();
+        let client_files = FileQueue::default();
+        let server_files = FileQueue::default();
+
+        let client = Self {
+            reader: PipeReader::new(to_client.clone()),
+            writer: PipeWriter::new(to_server.clone()),
+            received_files: client_files.clone(),
+            peer_files: server_files.clone(),
+        };
+        let server = Self {
+            reader: PipeReader::new(to_server),
+            writer: PipeWriter::new(to_client),
+            received_files: server_files,
+            peer_files: client_files,
+        };
+        (client, server)
+    }
+}
+
+/// The server side of a bound socket. The socket file goes away with it.
+struct Listener {
+    pending: Mutex<VecDeque<Connection>>,
+    /// How many connections may wait in `pending`, 0 until `listen`
+    backlog: AtomicUsize,
+    incoming: WaitQueue,
+    path: String,
+    parent: InodeRef,
+    name: String,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        BOUND.lock().remove(&self.path);
+        self.parent.read().umount(self.name.clone());
+    }
+}
+
+enum SocketState {
+    Unbound,
+    Bound(Arc<Listener>),
+    Connected(Arc<Connection>),
+}
+
+/// A local stream socket, as created by the `socket` syscall.
+///
+/// A socket is either bound to a path and accepts connections on it, or
+/// connected and then reads and writes like both ends of a pipe at once.
+pub struct Socket {
+    state: Mutex<SocketState>,
+    path: String,
+}
+
+impl Socket {
+    pub fn new() -> Self {
+        Self {
+            state: Mutex::new(SocketState::Unbound),
+            path: String::new(),
+        }
+    }
+
+    fn connected(connection: Connection) -> Self {
+        Self {
+            state: Mutex::new(SocketState::Connected(Arc::new(connection))),
+            path: String::new(),
+        }
+    }
+
+    fn connection(&self) -> Option<Arc<Connection>> {
+        match &*self.state.lock() {
+            SocketState::Connected(connection) => Some(connection.clone()),
+            _ => None,
+        }
+    }
+
+    fn listener(&self) -> Option<Arc<Listener>> {
+        match &*self.state.lock() {
+            SocketState::Bound(listener) => Some(listener.clone()),
+            _ => None,
+        }
+    }
+
+    /// Create the socket file `name` in the directory `parent` and take
+    /// connections made to it from now on. Only directories that can create
+    /// socket files, and remove them again, such as the one at `/tmp`, can
+    /// be bound in.
+    pub fn bind(&self, parent: InodeRef, name: String) -> Option<()> {
+        let reserved = name.is_empty() || name == "." || name == ".." || name.contains('/');
+        if reserved || parent.read().inode_type() != InodeTy::Dir {
+            return None;
+        }
+
+        let mut state = self.state.lock();
+        if !matches!(*state, SocketState::Unbound) {
+            return None;
+        }
+
+        let file = parent.read().create(name.clone(), InodeTy::Socket)?;
+        let path = file.read().get_path();
+
+        let listener = Arc::new(Listener {
+            pending: Mutex::new(VecDeque::new()),
+            backlog: AtomicUsize::new(0),
+            incoming: WaitQueue::new(),
+            path: path.clone(),
+            parent,
+            name,
+        });
+        BOUND.lock().insert(path, Arc::downgrade(&listener));
+        *state = SocketState::Bound(listener);
+        Some(())
+    }
+
+    /// Start accepting connections, keeping up to `backlog` of them waiting.
+    pub fn listen(&self, backlog: usize) -> Option<()> {
+        let listener = self.listener()?;
+        listener
+            .backlog
+            .store(backlog.clamp(1, MAX_BACKLOG), Ordering::SeqCst);
+        Some(())
+    }
+
+    /// Wait for the next connection and return the server side of it.
+    pub fn accept(&self) -> Option<InodeRef> {
+        let listener = self.listener()?;
+        if listener.backlog.load(Ordering::SeqCst) == 0 {
+            return None;
+        }
+        let connection = listener
+            .incoming
+            .wait_until(|| listener.pending.lock().pop_front())?;
+        Some(Arc::new(RwLock::new(Socket::connected(connection))))
+    }
+
+    /// Connect to the socket bound to the file `file`. Succeeds as soon as
+    /// the connection is queued, without waiting for it to be accepted.
+    pub fn connect(&self, file: InodeRef) -> Option<()> {
+        if file.read().inode_type() != InodeTy::Socket {
+            return None;
+        }
+        let path = file.read().get_path();
+        let listener = BOUND.lock().get(&path).and_then(Weak::upgrade)?;
+
+        let mut state = self.state.lock();
+        if !matches!(*state, SocketState::Unbound) {
+            return None;
+        }
+
+        let (client, server) = Connection::pair();
+        {
+            let mut pending = listener.pending.lock();
+            if pending.len() >= listener.backlog.load(Ordering::SeqCst) {
+                return None;
+            }
+            pending.push_back(server);
+        }
+        listener.incoming.wake_all();
+
+        *state = SocketState::Connected(Arc::new(client));
+        Some(())
+    }
+
+    /// Hand `file` to the other side, which picks it up with
+    /// [`receive_file`](Self::receive_file).
+    pub fn send_file(&self, file: PassedFile) -> Option<()> {
+        self.connection()?.peer_files.lock().push_back(file);
+        Some(())
+    }
+
+    /// The oldest file the other side passed and nobody took yet. Files are
+    /// queued apart from the byte stream, so the sender usually writes a
+    /// message saying one is coming after passing it.
+    pub fn receive_file(&self) -> Option<PassedFile> {
+        self.connection()?.received_files.lock().pop_front()
+    }
+}
+
+impl Inode for Socket {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {
+        self.path.clear();
+    }
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.connection()
+            .map_or(0, |connection| connection.reader.size())
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        self.connection()
+            .map_or(0, |connection| connection.reader.read_at(offset, buf))
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        self.connection()
+            .map_or(0, |connection| connection.writer.write_at(offset, buf))
+    }
+
+    fn flush(&self) {}
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Socket
+    }
+
+    fn as_socket(&self) -> Option<&Socket> {
+        Some(self)
+    }
+}
+
+/// The name a socket is bound to. Only marks the path, reading or writing
+/// it does nothing.
+pub struct SocketFile {
+    path: String,
+}
+
+impl SocketFile {
+    pub fn new() -> Self {
+        Self {
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for SocketFile {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn flush(&self) {}
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Socket
+    }
+}
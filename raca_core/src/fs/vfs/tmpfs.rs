@@ -0,0 +1,168 @@
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use framework::ref_to_mut;
+use spin::RwLock;
+
+use super::{
+    inode::{mount_to, FileInfo, Inode, InodeRef, InodeTy},
+    pipe::Fifo,
+    socket::SocketFile,
+};
+
+/// Largest size a file can grow to
+pub const MAX_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+/// A directory of a filesystem that only lives in memory.
+pub struct TmpDir {
+    this: Weak<RwLock<TmpDir>>,
+    nodes: BTreeMap<String, InodeRef>,
+    path: String,
+}
+
+impl TmpDir {
+    pub fn new() -> InodeRef {
+        Arc::new_cyclic(|this| {
+            RwLock::new(Self {
+                this: this.clone(),
+                nodes: BTreeMap::new(),
+                path: String::new(),
+            })
+        })
+    }
+}
+
+impl Inode for TmpDir {
+    fn when_mounted(&mut self, path: String, father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+        if let Some(father) = father {
+            self.nodes.insert("..".into(), father);
+        }
+    }
+
+    fn when_umounted(&mut self) {
+        for (name, node) in self.nodes.iter() {
+            if name != "." && name != ".." {
+                node.write().when_umounted();
+            }
+        }
+    }
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn mount(&self, node: InodeRef, name: String) {
+        ref_to_mut(self).nodes.insert(name, node);
+    }
+
+    fn umount(&self, name: String) {
+        if name == "." || name == ".." {
+            return;
+        }
+        if let Some(node) = ref_to_mut(self).nodes.remove(&name) {
+            node.write().when_umounted();
+        }
+    }
+
+    fn open(&self, name: String) -> Option<InodeRef> {
+        if name == "." {
+            return Some(self.this.upgrade()?);
+        }
+        self.nodes.get(&name).cloned()
+    }
+
+    fn create(&self, name: String, ty: InodeTy) -> Option<InodeRef> {
+        let reserved = name.is_empty() || name == "." || name.contains('/');
+        if reserved || self.nodes.contains_key(&name) {
+            return None;
+        }
+
+        let node: InodeRef = match ty {
+            InodeTy::Dir => TmpDir::new(),
+            InodeTy::File => Arc::new(RwLock::new(TmpFile::new())),
+            InodeTy::Fifo => Arc::new(RwLock::new(Fifo::new())),
+            // only asked for by `Socket::bind`, which listens on it
+            InodeTy::Socket => Arc::new(RwLock::new(SocketFile::new())),
+        };
+        mount_to(node.clone(), self.this.upgrade()?, name);
+        Some(node)
+    }
+
+    fn list(&self) -> Vec<FileInfo> {
+        let mut vec = Vec::from([FileInfo::new(".".into(), InodeTy::Dir)]);
+        for (name, inode) in self.nodes.iter() {
+            vec.push(FileInfo::new(name.clone(), inode.read().inode_type()));
+        }
+        vec
+    }
+
+    fn inode_type(&self) -> InodeTy {
+        InodeTy::Dir
+    }
+}
+
+/// A regular file of a filesystem that only lives in memory.
+pub struct TmpFile {
+    data: Vec<u8>,
+    path: String,
+}
+
+impl TmpFile {
+    pub fn new() -> Self {
+        Self {
+            data: Vec::new(),
+            path: String::new(),
+        }
+    }
+}
+
+impl Inode for TmpFile {
+    fn when_mounted(&mut self, path: String, _father: Option<InodeRef>) {
+        self.path.clear();
+        self.path.push_str(path.as_str());
+    }
+
+    fn when_umounted(&mut self) {}
+
+    fn get_path(&self) -> String {
+        self.path.clone()
+    }
+
+    fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    fn read_at(&self, offset: usize, buf: &mut [u8]) -> usize {
+        let Some(src) = self.data.get(offset..) else {
+            return 0;
+        };
+        let len = buf.len().min(src.len());
+        buf[..len].copy_from_slice(&src[..len]);
+        len
+    }
+
+    fn write_at(&self, offset: usize, buf: &[u8]) -> usize {
+        let data = &mut ref_to_mut(self).data;
+        let Some(end) = offset.checked_add(buf.len()) else {
+            return 0;
+        };
+        if end > MAX_FILE_SIZE {
+            return 0;
+        }
+        if data.len() < end {
+            if data.try_reserve(end - data.len()).is_err() {
+                return 0;
+            }
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(buf);
+        buf.len()
+    }
+
+    fn flush(&self) {}
+}
@@ -0,0 +1,196 @@
+use alloc::{collections::VecDeque, format, string::String};
+use core::{
+    fmt::Write,
+    sync::atomic::{AtomicBool, Ordering},
+};
+use framework::arch::apic::get_lapic_id;
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use spin::Mutex;
+
+use crate::drivers::clock;
+
+/// Bytes of log text kept; the oldest lines are dropped first.
+const BUFFER_SIZE: usize = 64 * 1024;
+
+/// Messages at this level or more severe also go to the screen, the rest only
+/// to the buffer and the serial port.
+const CONSOLE_LEVEL: Level = Level::Warn;
+
+/// Bytes kept from before the framework is up, when there is no heap yet.
+const EARLY_BUFFER_SIZE: usize = 8 * 1024;
+
+static BUFFER: Mutex<VecDeque<u8>> = Mutex::new(VecDeque::new());
+
+/// Set once the heap, clock and consoles of the framework can be used.
+static FRAMEWORK_READY: AtomicBool = AtomicBool::new(false);
+
+/// Records logged while the framework starts, each a level byte followed by
+/// the text and a NUL.
+struct EarlyBuffer {
+    data: [u8; EARLY_BUFFER_SIZE],
+    len: usize,
+}
+
+impl Write for EarlyBuffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        // keep the last byte for the terminator, cut whatever doesn't fit
+        let room = EARLY_BUFFER_SIZE - 1 - self.len;
+        let bytes = s.as_bytes();
+        let count = bytes.len().min(room);
+        self.data[self.len..self.len + count].copy_from_slice(&bytes[..count]);
+        self.len += count;
+        Ok(())
+    }
+}
+
+static EARLY_BUFFER: Mutex<EarlyBuffer> = Mutex::new(EarlyBuffer {
+    data: [0; EARLY_BUFFER_SIZE],
+    len: 0,
+});
+
+fn level_from_byte(byte: u8) -> Level {
+    match byte {
+        1 => Level::Error,
+        2 => Level::Warn,
+        3 => Level::Info,
+        4 => Level::Debug,
+        _ => Level::Trace,
+    }
+}
+
+/// Store `record` if the framework isn't up yet, returning whether it was.
+fn early_log(record: &Record) -> bool {
+    let mut early = EARLY_BUFFER.lock();
+    // checked under the lock so nothing lands after the buffer was moved
+    if FRAMEWORK_READY.load(Ordering::Acquire) {
+        return false;
+    }
+    // a level byte, at least one byte of text and the terminator
+    if early.len + 3 > EARLY_BUFFER_SIZE {
+        return true;
+    }
+    let len = early.len;
+    early.data[len] = record.level() as u8;
+    early.len += 1;
+    let _ = write!(early, "{}", record.args());
+    let len = early.len;
+    early.data[len] = 0;
+    early.len += 1;
+    true
+}
+
+/// Append one line to the ring buffer, dropping whole lines from the front
+/// to make room.
+pub fn append(level: Level, text: &str) {
+    push_line(clock::uptime_ms(), level, text);
+}
+
+fn push_line(ms: u64, level: Level, text: &str) {
+    let line = format!(
+        "[{:5}.{:03}] CPU{} {:5} {}\n",
+        ms / 1000,
+        ms % 1000,
+        get_lapic_id(),
+        level,
+        text.trim_end_matches('\n'),
+    );
+    let line = &line.as_bytes()[..line.len().min(BUFFER_SIZE)];
+
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let mut buffer = BUFFER.lock();
+        while buffer.len() + line.len() > BUFFER_SIZE {
+            let end = buffer
+                .iter()
+                .position(|&byte| byte == b'\n')
+                .map_or(buffer.len(), |pos| pos + 1);
+            buffer.drain(..end);
+        }
+        buffer.extend(line);
+    });
+}
+
+/// Copy the buffered log from `offset` on into `buf`.
+pub fn read(offset: usize, buf: &mut [u8]) -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| {
+        let buffer = BUFFER.lock();
+        let mut len = 0;
+        for (dst, src) in buf.iter_mut().zip(buffer.iter().skip(offset)) {
+            *dst = *src;
+            len += 1;
+        }
+        len
+    })
+}
+
+pub fn size() -> usize {
+    x86_64::instructions::interrupts::without_interrupts(|| BUFFER.lock().len())
+}
+
+pub fn clear() {
+    x86_64::instructions::interrupts::without_interrupts(|| BUFFER.lock().clear());
+}
+
+/// Least severe level that is still recorded.
+pub fn level() -> LevelFilter {
+    log::max_level()
+}
+
+pub fn set_level(level: LevelFilter) {
+    log::set_max_level(level);
+}
+
+struct KernelLogger;
+
+impl Log for KernelLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        if !FRAMEWORK_READY.load(Ordering::Acquire) && early_log(record) {
+            return;
+        }
+
+        let mut text = String::new();
+        let _ = write!(text, "{}", record.args());
+        append(record.level(), &text);
+
+        framework::serial_println!("[{}] {}", record.level(), text);
+        if record.level() <= CONSOLE_LEVEL {
+            framework::println!("[{}] {}", record.level(), text);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static LOGGER: KernelLogger = KernelLogger;
+
+/// Route the `log` macros through the ring buffer. Called before the
+/// framework starts so its boot messages are kept too; until
+/// [`framework_ready`] they are only stored.
+pub fn init() {
+    if log::set_logger(&LOGGER).is_ok() {
+        log::set_max_level(LevelFilter::Info);
+    }
+}
+
+/// Move the messages logged while the framework started into the ring
+/// buffer and the serial port, and log normally from now on.
+pub fn framework_ready() {
+    let early = EARLY_BUFFER.lock();
+    let records = early.data[..early.len]
+        .split(|&byte| byte == 0)
+        .filter(|record| !record.is_empty());
+    for record in records {
+        let level = level_from_byte(record[0]);
+        let text = String::from_utf8_lossy(&record[1..]);
+        // the clock wasn't running yet
+        push_line(0, level, &text);
+        framework::serial_println!("[{}] {}", level, text);
+    }
+    FRAMEWORK_READY.store(true, Ordering::Release);
+}
@@ -1,9 +1,11 @@
 #![no_std]
+#![feature(abi_x86_interrupt)]
 #![deny(unsafe_code)]
 
 extern crate alloc;
 
 pub mod drivers;
 pub mod fs;
+pub mod kmsg;
 pub mod ui;
 pub mod user;
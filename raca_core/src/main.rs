@@ -22,7 +22,10 @@ pub mod drivers;
 
 #[no_mangle]
 pub extern "C" fn _start() {
+    raca_core::kmsg::init();
     init_framework();
+    raca_core::kmsg::framework_ready();
+    raca_core::drivers::rng::init();
     raca_core::drivers::xhci::init();
     fs::init();
 
@@ -34,9 +37,11 @@ pub extern "C" fn _start() {
     hello1.read().read_at(0, buf);
 
     Thread::new_kernel_thread(raca_core::fs::vfs::dev::terminal::keyboard_parse_thread);
+    Thread::new_kernel_thread(raca_core::fs::vfs::dev::serial::serial_receive_thread);
 
     let process = Process::new_user_process("init", buf);
     init_file_descriptor_manager(process.read().id);
+    raca_core::user::set_init_process(process.read().id);
     //Process::new_user_process("Hello2", include_bytes!("../../../apps/hello2.rae"));
 
     (40..=47).for_each(|index| framework::print!("\x1b[{}m   \x1b[0m", index));
@@ -51,7 +56,7 @@ pub extern "C" fn _start() {
 
     raca_core::ui::init();
 
-    framework::start_schedule();
+    raca_core::user::start_schedule();
     loop {
         x86_64::instructions::hlt();
     }
@@ -5,6 +5,19 @@ use crate::fs::operation::kernel_open;
 
 use fontdue::Font;
 
+/// Pixel size the console draws the font at
+const FONT_SIZE: f32 = 16.0;
+
+/// Round a font metric up to whole pixels.
+fn whole_pixels(size: f32) -> usize {
+    let whole = size as usize;
+    if (whole as f32) < size {
+        whole + 1
+    } else {
+        whole
+    }
+}
+
 pub fn init() {
     let font_inode = kernel_open("/raca/fonts/default.ttf".into()).unwrap();
 
@@ -15,7 +28,7 @@ pub fn init() {
 
     // Parse it into the font type.
     let fira_code = Font::from_bytes(font_buffer.as_slice(), fontdue::FontSettings::default()).unwrap();
-    let (metrics, bitmap) = fira_code.rasterize('R', 16.0);
+    let (metrics, bitmap) = fira_code.rasterize('R', FONT_SIZE);
     for (idx,byte) in bitmap.iter().enumerate() {
         framework::print!("{}", if *byte > 128 { "R" } else { " " });
         if (idx + 1) % metrics.width == 0 {
@@ -23,5 +36,14 @@ pub fn init() {
         }
     }
 
+    // the console font is monospaced, so any character gives the cell width
+    let cell_width = whole_pixels(fira_code.metrics('M', FONT_SIZE).advance_width);
+    let cell_height = whole_pixels(
+        fira_code
+            .horizontal_line_metrics(FONT_SIZE)
+            .map_or(FONT_SIZE, |line| line.new_line_size),
+    );
+    crate::fs::vfs::dev::terminal::set_cell_size(cell_width, cell_height);
+
     set_font(font_buffer.leak());
 }
@@ -0,0 +1,136 @@
+use alloc::{collections::BTreeMap, sync::Arc};
+use framework::{
+    memory::MemoryManager,
+    ref_to_mut,
+    task::{process::ProcessId, Process},
+};
+use spin::{Mutex, RwLock};
+use x86_64::{
+    structures::paging::{Mapper, Page, PageTableFlags, PhysFrame, Size4KiB},
+    VirtAddr,
+};
+
+use crate::fs::vfs::inode::InodeRef;
+
+use super::{get_current_process, get_current_process_id};
+
+const PAGE_SIZE: usize = 4096;
+
+/// Mapped pages can always be read, the flag only exists to be explicit.
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
+/// Mappings are placed upwards from here, well clear of the heap and stack.
+const MMAP_AREA_START: u64 = 0x0000_5000_0000_0000;
+const MMAP_AREA_END: u64 = 0x0000_6000_0000_0000;
+
+struct Mapping {
+    pages: usize,
+    /// Keeps the mapped node alive as long as its pages are in use
+    _inode: InodeRef,
+}
+
+#[derive(Default)]
+struct MmapSpace {
+    mappings: BTreeMap<u64, Mapping>,
+}
+
+impl MmapSpace {
+    /// First gap between mappings that fits `pages` pages.
+    fn find_free(&self, pages: usize) -> Option<u64> {
+        let len = (pages as u64).checked_mul(PAGE_SIZE as u64)?;
+        let mut start = MMAP_AREA_START;
+        for (&addr, mapping) in self.mappings.iter() {
+            if addr - start >= len {
+                break;
+            }
+            start = addr + (mapping.pages * PAGE_SIZE) as u64;
+        }
+        (MMAP_AREA_END - start >= len).then_some(start)
+    }
+}
+
+static MMAP_SPACES: Mutex<BTreeMap<ProcessId, MmapSpace>> = Mutex::new(BTreeMap::new());
+
+fn unmap_pages(process: &Arc<RwLock<Process>>, start: u64, pages: usize) {
+    let page_table = &mut ref_to_mut(&*process.read()).page_table;
+    for idx in 0..pages {
+        let page =
+            Page::<Size4KiB>::containing_address(VirtAddr::new(start + (idx * PAGE_SIZE) as u64));
+        // the frames belong to the node, only the translation goes away
+        if let Ok((_, flush)) = page_table.unmap(page) {
+            flush.flush();
+        }
+    }
+}
+
+/// Map `len` bytes of `inode` starting at `offset` into the current process
+/// with the protection `prot` and return where they ended up.
+pub fn map(inode: InodeRef, offset: usize, len: usize, prot: usize) -> Option<VirtAddr> {
+    if len == 0 || len as u64 > MMAP_AREA_END - MMAP_AREA_START || offset % PAGE_SIZE != 0 {
+        return None;
+    }
+    let pages = len.div_ceil(PAGE_SIZE);
+
+    let mut spaces = MMAP_SPACES.lock();
+    let space = spaces.entry(get_current_process_id()).or_default();
+    let start = space.find_free(pages)?;
+
+    let mut flags = PageTableFlags::PRESENT | PageTableFlags::USER_ACCESSIBLE;
+    if prot & PROT_WRITE != 0 {
+        flags |= PageTableFlags::WRITABLE;
+    }
+    if prot & PROT_EXEC == 0 {
+        flags |= PageTableFlags::NO_EXECUTE;
+    }
+
+    let process = get_current_process();
+    for idx in 0..pages {
+        let mapped = inode
+            .read()
+            .mmap(offset + idx * PAGE_SIZE)
+            .and_then(|frame| {
+                let frame = PhysFrame::<Size4KiB>::from_start_address(frame).ok()?;
+                let page =
+                    Page::containing_address(VirtAddr::new(start + (idx * PAGE_SIZE) as u64));
+                let page_table = &mut ref_to_mut(&*process.read()).page_table;
+                MemoryManager::map_frame_to_page(frame, page, flags, page_table).ok()
+            });
+        if mapped.is_none() {
+            unmap_pages(&process, start, idx);
+            return None;
+        }
+    }
+
+    space.mappings.insert(
+        start,
+        Mapping {
+            pages,
+            _inode: inode,
+        },
+    );
+    Some(VirtAddr::new(start))
+}
+
+/// Remove the mapping starting at `addr` from the current process.
+pub fn unmap(addr: VirtAddr) -> Option<()> {
+    let mapping = MMAP_SPACES
+        .lock()
+        .get_mut(&get_current_process_id())?
+        .mappings
+        .remove(&addr.as_u64())?;
+    unmap_pages(&get_current_process(), addr.as_u64(), mapping.pages);
+    Some(())
+}
+
+/// Drop every mapping of `process`, once it exits or is killed.
+pub fn release(process: &Arc<RwLock<Process>>) {
+    let pid = process.read().id;
+    let Some(space) = MMAP_SPACES.lock().remove(&pid) else {
+        return;
+    };
+    for (start, mapping) in space.mappings {
+        unmap_pages(process, start, mapping.pages);
+    }
+}
@@ -1,12 +1,69 @@
-use alloc::sync::Arc;
+use alloc::{
+    collections::BTreeMap,
+    string::String,
+    sync::{Arc, Weak},
+};
+use core::sync::atomic::{AtomicBool, Ordering};
 use framework::{
     arch::apic::get_lapic_id,
-    task::{process::ProcessId, scheduler::SCHEDULERS, thread::ThreadState, Process, Thread},
+    task::{
+        process::{ProcessId, PROCESSES},
+        scheduler::SCHEDULERS,
+        signal::Signal,
+        thread::ThreadState,
+        Process, Thread,
+    },
 };
-use spin::RwLock;
+use spin::{Mutex, Once, RwLock};
 
 pub mod syscall;
 pub mod login;
+pub mod mmap;
+pub mod signal;
+pub mod wait_queue;
+
+static SCHEDULING: AtomicBool = AtomicBool::new(false);
+
+/// The first user process, started by the kernel itself
+static INIT_PROCESS: Once<ProcessId> = Once::new();
+
+/// Command lines processes were started with, for `/proc/<pid>/cmdline`
+static CMDLINES: Mutex<BTreeMap<ProcessId, String>> = Mutex::new(BTreeMap::new());
+
+pub fn set_cmdline(pid: ProcessId, cmdline: String) {
+    CMDLINES.lock().insert(pid, cmdline);
+}
+
+pub fn cmdline_of(pid: ProcessId) -> Option<String> {
+    CMDLINES.lock().get(&pid).cloned()
+}
+
+/// Note that `pid` is init, which can't be killed or stopped.
+pub fn set_init_process(pid: ProcessId) {
+    INIT_PROCESS.call_once(|| pid);
+}
+
+pub fn is_init_process(pid: ProcessId) -> bool {
+    INIT_PROCESS.get() == Some(&pid)
+}
+
+/// Whether `pid` is a process started from a binary. Every one of them is
+/// given files when it is created, the kernel threads have none.
+pub fn is_user_process(pid: ProcessId) -> bool {
+    crate::fs::operation::has_file_descriptor_manager(pid)
+}
+
+/// Start running threads. Until then there is no current thread, and code
+/// that may run during boot has to busy wait instead of blocking.
+pub fn start_schedule() {
+    SCHEDULING.store(true, Ordering::SeqCst);
+    framework::start_schedule();
+}
+
+/// Whether [`start_schedule`] was called, so threads can block.
+pub fn is_scheduling() -> bool {
+    SCHEDULING.load(Ordering::SeqCst)
+}
 
 #[inline]
 pub fn get_current_thread() -> Arc<RwLock<Thread>> {
@@ -34,3 +91,42 @@ pub fn sleep() {
     framework::task::schedule();
     while get_current_thread().read().state == ThreadState::Blocked {}
 }
+
+/// Tear `process` down and tell its parent it exited with `code`. Does not
+/// return if `process` is the current one, any other one must be blocked and
+/// off the CPUs.
+pub fn terminate(process: Arc<RwLock<Process>>, code: usize) {
+    let pid = process.read().id;
+    mmap::release(&process);
+    crate::fs::operation::release_file_descriptor_manager(pid);
+    signal::release(pid);
+    CMDLINES.lock().remove(&pid);
+
+    notify_father(&process, code, false);
+
+    if pid == get_current_process_id() {
+        drop(process);
+        framework::task::scheduler::exit();
+    } else {
+        // its threads are dropped along with it once gone from the process
+        // list, which is what the scheduler runs
+        PROCESSES
+            .write()
+            .retain(|other| !Arc::ptr_eq(other, &process));
+    }
+}
+
+/// Tell the parent of `process`, which may be waiting for it, that it exited
+/// with `code` or was stopped.
+pub fn notify_father(process: &Arc<RwLock<Process>>, code: usize, stopped: bool) {
+    let father = process.read().father.as_ref().and_then(Weak::upgrade);
+    if let Some(father) = father {
+        father.write().signal_manager.register_signal(
+            1,
+            Signal {
+                ty: 0,
+                data: [code as u64, stopped as u64, 0, 0, 0, 0, 0, 0],
+            },
+        );
+    }
+}
@@ -0,0 +1,558 @@
+use alloc::{
+    collections::BTreeMap,
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use framework::{
+    memory::write_for_syscall,
+    task::{
+        process::{ProcessId, PROCESSES},
+        scheduler::SCHEDULERS,
+        thread::ThreadState,
+        Process,
+    },
+};
+use spin::{Mutex, RwLock};
+use x86_64::{instructions::interrupts::without_interrupts, VirtAddr};
+
+use super::{
+    get_current_process, get_current_process_id, get_current_thread, is_init_process,
+    is_user_process,
+};
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGWINCH: usize = 28;
+
+/// Signal numbers are below this
+pub const NSIG: usize = 32;
+
+/// What `sigaction` sets a signal to
+pub const SIG_DFL: usize = 0;
+pub const SIG_IGN: usize = 1;
+/// Caught by a handler, which user space runs
+pub const SIG_HANDLE: usize = 2;
+
+/// What `sigprocmask` does with the set it is given
+pub const SIG_BLOCK: usize = 0;
+pub const SIG_UNBLOCK: usize = 1;
+pub const SIG_SETMASK: usize = 2;
+
+/// What a syscall that waited returns when a signal came in before it got
+/// anything done
+pub const INTERRUPTED: usize = usize::MAX - 1;
+
+/// Signals that can't be caught, ignored or blocked
+const UNMASKABLE: u64 = (1 << SIGKILL) | (1 << SIGSTOP);
+const STOP_SIGNALS: u64 = (1 << SIGSTOP) | (1 << SIGTSTP) | (1 << SIGTTIN) | (1 << SIGTTOU);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Action {
+    Default,
+    Ignore,
+    Handle,
+}
+
+/// What happens to a process when a signal arrives that it doesn't handle.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Effect {
+    None,
+    Stop(usize),
+    Terminate(usize),
+}
+
+fn default_effect(sig: usize) -> Effect {
+    match sig {
+        SIGCHLD | SIGCONT | SIGWINCH => Effect::None,
+        SIGSTOP | SIGTSTP | SIGTTIN | SIGTTOU => Effect::Stop(sig),
+        _ => Effect::Terminate(sig),
+    }
+}
+
+/// User stack below the return address of the syscall stub that is left
+/// alone, the red zone of the System V ABI
+const RED_ZONE: usize = 128;
+/// Stack addresses are below this
+const USER_END: usize = 0x0000_8000_0000_0000;
+
+/// What a handler is entered with, pushed onto the user stack when a syscall
+/// returns. The trampoline of raca_std lays it out the same way.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct SignalFrame {
+    sig: u64,
+    /// Blocked signals before the handler ran, put back by `sigreturn`
+    blocked: u64,
+    /// What the syscall returned
+    ret: u64,
+    /// Where the syscall stub would have returned to, and its stack pointer
+    /// after that return
+    rip: u64,
+    rsp: u64,
+}
+
+/// Stopping or killing a process only takes effect where its thread holds no
+/// kernel locks: right away if it is running its own code, otherwise when it
+/// returns from the syscall it is in. User processes have a single thread.
+struct SignalState {
+    actions: [Action; NSIG],
+    pending: u64,
+    blocked: u64,
+    stopped: bool,
+    /// Exit code of a kill that hasn't taken effect yet
+    killed: Option<usize>,
+    /// Being torn down by the process that killed it
+    exiting: bool,
+    /// The thread is between [`enter_syscall`] and [`leave_syscall`]
+    in_syscall: bool,
+    /// Where handlers are entered in user space, set along with the first
+    /// handler
+    trampoline: Option<usize>,
+}
+
+impl Default for SignalState {
+    fn default() -> Self {
+        Self {
+            actions: [Action::Default; NSIG],
+            pending: 0,
+            blocked: 0,
+            stopped: false,
+            killed: None,
+            exiting: false,
+            in_syscall: false,
+            trampoline: None,
+        }
+    }
+}
+
+impl SignalState {
+    fn handled(&self) -> u64 {
+        (1..NSIG)
+            .filter(|&sig| self.actions[sig] == Action::Handle)
+            .fold(0, |mask, sig| mask | (1 << sig))
+    }
+
+    /// Take every pending signal that isn't blocked or left to a handler,
+    /// and mark the process stopped or killed if they do that together.
+    /// Returns the signal that stopped it, if it wasn't stopped before.
+    fn take_effect(&mut self) -> Option<usize> {
+        let ready = self.pending & !self.blocked & !self.handled();
+        self.pending &= !ready;
+
+        let effect = (1..NSIG)
+            .filter(|&sig| ready & (1 << sig) != 0)
+            .filter(|&sig| self.actions[sig] == Action::Default)
+            .map(default_effect)
+            .max()
+            .unwrap_or(Effect::None);
+
+        match effect {
+            Effect::Stop(sig) if !self.stopped && self.killed.is_none() => {
+                self.stopped = true;
+                Some(sig)
+            }
+            Effect::Terminate(sig) => {
+                self.killed.get_or_insert(128 + sig);
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Signals a handler has to run for, which cut waits short.
+    fn deliverable(&self) -> u64 {
+        self.pending & !self.blocked & self.handled()
+    }
+
+    fn interrupts_wait(&self) -> bool {
+        self.killed.is_some() || (!self.stopped && self.deliverable() != 0)
+    }
+}
+
+static SIGNAL_STATES: Mutex<BTreeMap<ProcessId, SignalState>> = Mutex::new(BTreeMap::new());
+
+fn find_process(pid: ProcessId) -> Option<Arc<RwLock<Process>>> {
+    PROCESSES
+        .read()
+        .iter()
+        .find(|process| process.read().id == pid)
+        .cloned()
+}
+
+fn set_threads(process: &Arc<RwLock<Process>>, from: Option<ThreadState>, to: ThreadState) {
+    for thread in process.read().threads.iter() {
+        let mut thread = thread.write();
+        if from.map_or(true, |from| thread.state == from) {
+            thread.state = to;
+        }
+    }
+}
+
+/// Whether a thread of `process` is what some CPU runs at the moment.
+fn is_on_cpu(process: &Arc<RwLock<Process>>) -> bool {
+    let threads = process.read().threads.clone();
+    without_interrupts(|| {
+        SCHEDULERS.lock().values().any(|scheduler| {
+            threads
+                .iter()
+                .any(|thread| Arc::ptr_eq(&scheduler.current_thread, thread))
+        })
+    })
+}
+
+fn notify_stopped(process: &Arc<RwLock<Process>>, stopped_by: Option<usize>) {
+    if let Some(sig) = stopped_by {
+        // lets a shell waiting for the process take the terminal back
+        super::notify_father(process, 128 + sig, true);
+    }
+}
+
+/// Send `sig` to process `pid`. Signal 0 only checks that the process exists.
+///
+/// Kernel threads take no signals, and init can't be killed or stopped.
+pub fn send(pid: ProcessId, sig: usize) -> Option<()> {
+    if sig >= NSIG || !is_user_process(pid) {
+        return None;
+    }
+    if is_init_process(pid) && UNMASKABLE & (1 << sig) != 0 {
+        return None;
+    }
+    let process = find_process(pid)?;
+    if sig == 0 {
+        return Some(());
+    }
+
+    let current = pid == get_current_process_id();
+    let (stopped_by, kill, wake) = {
+        let mut states = SIGNAL_STATES.lock();
+        let state = states.entry(pid).or_default();
+        if state.exiting {
+            return Some(());
+        }
+
+        let mut resume = false;
+        if sig == SIGCONT {
+            state.pending &= !STOP_SIGNALS;
+            resume = core::mem::take(&mut state.stopped);
+        } else if STOP_SIGNALS & (1 << sig) != 0 {
+            state.pending &= !(1 << SIGCONT);
+        }
+
+        if state.actions[sig] != Action::Ignore {
+            state.pending |= 1 << sig;
+        }
+        let stopped_by = state.take_effect();
+
+        // a thread running its own code holds no kernel locks, so it can be
+        // frozen where it is. Done with the states locked, a thread that
+        // enters a syscall meanwhile finds out in `enter_syscall`.
+        let outside = !current && !state.in_syscall;
+        if outside && (stopped_by.is_some() || state.killed.is_some()) {
+            set_threads(&process, None, ThreadState::Blocked);
+        }
+        let kill = match state.killed {
+            Some(code) if outside => {
+                state.exiting = true;
+                Some(code)
+            }
+            _ => None,
+        };
+
+        let wake = resume || (state.in_syscall && state.interrupts_wait());
+        (stopped_by, kill, wake)
+    };
+
+    // waking the threads makes the ones in a wait notice the signal, the
+    // others check their condition again and go back to sleep
+    if wake {
+        set_threads(&process, Some(ThreadState::Blocked), ThreadState::Ready);
+    }
+    notify_stopped(&process, stopped_by);
+    if let Some(code) = kill {
+        // its page table goes away with it
+        while is_on_cpu(&process) {
+            framework::task::schedule();
+        }
+        super::terminate(process, code);
+    }
+    Some(())
+}
+
+/// Whether `process` is `ancestor` or was started by it, directly or through
+/// others.
+fn descends_from(process: &Arc<RwLock<Process>>, ancestor: ProcessId) -> bool {
+    let mut process = process.clone();
+    loop {
+        if process.read().id == ancestor {
+            return true;
+        }
+        let father = process.read().father.as_ref().and_then(Weak::upgrade);
+        match father {
+            Some(father) => process = father,
+            None => return false,
+        }
+    }
+}
+
+/// Whether process `pid` exists and is `ancestor` or was started by it.
+pub fn is_descendant(pid: ProcessId, ancestor: ProcessId) -> bool {
+    find_process(pid).is_some_and(|process| descends_from(&process, ancestor))
+}
+
+/// Send `sig` to process `leader` and every process it started, directly or
+/// through others, such as a job run from a shell.
+pub fn send_group(leader: ProcessId, sig: usize) -> Option<()> {
+    let members: Vec<ProcessId> = PROCESSES
+        .read()
+        .iter()
+        .filter(|process| descends_from(process, leader))
+        .map(|process| process.read().id)
+        .collect();
+
+    if members.is_empty() {
+        return None;
+    }
+    for pid in members {
+        send(pid, sig);
+    }
+    Some(())
+}
+
+/// Set what the current process does on `sig`. Handlers are entered
+/// through `trampoline` when a syscall returns, see [`leave_syscall`].
+pub fn set_action(sig: usize, action: usize, trampoline: usize) -> Option<()> {
+    let action = match action {
+        SIG_DFL => Action::Default,
+        SIG_IGN => Action::Ignore,
+        SIG_HANDLE => Action::Handle,
+        _ => return None,
+    };
+    if sig == 0 || sig >= NSIG || UNMASKABLE & (1 << sig) != 0 {
+        return None;
+    }
+    if action == Action::Handle && (trampoline == 0 || trampoline >= USER_END) {
+        return None;
+    }
+
+    let pid = get_current_process_id();
+    let mut states = SIGNAL_STATES.lock();
+    let state = states.entry(pid).or_default();
+
+    if action == Action::Handle {
+        state.trampoline = Some(trampoline);
+    }
+    state.actions[sig] = action;
+    if action == Action::Ignore {
+        state.pending &= !(1 << sig);
+    }
+    Some(())
+}
+
+/// Change the blocked signals of the current process and return the old set.
+pub fn set_mask(how: usize, set: u64) -> Option<u64> {
+    let pid = get_current_process_id();
+    let (old, stopped_by) = {
+        let mut states = SIGNAL_STATES.lock();
+        let state = states.entry(pid).or_default();
+
+        let old = state.blocked;
+        let blocked = match how {
+            SIG_BLOCK => old | set,
+            SIG_UNBLOCK => old & !set,
+            SIG_SETMASK => set,
+            _ => return None,
+        };
+        // bit 0 stands for no signal
+        state.blocked = blocked & !UNMASKABLE & !1;
+
+        (old, state.take_effect())
+    };
+    notify_stopped(&get_current_process(), stopped_by);
+    Some(old)
+}
+
+/// Note that the current thread entered a syscall. From here on a stop or
+/// kill waits until [`leave_syscall`], unless it came in just before, while
+/// the thread held no kernel locks yet.
+pub fn enter_syscall() {
+    let pid = get_current_process_id();
+    let exiting = {
+        let mut states = SIGNAL_STATES.lock();
+        let state = states.entry(pid).or_default();
+        state.in_syscall = true;
+        state.exiting
+    };
+    if exiting {
+        // frozen before it got here, the process that killed it is waiting
+        // for it to leave the CPU
+        loop {
+            framework::task::schedule();
+        }
+    }
+    wait_while_stopped();
+}
+
+/// Finish a syscall that returns `ret`: stay put while the process is
+/// stopped, exit if it was killed, and otherwise run the handler of the next
+/// signal that waits for one. Returns what the syscall returns.
+pub fn leave_syscall(ret: usize, user_rsp: usize) -> usize {
+    let pid = get_current_process_id();
+    loop {
+        let killed = {
+            let mut states = SIGNAL_STATES.lock();
+            let Some(state) = states.get_mut(&pid) else {
+                return ret;
+            };
+            if state.killed.is_none() && !state.stopped {
+                state.in_syscall = false;
+                return deliver(state, ret, user_rsp);
+            }
+            state.killed
+        };
+        if let Some(code) = killed {
+            super::terminate(get_current_process(), code);
+        }
+        wait_while_stopped();
+    }
+}
+
+/// Make the current syscall return into the handler of the next signal
+/// that waits for one, and return what the syscall returns instead of `ret`.
+///
+/// `user_rsp` is the stack pointer of the syscall stub, which points at its
+/// return address. That address is swapped for the trampoline, and a
+/// [`SignalFrame`] holding it and `ret` goes further down the stack, below
+/// the red zone. The trampoline is entered with the frame address as the
+/// return value, runs the handler on the stack below the frame, and calls
+/// [`sigreturn`] before it resumes where the stub would have returned to.
+///
+/// The signal is blocked until then. Signals only reach handlers on the way
+/// out of a syscall; nothing runs them when a process is interrupted while
+/// running its own code.
+fn deliver(state: &mut SignalState, ret: usize, user_rsp: usize) -> usize {
+    let Some(trampoline) = state.trampoline else {
+        return ret;
+    };
+    let deliverable = state.deliverable();
+    let Some(sig) = (1..NSIG).find(|&sig| deliverable & (1 << sig) != 0) else {
+        return ret;
+    };
+
+    if user_rsp >= USER_END - 8 {
+        return ret;
+    }
+    let frame_size = core::mem::size_of::<SignalFrame>();
+    let Some(frame_addr) = user_rsp.checked_sub(RED_ZONE + frame_size) else {
+        return ret;
+    };
+    let frame_addr = frame_addr & !0xf;
+
+    // both the frame and the return address have to be mapped, otherwise
+    // the signal stays pending for the next syscall
+    let mut rip = [0; 8];
+    let mut below = [0; core::mem::size_of::<SignalFrame>()];
+    let process = get_current_process();
+    let process = process.read();
+    let mapped = process
+        .page_table
+        .read(VirtAddr::new(user_rsp as u64), rip.len(), &mut rip)
+        .and_then(|_| {
+            process
+                .page_table
+                .read(VirtAddr::new(frame_addr as u64), below.len(), &mut below)
+        });
+    drop(process);
+    if mapped.is_err() {
+        return ret;
+    }
+
+    let frame = SignalFrame {
+        sig: sig as u64,
+        blocked: state.blocked,
+        ret: ret as u64,
+        rip: u64::from_ne_bytes(rip),
+        rsp: user_rsp as u64 + 8,
+    };
+    write_for_syscall(VirtAddr::new(frame_addr as u64), &[frame]);
+    write_for_syscall(VirtAddr::new(user_rsp as u64), &[trampoline as u64]);
+
+    state.pending &= !(1 << sig);
+    state.blocked |= 1 << sig;
+    frame_addr
+}
+
+/// A handler returned. Put back the blocked signals from its frame at
+/// `frame_addr`, the trampoline restores the rest.
+pub fn sigreturn(frame_addr: usize) -> Option<()> {
+    if frame_addr >= USER_END {
+        return None;
+    }
+    let mut frame = [0; core::mem::size_of::<SignalFrame>()];
+    get_current_process()
+        .read()
+        .page_table
+        .read(VirtAddr::new(frame_addr as u64), frame.len(), &mut frame)
+        .ok()?;
+    let blocked = u64::from_ne_bytes(frame[8..16].try_into().ok()?);
+
+    let stopped_by = {
+        let mut states = SIGNAL_STATES.lock();
+        let state = states.get_mut(&get_current_process_id())?;
+        // bit 0 stands for no signal
+        state.blocked = blocked & !UNMASKABLE & !1;
+        state.take_effect()
+    };
+    notify_stopped(&get_current_process(), stopped_by);
+    Some(())
+}
+
+/// Whether a signal came in that a wait of the current process has to give
+/// way to, so its handler can run or the process can exit.
+pub fn wait_interrupted() -> bool {
+    let pid = get_current_process_id();
+    SIGNAL_STATES
+        .lock()
+        .get(&pid)
+        .is_some_and(SignalState::interrupts_wait)
+}
+
+/// Stay put while the current process is stopped and not killed. Threads
+/// woken for other reasons call this before they carry on, so it must only
+/// be called where they hold no locks others need.
+pub fn wait_while_stopped() {
+    let pid = get_current_process_id();
+    let current_thread = get_current_thread();
+    loop {
+        {
+            let states = SIGNAL_STATES.lock();
+            let stopped = states
+                .get(&pid)
+                .is_some_and(|state| state.stopped && state.killed.is_none());
+            if !stopped {
+                return;
+            }
+            // blocked with the states locked, so a SIGCONT can't slip in
+            // before it
+            current_thread.write().state = ThreadState::Blocked;
+        }
+        framework::task::schedule();
+        while without_interrupts(|| current_thread.read().state == ThreadState::Blocked) {}
+    }
+}
+
+/// Forget the signal state of process `pid`, once it exits.
+pub fn release(pid: ProcessId) {
+    SIGNAL_STATES.lock().remove(&pid);
+}
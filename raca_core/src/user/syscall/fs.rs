@@ -3,11 +3,20 @@ use core::alloc::Layout;
 use crate::{
     fs::{
         operation::OpenMode,
-        vfs::inode::{FileInfo, InodeTy},
+        FileSystemType,
+        vfs::{
+            dev::{
+                gpt_editor::GptEntry,
+                partition::{PartitionInfo, PartitionType},
+            },
+            inode::{FileInfo, InodeTy},
+            pipe::BROKEN_PIPE,
+        },
     },
-    user::get_current_process,
+    user::{get_current_process, get_current_process_id, signal},
 };
 use alloc::{string::String, vec, vec::Vec};
+use uuid::Uuid;
 use framework::{
     memory::{addr_to_array, addr_to_mut_ref, write_for_syscall},
     ref_to_mut,
@@ -53,7 +62,12 @@ pub fn write(fd: usize, buf_addr: usize, buf_len: usize) -> usize {
         panic!("Read error at {:x}!", buf_addr);
     }
 
-    crate::fs::operation::write(fd, buf.as_slice())
+    let len = crate::fs::operation::write(fd, buf.as_slice());
+    if len == BROKEN_PIPE {
+        // raised here rather than in the pipe, where the file is still locked
+        signal::send(get_current_process_id(), signal::SIGPIPE);
+    }
+    len
 }
 
 pub fn read(fd: usize, buf_addr: usize, buf_len: usize) -> usize {
@@ -236,7 +250,13 @@ pub fn mount(
     to_path_len: usize,
     p_path_addr: usize,
     p_path_len: usize,
+    fs_type: usize,
 ) -> usize {
+    let fs_type = match FileSystemType::from_id(fs_type) {
+        Some(fs_type) => fs_type,
+        None => return 0,
+    };
+
     let mut to_buf = vec![0; to_path_len];
     let mut p_buf = vec![0; p_path_len];
 
@@ -259,9 +279,373 @@ pub fn mount(
     let to_path = String::from(core::str::from_utf8(to_buf.as_slice()).unwrap());
     let p_path = String::from(core::str::from_utf8(p_buf.as_slice()).unwrap());
 
-    if let Some(_) = crate::fs::operation::mount(to_path, p_path) {
+    if let Some(_) = crate::fs::operation::mount(to_path, p_path, fs_type) {
         1
     } else {
         0
     }
 }
+
+pub fn partition_info(fd: usize, buf_addr: usize) -> usize {
+    #[repr(C)]
+    struct RawPartitionInfo {
+        /// 1 for GPT, 2 for MBR
+        scheme: u64,
+        /// Type GUID, or the system id in the first byte for MBR
+        type_id: [u8; 16],
+        attributes: u64,
+        first_lba: u64,
+        last_lba: u64,
+        name_len: u64,
+        name: [u8; 144],
+    }
+
+    let PartitionInfo {
+        partition_type,
+        name,
+        attributes,
+        first_lba,
+        last_lba,
+    } = match crate::fs::operation::partition_info(fd) {
+        Some(info) => info,
+        None => return 0,
+    };
+
+    let (scheme, type_id) = match partition_type {
+        PartitionType::Gpt(guid) => (1, *guid.as_bytes()),
+        PartitionType::Mbr(ty) => {
+            let mut type_id = [0; 16];
+            type_id[0] = ty;
+            (2, type_id)
+        }
+    };
+
+    let mut raw = RawPartitionInfo {
+        scheme,
+        type_id,
+        attributes,
+        first_lba,
+        last_lba,
+        name_len: 0,
+        name: [0; 144],
+    };
+    let name_len = name.len().min(raw.name.len());
+    raw.name[..name_len].copy_from_slice(&name.as_bytes()[..name_len]);
+    raw.name_len = name_len as u64;
+
+    write_for_syscall(VirtAddr::new(buf_addr as u64), core::slice::from_ref(&raw));
+
+    1
+}
+
+const GPT_CREATE_TABLE: usize = 0;
+const GPT_ADD: usize = 1;
+const GPT_DELETE: usize = 2;
+const GPT_RESIZE: usize = 3;
+const GPT_INFO: usize = 4;
+const GPT_ENTRY: usize = 5;
+const GPT_FIND_FREE: usize = 6;
+
+#[repr(C)]
+struct RawGptEntry {
+    /// GUIDs in the order they are printed in
+    type_guid: [u8; 16],
+    unique_guid: [u8; 16],
+    first_lba: u64,
+    last_lba: u64,
+    attributes: u64,
+    name_len: u64,
+    name: [u8; 144],
+}
+
+#[repr(C)]
+struct RawGptInfo {
+    first_usable_lba: u64,
+    last_usable_lba: u64,
+    num_entries: u64,
+}
+
+/// Edit the GPT of a whole-disk node. The meaning of `arg` and `buf_addr`
+/// depends on `op`; the return value is 0 on failure.
+pub fn gpt_edit(fd: usize, op: usize, arg: usize, buf_addr: usize) -> usize {
+    use crate::fs::operation::{edit_gpt, read_gpt};
+
+    let result = match op {
+        GPT_CREATE_TABLE => edit_gpt(fd, true, |_| Some(1)),
+        GPT_ADD => {
+            let raw = addr_to_mut_ref::<RawGptEntry>(VirtAddr::new(buf_addr as u64));
+            let name_len = (raw.name_len as usize).min(raw.name.len());
+            let name = match core::str::from_utf8(&raw.name[..name_len]) {
+                Ok(name) => String::from(name),
+                Err(_) => return 0,
+            };
+            let entry = GptEntry {
+                type_guid: Uuid::from_bytes(raw.type_guid),
+                unique_guid: Uuid::from_bytes(raw.unique_guid),
+                first_lba: raw.first_lba,
+                last_lba: raw.last_lba,
+                attributes: raw.attributes,
+                name,
+            };
+            edit_gpt(fd, false, |table| table.add(entry).map(|index| index + 1))
+        }
+        GPT_DELETE => edit_gpt(fd, false, |table| table.delete(arg).map(|_| 1)),
+        GPT_RESIZE => edit_gpt(fd, false, |table| {
+            table.resize(arg, buf_addr as u64).map(|_| 1)
+        }),
+        GPT_INFO => read_gpt(fd).map(|table| {
+            let (first_usable_lba, last_usable_lba) = table.usable_range();
+            let info = RawGptInfo {
+                first_usable_lba,
+                last_usable_lba,
+                num_entries: table.num_entries() as u64,
+            };
+            write_for_syscall(VirtAddr::new(buf_addr as u64), core::slice::from_ref(&info));
+            1
+        }),
+        GPT_ENTRY => read_gpt(fd).and_then(|table| {
+            let entry = table.entry(arg)?;
+            let mut raw = RawGptEntry {
+                type_guid: *entry.type_guid.as_bytes(),
+                unique_guid: *entry.unique_guid.as_bytes(),
+                first_lba: entry.first_lba,
+                last_lba: entry.last_lba,
+                attributes: entry.attributes,
+                name_len: 0,
+                name: [0; 144],
+            };
+            let name_len = entry.name.len().min(raw.name.len());
+            raw.name[..name_len].copy_from_slice(&entry.name.as_bytes()[..name_len]);
+            raw.name_len = name_len as u64;
+            write_for_syscall(VirtAddr::new(buf_addr as u64), core::slice::from_ref(&raw));
+            Some(1)
+        }),
+        GPT_FIND_FREE => read_gpt(fd).and_then(|table| {
+            let (first_lba, last_lba) = table.find_free(arg as u64)?;
+            write_for_syscall(VirtAddr::new(buf_addr as u64), &[first_lba, last_lba]);
+            Some(1)
+        }),
+        _ => None,
+    };
+
+    result.unwrap_or(0)
+}
+
+pub fn loop_attach(path_addr: usize, path_len: usize) -> usize {
+    let mut buf = vec![0; path_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(path_addr as u64),
+        path_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", path_addr);
+    }
+
+    let path = match core::str::from_utf8(buf.as_slice()) {
+        Ok(path) => String::from(path),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::loop_attach(path) {
+        Some(id) => id + 1,
+        None => 0,
+    }
+}
+
+pub fn loop_detach(id: usize) -> usize {
+    match crate::fs::operation::loop_detach(id) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn ramdisk_create(size: usize) -> usize {
+    match crate::fs::vfs::dev::create_ram_disk(size) {
+        Some(num) => num + 1,
+        None => 0,
+    }
+}
+
+pub fn ioctl(fd: usize, cmd: usize, arg: usize) -> usize {
+    crate::fs::operation::ioctl(fd, cmd, arg).unwrap_or(0)
+}
+
+pub fn mkfifo(path_addr: usize, path_len: usize) -> usize {
+    let mut buf = vec![0; path_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(path_addr as u64),
+        path_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", path_addr);
+    }
+
+    let path = match core::str::from_utf8(buf.as_slice()) {
+        Ok(path) => String::from(path),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::mkfifo(path) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn mq_open(
+    name_addr: usize,
+    name_len: usize,
+    max_message_size: usize,
+    capacity: usize,
+) -> usize {
+    let mut buf = vec![0; name_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(name_addr as u64),
+        name_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", name_addr);
+    }
+
+    let name = match core::str::from_utf8(buf.as_slice()) {
+        Ok(name) => String::from(name),
+        Err(_) => return 0,
+    };
+
+    crate::fs::operation::mq_open(name, max_message_size, capacity).unwrap_or(0)
+}
+
+pub fn mq_unlink(name_addr: usize, name_len: usize) -> usize {
+    let mut buf = vec![0; name_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(name_addr as u64),
+        name_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", name_addr);
+    }
+
+    let name = match core::str::from_utf8(buf.as_slice()) {
+        Ok(name) => String::from(name),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::mq_unlink(name) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn socket() -> usize {
+    crate::fs::operation::socket().unwrap_or(0)
+}
+
+pub fn bind(fd: usize, path_addr: usize, path_len: usize) -> usize {
+    let mut buf = vec![0; path_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(path_addr as u64),
+        path_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", path_addr);
+    }
+
+    let path = match core::str::from_utf8(buf.as_slice()) {
+        Ok(path) => String::from(path),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::bind(fd, path) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn listen(fd: usize, backlog: usize) -> usize {
+    match crate::fs::operation::listen(fd, backlog) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn accept(fd: usize) -> usize {
+    crate::fs::operation::accept(fd).unwrap_or(0)
+}
+
+pub fn connect(fd: usize, path_addr: usize, path_len: usize) -> usize {
+    let mut buf = vec![0; path_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(path_addr as u64),
+        path_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", path_addr);
+    }
+
+    let path = match core::str::from_utf8(buf.as_slice()) {
+        Ok(path) => String::from(path),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::connect(fd, path) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn send_fd(socket_fd: usize, fd: usize) -> usize {
+    match crate::fs::operation::send_fd(socket_fd, fd) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn receive_fd(socket_fd: usize) -> usize {
+    crate::fs::operation::receive_fd(socket_fd).unwrap_or(0)
+}
+
+pub fn shm_open(name_addr: usize, name_len: usize, size: usize) -> usize {
+    let mut buf = vec![0; name_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(name_addr as u64),
+        name_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", name_addr);
+    }
+
+    let name = match core::str::from_utf8(buf.as_slice()) {
+        Ok(name) => String::from(name),
+        Err(_) => return 0,
+    };
+
+    crate::fs::operation::shm_open(name, size).unwrap_or(0)
+}
+
+pub fn shm_unlink(name_addr: usize, name_len: usize) -> usize {
+    let mut buf = vec![0; name_len];
+
+    if let Err(_) = get_current_process().read().page_table.read(
+        VirtAddr::new(name_addr as u64),
+        name_len,
+        &mut buf,
+    ) {
+        panic!("Read error at {:x}!", name_addr);
+    }
+
+    let name = match core::str::from_utf8(buf.as_slice()) {
+        Ok(name) => String::from(name),
+        Err(_) => return 0,
+    };
+
+    match crate::fs::operation::shm_unlink(name) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
@@ -1,8 +1,9 @@
 use core::alloc::Layout;
 
 use framework::ref_to_mut;
+use x86_64::VirtAddr;
 
-use crate::user::get_current_process;
+use crate::user::{get_current_process, mmap::unmap};
 
 pub fn malloc(size: usize, align: usize) -> usize {
     let layout = Layout::from_size_align(size, align);
@@ -27,3 +28,14 @@ pub fn free(addr: usize, size: usize, align: usize) -> usize {
     }
     0
 }
+
+pub fn mmap(fd: usize, offset: usize, len: usize, prot: usize) -> usize {
+    crate::fs::operation::mmap(fd, offset, len, prot).unwrap_or(0)
+}
+
+pub fn munmap(addr: usize) -> usize {
+    match unmap(VirtAddr::new(addr as u64)) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
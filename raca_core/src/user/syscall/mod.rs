@@ -1,8 +1,12 @@
+use super::signal;
+
 mod debug;
 mod fs;
 mod mm;
 mod task;
 
+/// `arg6` is the stack pointer of the syscall stub in raca_std, used to run
+/// signal handlers when the syscall returns.
 #[allow(unused_variables)]
 pub fn syscall_handler(
     idx: usize,
@@ -14,7 +18,8 @@ pub fn syscall_handler(
     arg6: usize,
 ) -> usize {
     //log::info!("Syscall {}",idx);
-    match idx {
+    signal::enter_syscall();
+    let ret = match idx {
         0 => debug::write(arg1, arg2),
         1 => debug::show_cpu_id(),
         2 => fs::open(arg1, arg2, arg3),
@@ -47,12 +52,37 @@ pub fn syscall_handler(
         16 => fs::get_cwd(),
         17 => fs::create(arg1, arg2, arg3),
         19 => fs::get_type(arg1),
-        20 => fs::mount(arg1, arg2, arg3, arg4),
+        20 => fs::mount(arg1, arg2, arg3, arg4, arg5),
         21 => task::exit(arg1),
         22 => task::done_signal(arg1),
         23 => task::has_signal(arg1),
         24 => task::start_wait_for_signal(arg1),
         25 => task::get_signal(arg1),
+        26 => fs::partition_info(arg1, arg2),
+        27 => fs::gpt_edit(arg1, arg2, arg3, arg4),
+        28 => fs::loop_attach(arg1, arg2),
+        29 => fs::loop_detach(arg1),
+        30 => fs::ramdisk_create(arg1),
+        31 => fs::ioctl(arg1, arg2, arg3),
+        32 => mm::mmap(arg1, arg2, arg3, arg4),
+        33 => mm::munmap(arg1),
+        34 => fs::mkfifo(arg1, arg2),
+        35 => fs::mq_open(arg1, arg2, arg3, arg4),
+        36 => fs::mq_unlink(arg1, arg2),
+        37 => fs::socket(),
+        38 => fs::bind(arg1, arg2, arg3),
+        39 => fs::listen(arg1, arg2),
+        40 => fs::accept(arg1),
+        41 => fs::connect(arg1, arg2, arg3),
+        42 => fs::send_fd(arg1, arg2),
+        43 => fs::receive_fd(arg1),
+        44 => fs::shm_open(arg1, arg2, arg3),
+        45 => fs::shm_unlink(arg1, arg2),
+        46 => task::kill(arg1, arg2),
+        47 => task::sigaction(arg1, arg2, arg3),
+        48 => task::sigprocmask(arg1, arg2),
+        50 => task::sigreturn(arg1),
         _ => 0,
-    }
+    };
+    signal::leave_syscall(ret, arg6)
 }
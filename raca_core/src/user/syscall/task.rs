@@ -5,13 +5,13 @@ use crate::{
         get_inode_by_fd, init_file_descriptor_manager,
         init_file_descriptor_manager_with_stdin_stdout,
     },
-    user::{get_current_process, get_current_thread},
+    user::{get_current_process, get_current_thread, signal},
 };
-use alloc::{sync::Arc, vec};
+use alloc::{string::String, sync::Arc, vec};
 
 use framework::{
     memory::addr_to_mut_ref,
-    task::{signal::Signal, thread::ThreadState, Process},
+    task::{process::ProcessId, signal::Signal, thread::ThreadState, Process},
 };
 use x86_64::VirtAddr;
 
@@ -24,8 +24,13 @@ struct ProcessInfo {
     name_len: usize,
     stdin: usize,
     stdout: usize,
+    cmdline_addr: usize,
+    cmdline_len: usize,
 }
 
+/// Longest command line kept for a process
+const MAX_CMDLINE_LEN: usize = 4096;
+
 pub fn create_process(info_addr: usize) -> usize {
     let info: &mut ProcessInfo = addr_to_mut_ref(VirtAddr::new(info_addr as u64));
 
@@ -35,6 +40,8 @@ pub fn create_process(info_addr: usize) -> usize {
     let name_len = info.name_len;
     let stdin = info.stdin;
     let stdout = info.stdout;
+    let cmdline_addr = info.cmdline_addr;
+    let cmdline_len = info.cmdline_len.min(MAX_CMDLINE_LEN);
 
     let func = || {
         let mut buf = vec![0; binary_len];
@@ -63,8 +70,21 @@ pub fn create_process(info_addr: usize) -> usize {
             return 0;
         }
 
+        let mut cmdline = vec![0; cmdline_len];
+        let cmdline = match get_current_process().read().page_table.read(
+            VirtAddr::new(cmdline_addr as u64),
+            cmdline_len,
+            &mut cmdline,
+        ) {
+            Ok(_) if cmdline_len > 0 => String::from_utf8(cmdline).ok(),
+            _ => None,
+        };
+
         let process = Process::new_user_process(name.unwrap(), buf.leak());
         let pid = process.read().id;
+        if let Some(cmdline) = cmdline {
+            crate::user::set_cmdline(pid, cmdline);
+        }
 
         if let Some(stdin) = get_inode_by_fd(stdin) {
             let stdout = get_inode_by_fd(stdout).unwrap();
@@ -84,27 +104,40 @@ pub fn create_process(info_addr: usize) -> usize {
 }
 
 pub fn exit(code: usize) -> usize {
-    let process = get_current_process();
-    if let Some(ref father) = process.read().father {
-        father
-            .upgrade()
-            .unwrap()
-            .write()
-            .signal_manager
-            .register_signal(
-                1,
-                Signal {
-                    ty: 0,
-                    data: [code as u64, 0, 0, 0, 0, 0, 0, 0],
-                },
-            );
-    }
-    drop(process);
-    framework::task::scheduler::exit();
+    crate::user::terminate(get_current_process(), code);
     log::info!("Done");
     return 0;
 }
 
+pub fn kill(pid: usize, sig: usize) -> usize {
+    match signal::send(ProcessId(pid as u64), sig) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+pub fn sigaction(sig: usize, action: usize, trampoline: usize) -> usize {
+    match signal::set_action(sig, action, trampoline) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
+/// Returns the old mask plus one.
+pub fn sigprocmask(how: usize, set: usize) -> usize {
+    match signal::set_mask(how, set as u64) {
+        Some(old) => old as usize + 1,
+        None => 0,
+    }
+}
+
+pub fn sigreturn(frame_addr: usize) -> usize {
+    match signal::sigreturn(frame_addr) {
+        Some(_) => 1,
+        None => 0,
+    }
+}
+
 pub fn has_signal(ty: usize) -> usize {
     let process = get_current_process();
     let process = process.read();
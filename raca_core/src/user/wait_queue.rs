@@ -0,0 +1,122 @@
+use alloc::{
+    sync::{Arc, Weak},
+    vec::Vec,
+};
+use framework::task::{thread::ThreadState, Thread};
+use spin::{Mutex, RwLock};
+use x86_64::instructions::interrupts::without_interrupts;
+
+use crate::drivers::clock;
+
+use super::{get_current_thread, signal};
+
+/// Threads blocked until some condition changes, such as data arriving in a
+/// buffer.
+pub struct WaitQueue {
+    waiters: Mutex<Vec<Weak<RwLock<Thread>>>>,
+}
+
+impl WaitQueue {
+    pub const fn new() -> Self {
+        Self {
+            waiters: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Block the current thread until `poll` returns a value. Gives `None`
+    /// instead if a signal comes in that the process has a handler for, so
+    /// the handler can run when the syscall returns.
+    ///
+    /// `poll` runs with the queue locked, so a [`wake_all`](Self::wake_all)
+    /// after the state it looks at changed can not slip in between the check
+    /// and going to sleep.
+    pub fn wait_until<T>(&self, poll: impl FnMut() -> Option<T>) -> Option<T> {
+        self.wait(true, poll)
+    }
+
+    /// Like [`wait_until`](Self::wait_until), but signals don't cut the wait
+    /// short and the process doesn't stop in it. For waits that have to see
+    /// something through, such as a command a device is working on.
+    pub fn wait_uninterruptible<T>(&self, poll: impl FnMut() -> Option<T>) -> T {
+        // only a signal ends the wait without a value
+        self.wait(false, poll).unwrap()
+    }
+
+    fn wait<T>(&self, interruptible: bool, mut poll: impl FnMut() -> Option<T>) -> Option<T> {
+        let current_thread = get_current_thread();
+        loop {
+            // interrupt handlers wake queues too, they must not find the
+            // locks taken by the thread they interrupted
+            let value = without_interrupts(|| {
+                let mut waiters = self.waiters.lock();
+                // a signal may have woken the thread instead of `wake_all`,
+                // a stale entry would wake it again wherever it is later
+                waiters.retain(|waiter| waiter.as_ptr() != Arc::as_ptr(&current_thread));
+                if let Some(value) = poll() {
+                    return Some(Some(value));
+                }
+                // blocked before looking at the signals, a signal sent after
+                // that wakes the thread again
+                let previous = current_thread.read().state;
+                current_thread.write().state = ThreadState::Blocked;
+                if interruptible && signal::wait_interrupted() {
+                    current_thread.write().state = previous;
+                    return Some(None);
+                }
+                waiters.push(Arc::downgrade(&current_thread));
+                None
+            });
+            if let Some(value) = value {
+                return value;
+            }
+
+            framework::task::schedule();
+            while without_interrupts(|| current_thread.read().state == ThreadState::Blocked) {}
+            // a wake up must not let a stopped process carry on, a kill is
+            // seen by `wait_interrupted` or when the syscall returns. Waits
+            // that have to be seen through stop on the syscall return too.
+            if interruptible {
+                signal::wait_while_stopped();
+            }
+        }
+    }
+
+    /// Like [`wait_until`](Self::wait_until), but give up after `timeout_ms`
+    /// milliseconds. `None` waits forever.
+    ///
+    /// Nothing wakes a thread when its time is up, so while a timeout is
+    /// pending the thread keeps yielding instead of blocking.
+    pub fn wait_timeout<T>(
+        &self,
+        timeout_ms: Option<u64>,
+        mut poll: impl FnMut() -> Option<T>,
+    ) -> Option<T> {
+        let Some(timeout_ms) = timeout_ms else {
+            return self.wait_until(poll);
+        };
+
+        let deadline = clock::uptime_ms().saturating_add(timeout_ms);
+        loop {
+            if let Some(value) = poll() {
+                return Some(value);
+            }
+            if clock::uptime_ms() >= deadline || signal::wait_interrupted() {
+                return None;
+            }
+            framework::task::schedule();
+            signal::wait_while_stopped();
+        }
+    }
+
+    /// Make every waiting thread check its condition again. Can be called
+    /// from interrupt handlers.
+    pub fn wake_all(&self) {
+        without_interrupts(|| {
+            for thread in self.waiters.lock().drain(..) {
+                if let Some(thread) = thread.upgrade() {
+                    thread.write().state = ThreadState::Ready;
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,312 @@
+use core::fmt;
+
+use alloc::{string::String, vec::Vec};
+
+use crate::fs::{FileDescriptor, OpenMode};
+
+/// A GUID in the byte order it is printed in
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub struct Guid(pub [u8; 16]);
+
+impl Guid {
+    pub const NIL: Self = Self([0; 16]);
+    pub const EFI_SYSTEM: Self = Self([
+        0xc1, 0x2a, 0x73, 0x28, 0xf8, 0x1f, 0x11, 0xd2, 0xba, 0x4b, 0x00, 0xa0, 0xc9, 0x3e, 0xc9,
+        0x3b,
+    ]);
+    pub const BASIC_DATA: Self = Self([
+        0xeb, 0xd0, 0xa0, 0xa2, 0xb9, 0xe5, 0x44, 0x33, 0x87, 0xc0, 0x68, 0xb6, 0xb7, 0x26, 0x99,
+        0xc7,
+    ]);
+    pub const LINUX_FILESYSTEM: Self = Self([
+        0x0f, 0xc6, 0x3d, 0xaf, 0x84, 0x83, 0x47, 0x72, 0x8e, 0x79, 0x3d, 0x69, 0xd8, 0x47, 0x7d,
+        0xe4,
+    ]);
+
+    /// Parse the `xxxxxxxx-xxxx-xxxx-xxxx-xxxxxxxxxxxx` form.
+    pub fn parse(s: &str) -> Option<Self> {
+        let groups: Vec<&str> = s.split('-').collect();
+        let lens = [8, 4, 4, 4, 12];
+        if groups.len() != lens.len() || groups.iter().zip(lens).any(|(g, len)| g.len() != len) {
+            return None;
+        }
+
+        let digits: Vec<u8> = groups.concat().bytes().collect();
+        let mut guid = [0; 16];
+        for (idx, pair) in digits.chunks_exact(2).enumerate() {
+            let hex = core::str::from_utf8(pair).ok()?;
+            guid[idx] = u8::from_str_radix(hex, 16).ok()?;
+        }
+        Some(Self(guid))
+    }
+}
+
+impl fmt::Display for Guid {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, byte) in self.0.iter().enumerate() {
+            if idx == 4 || idx == 6 || idx == 8 || idx == 10 {
+                write!(f, "-")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PartitionType {
+    /// Partition type GUID of a GPT entry
+    Gpt(Guid),
+    /// System id byte of an MBR entry
+    Mbr(u8),
+}
+
+#[derive(Clone, Debug)]
+pub struct PartitionInfo {
+    pub partition_type: PartitionType,
+    /// GPT partition name, empty for MBR partitions
+    pub name: String,
+    /// GPT attribute bits, or the status byte of an MBR entry
+    pub attributes: u64,
+    pub first_lba: u64,
+    /// Last sector of the partition, inclusive
+    pub last_lba: u64,
+}
+
+impl PartitionInfo {
+    /// Size of the partition in bytes
+    pub fn size(&self) -> u64 {
+        (self.last_lba - self.first_lba + 1) * 512
+    }
+}
+
+const BLKDISCARD: usize = 0x1277;
+
+impl FileDescriptor {
+    /// Tell the disk behind a whole-disk node that `len` bytes from `offset`
+    /// are unused. Both have to be multiples of 512.
+    pub fn discard(&self, offset: u64, len: u64) -> Result<(), ()> {
+        let range = [offset, len];
+        self.ioctl(BLKDISCARD, range.as_ptr() as usize).map(|_| ())
+    }
+
+    /// Read the partition table entry of an opened partition device.
+    pub fn partition_info(&self) -> Result<PartitionInfo, ()> {
+        #[repr(C)]
+        struct RawPartitionInfo {
+            scheme: u64,
+            type_id: [u8; 16],
+            attributes: u64,
+            first_lba: u64,
+            last_lba: u64,
+            name_len: u64,
+            name: [u8; 144],
+        }
+
+        let mut raw = RawPartitionInfo {
+            scheme: 0,
+            type_id: [0; 16],
+            attributes: 0,
+            first_lba: 0,
+            last_lba: 0,
+            name_len: 0,
+            name: [0; 144],
+        };
+
+        const PARTITION_INFO_SYSCALL_ID: u64 = 26;
+        let code = crate::syscall(
+            PARTITION_INFO_SYSCALL_ID,
+            self.0,
+            &mut raw as *mut RawPartitionInfo as usize,
+            0,
+            0,
+            0,
+        );
+        if code == 0 {
+            return Err(());
+        }
+
+        let partition_type = match raw.scheme {
+            1 => PartitionType::Gpt(Guid(raw.type_id)),
+            2 => PartitionType::Mbr(raw.type_id[0]),
+            _ => return Err(()),
+        };
+
+        Ok(PartitionInfo {
+            partition_type,
+            name: String::from_utf8_lossy(&raw.name[..raw.name_len as usize]).into(),
+            attributes: raw.attributes,
+            first_lba: raw.first_lba,
+            last_lba: raw.last_lba,
+        })
+    }
+}
+
+/// An entry of a GUID partition table
+#[derive(Clone, Debug)]
+pub struct GptEntry {
+    pub type_guid: Guid,
+    /// Left as `Guid::NIL` to let the kernel pick a random one
+    pub unique_guid: Guid,
+    pub first_lba: u64,
+    /// Last sector of the partition, inclusive
+    pub last_lba: u64,
+    pub attributes: u64,
+    /// At most 36 UTF-16 code units are stored
+    pub name: String,
+}
+
+#[repr(C)]
+struct RawGptEntry {
+    type_guid: [u8; 16],
+    unique_guid: [u8; 16],
+    first_lba: u64,
+    last_lba: u64,
+    attributes: u64,
+    name_len: u64,
+    name: [u8; 144],
+}
+
+#[derive(Clone, Copy, Debug)]
+pub struct GptInfo {
+    pub first_usable_lba: u64,
+    pub last_usable_lba: u64,
+    pub num_entries: usize,
+}
+
+const GPT_EDIT_SYSCALL_ID: u64 = 27;
+
+const GPT_CREATE_TABLE: usize = 0;
+const GPT_ADD: usize = 1;
+const GPT_DELETE: usize = 2;
+const GPT_RESIZE: usize = 3;
+const GPT_INFO: usize = 4;
+const GPT_ENTRY: usize = 5;
+const GPT_FIND_FREE: usize = 6;
+
+/// The GUID partition table of a whole-disk node like `/dev/hdb`.
+///
+/// Every change is written to both the primary and the backup table at once,
+/// and the partition nodes in `/dev` are refreshed afterwards. Partitions that
+/// are mounted must not be changed.
+pub struct GptDisk {
+    fd: FileDescriptor,
+}
+
+impl GptDisk {
+    pub fn open(path: &str) -> Result<Self, ()> {
+        let fd = FileDescriptor::open(path, OpenMode::Write)?;
+        Ok(Self { fd })
+    }
+
+    fn edit(&self, op: usize, arg: usize, buf_addr: usize) -> usize {
+        crate::syscall(GPT_EDIT_SYSCALL_ID, self.fd.0, op, arg, buf_addr, 0)
+    }
+
+    /// Replace whatever the disk holds with an empty table.
+    pub fn create_table(&self) -> Result<(), ()> {
+        match self.edit(GPT_CREATE_TABLE, 0, 0) {
+            0 => Err(()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn info(&self) -> Result<GptInfo, ()> {
+        let mut raw = [0u64; 3];
+        if self.edit(GPT_INFO, 0, raw.as_mut_ptr() as usize) == 0 {
+            return Err(());
+        }
+        Ok(GptInfo {
+            first_usable_lba: raw[0],
+            last_usable_lba: raw[1],
+            num_entries: raw[2] as usize,
+        })
+    }
+
+    /// The entry in slot `index`, if it is used.
+    pub fn entry(&self, index: usize) -> Option<GptEntry> {
+        let mut raw = RawGptEntry {
+            type_guid: [0; 16],
+            unique_guid: [0; 16],
+            first_lba: 0,
+            last_lba: 0,
+            attributes: 0,
+            name_len: 0,
+            name: [0; 144],
+        };
+        if self.edit(GPT_ENTRY, index, &mut raw as *mut RawGptEntry as usize) == 0 {
+            return None;
+        }
+        Some(GptEntry {
+            type_guid: Guid(raw.type_guid),
+            unique_guid: Guid(raw.unique_guid),
+            first_lba: raw.first_lba,
+            last_lba: raw.last_lba,
+            attributes: raw.attributes,
+            name: String::from_utf8_lossy(&raw.name[..raw.name_len as usize]).into(),
+        })
+    }
+
+    /// All used entries along with their slot.
+    pub fn entries(&self) -> Result<Vec<(usize, GptEntry)>, ()> {
+        let info = self.info()?;
+        Ok((0..info.num_entries)
+            .filter_map(|index| Some((index, self.entry(index)?)))
+            .collect())
+    }
+
+    /// A 1 MiB aligned free range of `sectors` sectors, or the largest free
+    /// range if `sectors` is 0.
+    pub fn find_free(&self, sectors: u64) -> Option<(u64, u64)> {
+        let mut range = [0u64; 2];
+        match self.edit(GPT_FIND_FREE, sectors as usize, range.as_mut_ptr() as usize) {
+            0 => None,
+            _ => Some((range[0], range[1])),
+        }
+    }
+
+    /// Add a partition and return the slot it was put in.
+    pub fn add(&self, entry: &GptEntry) -> Result<usize, ()> {
+        let mut raw = RawGptEntry {
+            type_guid: entry.type_guid.0,
+            unique_guid: entry.unique_guid.0,
+            first_lba: entry.first_lba,
+            last_lba: entry.last_lba,
+            attributes: entry.attributes,
+            name_len: 0,
+            name: [0; 144],
+        };
+
+        // cut on a character boundary
+        let mut name_len = 0;
+        for (idx, c) in entry.name.char_indices().take(36) {
+            name_len = idx + c.len_utf8();
+        }
+        raw.name[..name_len].copy_from_slice(&entry.name.as_bytes()[..name_len]);
+        raw.name_len = name_len as u64;
+
+        match self.edit(GPT_ADD, 0, &raw as *const RawGptEntry as usize) {
+            0 => Err(()),
+            index => Ok(index - 1),
+        }
+    }
+
+    pub fn delete(&self, index: usize) -> Result<(), ()> {
+        match self.edit(GPT_DELETE, index, 0) {
+            0 => Err(()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Move the end of a partition. The filesystem inside is not touched.
+    pub fn resize(&self, index: usize, last_lba: u64) -> Result<(), ()> {
+        match self.edit(GPT_RESIZE, index, last_lba as usize) {
+            0 => Err(()),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn close(&mut self) {
+        self.fd.close();
+    }
+}
@@ -0,0 +1,71 @@
+use crate::fs::{munmap, FileDescriptor, OpenMode};
+
+const FBIOGET_INFO: usize = 0x4600;
+
+/// 32 bits per pixel, `0x00RRGGBB` in native byte order
+pub const PIXEL_FORMAT_XRGB8888: u32 = 1;
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FramebufferInfo {
+    pub width: u32,
+    pub height: u32,
+    /// Bytes from the start of one line to the next
+    pub pitch: u32,
+    pub bpp: u32,
+    pub pixel_format: u32,
+    /// Bytes covered by the framebuffer, rounded up to whole pages
+    pub size: u32,
+}
+
+/// A framebuffer device mapped into memory.
+pub struct Framebuffer {
+    fd: FileDescriptor,
+    info: FramebufferInfo,
+    pixels: &'static mut [u8],
+}
+
+impl Framebuffer {
+    /// Open and map a framebuffer device such as `/dev/fb0`.
+    pub fn open(path: &str) -> Result<Self, ()> {
+        let mut fd = FileDescriptor::open(path, OpenMode::Write)?;
+
+        let mut info = FramebufferInfo::default();
+        let mapped = fd
+            .ioctl(FBIOGET_INFO, &mut info as *mut FramebufferInfo as usize)
+            .and_then(|_| fd.mmap(0, info.size as usize));
+
+        match mapped {
+            Ok(pixels) => Ok(Self { fd, info, pixels }),
+            Err(()) => {
+                fd.close();
+                Err(())
+            }
+        }
+    }
+
+    pub fn info(&self) -> FramebufferInfo {
+        self.info
+    }
+
+    /// The mapped framebuffer, `pitch` bytes per line.
+    pub fn bytes(&mut self) -> &mut [u8] {
+        self.pixels
+    }
+
+    /// Set one pixel to `0x00RRGGBB`.
+    pub fn put_pixel(&mut self, x: usize, y: usize, color: u32) {
+        if x >= self.info.width as usize || y >= self.info.height as usize {
+            return;
+        }
+        let offset = y * self.info.pitch as usize + x * 4;
+        self.pixels[offset..offset + 4].copy_from_slice(&color.to_ne_bytes());
+    }
+}
+
+impl Drop for Framebuffer {
+    fn drop(&mut self) {
+        let _ = munmap(self.pixels);
+        self.fd.close();
+    }
+}
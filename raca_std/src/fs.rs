@@ -2,7 +2,7 @@ use core::fmt;
 
 use alloc::{string::String, vec::Vec};
 
-use crate::println;
+use crate::{println, signal::INTERRUPTED};
 
 #[repr(C)]
 pub enum OpenMode {
@@ -10,6 +10,11 @@ pub enum OpenMode {
     Write = 1,
 }
 
+/// Pages of a mapping can always be read.
+pub const PROT_READ: usize = 1;
+pub const PROT_WRITE: usize = 2;
+pub const PROT_EXEC: usize = 4;
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct FileDescriptor(pub usize, bool);
 
@@ -45,6 +50,11 @@ impl FileDescriptor {
         }
     }
 
+    /// Wrap a descriptor number a syscall handed out.
+    pub(crate) fn from_raw(fd: usize) -> Self {
+        Self(fd, false)
+    }
+
     pub fn stdin() -> Self {
         Self(0, false)
     }
@@ -53,6 +63,8 @@ impl FileDescriptor {
         Self(1, false)
     }
 
+    /// Returns how much was read, or [`INTERRUPTED`] if a signal handler ran
+    /// before anything came in.
     pub fn read(&self, buffer: &mut [u8]) -> usize {
         assert_ne!(self.1, true, "This File Descriptor had been closed!");
 
@@ -71,10 +83,15 @@ impl FileDescriptor {
         let mut readed = 0;
         while readed < buffer.len() {
             let read_size = self.read(&mut buffer[readed..]);
-            readed += read_size;
+            if read_size != INTERRUPTED {
+                readed += read_size;
+            }
         }
     }
 
+    /// Returns how much was written, or `usize::MAX` if this is a pipe or
+    /// socket nobody reads from any more. [`INTERRUPTED`] if a signal
+    /// handler ran before anything was written.
     pub fn write(&self, buffer: &[u8]) -> usize {
         assert_ne!(self.1, true, "This File Descriptor had been closed!");
 
@@ -110,15 +127,70 @@ impl FileDescriptor {
         crate::syscall(CLOSE_SYSCALL_ID, self.0, 0, 0, 0, 0);
     }
 
+    /// Whether the descriptor refers to an open file, e.g. to check if the
+    /// parent passed a stdin or stdout.
+    pub fn is_open(&self) -> bool {
+        const GET_TYPE_SYSCALL_ID: u64 = 19;
+        crate::syscall(GET_TYPE_SYSCALL_ID, self.0, 0, 0, 0, 0) != usize::MAX
+    }
+
     pub fn get_type(&self) -> FileType {
         const GET_TYPE_SYSCALL_ID: u64 = 19;
         let ty = crate::syscall(GET_TYPE_SYSCALL_ID, self.0, 0, 0, 0, 0) as usize;
         match ty {
             0 => FileType::Dir,
             1 => FileType::File,
+            2 => FileType::Fifo,
+            3 => FileType::Socket,
             _ => unreachable!(),
         }
     }
+
+    /// Send a device specific request. `arg` is often a pointer to a
+    /// structure the device reads or fills in.
+    pub fn ioctl(&self, cmd: usize, arg: usize) -> Result<usize, ()> {
+        const IOCTL_SYSCALL_ID: u64 = 31;
+        let ret = crate::syscall(IOCTL_SYSCALL_ID, self.0, cmd, arg, 0, 0);
+        if ret == 0 {
+            Err(())
+        } else {
+            Ok(ret)
+        }
+    }
+
+    /// Map `len` bytes of the file from `offset` on, which has to be page
+    /// aligned, into memory. Writes go straight to the file if it was opened
+    /// for writing.
+    pub fn mmap(&self, offset: usize, len: usize) -> Result<&'static mut [u8], ()> {
+        self.mmap_with(offset, len, 0)
+    }
+
+    /// Like [`mmap`](Self::mmap), with the protection given as `PROT_*` flags.
+    /// Asking for [`PROT_WRITE`] fails unless the file was opened for writing.
+    pub fn mmap_with(
+        &self,
+        offset: usize,
+        len: usize,
+        prot: usize,
+    ) -> Result<&'static mut [u8], ()> {
+        const MMAP_SYSCALL_ID: u64 = 32;
+        let addr = crate::syscall(MMAP_SYSCALL_ID, self.0, offset, len, prot, 0);
+        if addr == 0 {
+            Err(())
+        } else {
+            Ok(unsafe { core::slice::from_raw_parts_mut(addr as *mut u8, len) })
+        }
+    }
+}
+
+/// Undo a [`FileDescriptor::mmap`]. The slice must not be used afterwards.
+pub fn munmap(buf: &mut [u8]) -> Result<(), ()> {
+    const MUNMAP_SYSCALL_ID: u64 = 33;
+    if crate::syscall(MUNMAP_SYSCALL_ID, buf.as_ptr() as usize, 0, 0, 0, 0) == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
 }
 
 impl fmt::Write for FileDescriptor {
@@ -136,6 +208,8 @@ pub enum FileType {
     Dir = 0,
     #[default]
     File = 1,
+    Fifo = 2,
+    Socket = 3,
 }
 
 #[repr(C)]
@@ -234,15 +308,92 @@ pub fn create(path: String, ty: FileType) -> Result<FileDescriptor, ()> {
     }
 }
 
+#[derive(PartialEq, Eq, Clone, Copy, Debug, Default)]
+pub enum FileSystemType {
+    /// Let the kernel probe the partition
+    #[default]
+    Auto = 0,
+    Fat32 = 1,
+    Iso9660 = 2,
+}
+
+impl FileSystemType {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "auto" => Some(Self::Auto),
+            "fat32" | "vfat" => Some(Self::Fat32),
+            "iso9660" => Some(Self::Iso9660),
+            _ => None,
+        }
+    }
+}
+
 pub fn mount(path: String, partition: String) -> Result<(),()> {
+    mount_with_type(path, partition, FileSystemType::Auto)
+}
+
+pub fn mount_with_type(path: String, partition: String, fs_type: FileSystemType) -> Result<(), ()> {
     const MOUNT_SYSCALL_ID: u64 = 20;
-    crate::syscall(
+    let code = crate::syscall(
         MOUNT_SYSCALL_ID,
         path.as_ptr() as usize,
         path.len(),
         partition.as_ptr() as usize,
         partition.len(),
+        fs_type as usize,
+    );
+    if code == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Create a named pipe. Opening it blocks until the other end is opened too.
+pub fn mkfifo(path: &str) -> Result<(), ()> {
+    const MKFIFO_SYSCALL_ID: u64 = 34;
+    if crate::syscall(MKFIFO_SYSCALL_ID, path.as_ptr() as usize, path.len(), 0, 0, 0) == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Attach a file to the first free `/dev/loopN` and return N. Partitions in
+/// the image show up as `/dev/loopNpM`.
+pub fn loop_attach(path: &str) -> Result<usize, ()> {
+    const LOOP_ATTACH_SYSCALL_ID: u64 = 28;
+    let id = crate::syscall(
+        LOOP_ATTACH_SYSCALL_ID,
+        path.as_ptr() as usize,
+        path.len(),
+        0,
+        0,
         0,
     );
-    Ok(())
+    if id == 0 {
+        Err(())
+    } else {
+        Ok(id - 1)
+    }
+}
+
+pub fn loop_detach(id: usize) -> Result<(), ()> {
+    const LOOP_DETACH_SYSCALL_ID: u64 = 29;
+    if crate::syscall(LOOP_DETACH_SYSCALL_ID, id, 0, 0, 0, 0) == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Create `/dev/ramN` with `size` bytes of kernel memory and return N.
+pub fn ramdisk_create(size: usize) -> Result<usize, ()> {
+    const RAMDISK_CREATE_SYSCALL_ID: u64 = 30;
+    let num = crate::syscall(RAMDISK_CREATE_SYSCALL_ID, size, 0, 0, 0, 0);
+    if num == 0 {
+        Err(())
+    } else {
+        Ok(num - 1)
+    }
 }
@@ -1,22 +1,35 @@
-use alloc::string::String;
+use alloc::{string::String, vec::Vec};
 
-use crate::fs::FileDescriptor;
+use crate::{fs::FileDescriptor, signal::INTERRUPTED};
 
 impl FileDescriptor {
-    pub fn stdin_read_line(&self, buf: &mut String) {
+    /// Read a line, without the trailing newline. The terminal does the
+    /// editing and echo, so this just collects what it hands out. Returns the
+    /// number of bytes read, 0 at end of file.
+    pub fn stdin_read_line(&self, buf: &mut String) -> usize {
         buf.clear(); // make sure that the buf is clean
 
-        let mut tmp_buf = [0; 1];
-        self.read(&mut tmp_buf);
-
-        while tmp_buf[0] != b'\n' {
-            if tmp_buf[0] == 8 {
-                // backspace
-                let _ = buf.pop();
-            } else {
-                buf.push(tmp_buf[0] as char);
+        let mut line = Vec::new();
+        let mut tmp_buf = [0; 256];
+        loop {
+            let len = self.read(&mut tmp_buf);
+            if len == INTERRUPTED {
+                continue;
+            }
+            if len == 0 {
+                break;
+            }
+            line.extend_from_slice(&tmp_buf[..len]);
+            if line.last() == Some(&b'\n') {
+                break;
             }
-            self.read(&mut tmp_buf);
         }
+
+        let read = line.len();
+        if line.last() == Some(&b'\n') {
+            line.pop();
+        }
+        buf.push_str(&String::from_utf8_lossy(&line));
+        read
     }
 }
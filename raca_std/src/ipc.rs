@@ -0,0 +1,156 @@
+use crate::fs::FileDescriptor;
+
+const MQ_SEND: usize = 1;
+const MQ_RECEIVE: usize = 2;
+const MQ_GET_ATTR: usize = 3;
+
+const NO_TIMEOUT: u64 = u64::MAX;
+
+/// Largest message size a queue can be created with
+pub const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+/// Most messages a queue can be created to hold
+pub const MAX_CAPACITY: usize = 256;
+
+#[repr(C)]
+struct MqRequest {
+    buf_addr: u64,
+    len: u64,
+    timeout_ms: u64,
+    sender: u64,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct QueueAttr {
+    pub max_message_size: u64,
+    pub capacity: u64,
+    /// Messages currently queued
+    pub messages: u64,
+}
+
+/// What [`MessageQueue::receive`] got
+#[derive(Clone, Copy, Debug)]
+pub struct Received {
+    /// Bytes of the message that were copied into the buffer
+    pub len: usize,
+    /// Pid of the process that sent it
+    pub sender: u64,
+}
+
+/// A named message queue under `/dev/mq`, shared by every process that opens
+/// the same name. Messages keep their boundaries.
+pub struct MessageQueue {
+    fd: FileDescriptor,
+}
+
+impl MessageQueue {
+    fn mq_open(name: &str, max_message_size: usize, capacity: usize) -> Result<Self, ()> {
+        const MQ_OPEN_SYSCALL_ID: u64 = 35;
+        let fd = crate::syscall(
+            MQ_OPEN_SYSCALL_ID,
+            name.as_ptr() as usize,
+            name.len(),
+            max_message_size,
+            capacity,
+            0,
+        );
+        if fd == 0 {
+            Err(())
+        } else {
+            Ok(Self {
+                fd: FileDescriptor::from_raw(fd),
+            })
+        }
+    }
+
+    /// Open an existing queue.
+    pub fn open(name: &str) -> Result<Self, ()> {
+        Self::mq_open(name, 0, 0)
+    }
+
+    /// Open the queue, creating it first if needed. The limits only apply to
+    /// a queue that is newly created, and can be at most [`MAX_MESSAGE_SIZE`]
+    /// and [`MAX_CAPACITY`].
+    pub fn create(name: &str, max_message_size: usize, capacity: usize) -> Result<Self, ()> {
+        if max_message_size == 0 || capacity == 0 {
+            return Err(());
+        }
+        Self::mq_open(name, max_message_size, capacity)
+    }
+
+    /// Remove the name. Processes that have the queue open can keep using it.
+    pub fn unlink(name: &str) -> Result<(), ()> {
+        const MQ_UNLINK_SYSCALL_ID: u64 = 36;
+        let code = crate::syscall(
+            MQ_UNLINK_SYSCALL_ID,
+            name.as_ptr() as usize,
+            name.len(),
+            0,
+            0,
+            0,
+        );
+        if code == 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn attr(&self) -> Result<QueueAttr, ()> {
+        let mut attr = QueueAttr::default();
+        self.fd.ioctl(MQ_GET_ATTR, &mut attr as *mut QueueAttr as usize)?;
+        Ok(attr)
+    }
+
+    /// Send `message`, waiting while the queue is full.
+    pub fn send(&self, message: &[u8]) -> Result<(), ()> {
+        self.send_timeout(message, None)
+    }
+
+    /// Send `message`, waiting at most `timeout_ms` milliseconds for room.
+    /// Fails right away if the queue is full and the timeout is `Some(0)`.
+    pub fn send_timeout(&self, message: &[u8], timeout_ms: Option<u64>) -> Result<(), ()> {
+        let mut request = MqRequest {
+            buf_addr: message.as_ptr() as u64,
+            len: message.len() as u64,
+            timeout_ms: timeout_ms.unwrap_or(NO_TIMEOUT),
+            sender: 0,
+        };
+        self.fd
+            .ioctl(MQ_SEND, &mut request as *mut MqRequest as usize)
+            .map(|_| ())
+    }
+
+    /// Receive the oldest message, waiting for one to arrive. A message longer
+    /// than `buf` is cut short.
+    pub fn receive(&self, buf: &mut [u8]) -> Result<Received, ()> {
+        self.receive_timeout(buf, None)
+    }
+
+    /// Receive the oldest message, waiting at most `timeout_ms` milliseconds.
+    pub fn receive_timeout(
+        &self,
+        buf: &mut [u8],
+        timeout_ms: Option<u64>,
+    ) -> Result<Received, ()> {
+        let mut request = MqRequest {
+            buf_addr: buf.as_mut_ptr() as u64,
+            len: buf.len() as u64,
+            timeout_ms: timeout_ms.unwrap_or(NO_TIMEOUT),
+            sender: 0,
+        };
+        let len = self
+            .fd
+            .ioctl(MQ_RECEIVE, &mut request as *mut MqRequest as usize)?;
+        Ok(Received {
+            len: len - 1,
+            sender: request.sender,
+        })
+    }
+}
+
+impl Drop for MessageQueue {
+    fn drop(&mut self) {
+        self.fd.close();
+    }
+}
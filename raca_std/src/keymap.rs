@@ -0,0 +1,53 @@
+use crate::fs::{FileDescriptor, OpenMode};
+
+const KBD_GET_LAYOUT: usize = 0x4b80;
+const KBD_SET_LAYOUT: usize = 0x4b81;
+const KBD_GET_SCANCODE_SET: usize = 0x4b82;
+const KBD_SET_SCANCODE_SET: usize = 0x4b83;
+
+/// The keyboard layouts the kernel knows, by name and description. The
+/// names are also what `keymap=` on the kernel command line takes.
+pub const LAYOUTS: [(&str, &str); 4] = [
+    ("us", "US, 104 keys"),
+    ("uk", "UK, 105 keys"),
+    ("de", "German, 105 keys"),
+    ("fr", "French AZERTY"),
+];
+
+/// The keyboard belongs to the console terminal, whatever stdout is.
+fn terminal() -> Result<FileDescriptor, ()> {
+    FileDescriptor::open("/dev/terminal", OpenMode::Read)
+}
+
+fn keyboard_ioctl(cmd: usize, arg: usize) -> Result<usize, ()> {
+    let mut fd = terminal()?;
+    let result = fd.ioctl(cmd, arg);
+    fd.close();
+    result
+}
+
+/// Name of the layout the keyboard is read with.
+pub fn layout() -> Result<&'static str, ()> {
+    let num = keyboard_ioctl(KBD_GET_LAYOUT, 0)? - 1;
+    LAYOUTS.get(num).map(|(name, _)| *name).ok_or(())
+}
+
+/// Switch the keyboard to the layout called `name`, one of [`LAYOUTS`].
+pub fn set_layout(name: &str) -> Result<(), ()> {
+    let num = LAYOUTS
+        .iter()
+        .position(|(known, _)| *known == name)
+        .ok_or(())?;
+    keyboard_ioctl(KBD_SET_LAYOUT, num).map(|_| ())
+}
+
+/// The scancode set the keyboard is decoded with, 1 or 2.
+pub fn scancode_set() -> Result<usize, ()> {
+    keyboard_ioctl(KBD_GET_SCANCODE_SET, 0)
+}
+
+/// Decode the keyboard with scancode set 1 or 2. Set 2 is only right for
+/// controllers that don't translate to set 1.
+pub fn set_scancode_set(set: usize) -> Result<(), ()> {
+    keyboard_ioctl(KBD_SET_SCANCODE_SET, set).map(|_| ())
+}
@@ -0,0 +1,97 @@
+use alloc::{string::String, vec};
+
+use crate::fs::{FileDescriptor, OpenMode};
+
+const KMSG_GET_LEVEL: usize = 1;
+const KMSG_SET_LEVEL: usize = 2;
+const KMSG_CLEAR: usize = 3;
+
+/// Least severe kernel messages that are still recorded
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LogLevel {
+    Off = 0,
+    Error = 1,
+    Warn = 2,
+    Info = 3,
+    Debug = 4,
+    Trace = 5,
+}
+
+impl LogLevel {
+    pub fn from_index(index: usize) -> Option<Self> {
+        [
+            Self::Off,
+            Self::Error,
+            Self::Warn,
+            Self::Info,
+            Self::Debug,
+            Self::Trace,
+        ]
+        .get(index)
+        .copied()
+    }
+
+    /// Parse a level given by name or number.
+    pub fn from_name(name: &str) -> Option<Self> {
+        if let Ok(index) = name.parse() {
+            return Self::from_index(index);
+        }
+        (0..=5)
+            .filter_map(Self::from_index)
+            .find(|level| level.name() == name)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+            Self::Trace => "trace",
+        }
+    }
+}
+
+fn with_kmsg<T>(f: impl FnOnce(&FileDescriptor) -> Result<T, ()>) -> Result<T, ()> {
+    let mut fd = FileDescriptor::open("/dev/kmsg", OpenMode::Write)?;
+    let ret = f(&fd);
+    fd.close();
+    ret
+}
+
+/// Everything in the kernel log buffer, one message per line.
+pub fn read_all() -> Result<String, ()> {
+    with_kmsg(|fd| {
+        let mut buf = vec![0; fd.size()];
+        let len = fd.read(&mut buf);
+        buf.truncate(len);
+        String::from_utf8(buf).map_err(|_| ())
+    })
+}
+
+/// Add a line to the kernel log.
+pub fn write(text: &str) -> Result<(), ()> {
+    with_kmsg(|fd| {
+        if fd.write(text.as_bytes()) == 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    })
+}
+
+pub fn level() -> Result<LogLevel, ()> {
+    with_kmsg(|fd| {
+        let level = fd.ioctl(KMSG_GET_LEVEL, 0)?;
+        LogLevel::from_index(level - 1).ok_or(())
+    })
+}
+
+pub fn set_level(level: LogLevel) -> Result<(), ()> {
+    with_kmsg(|fd| fd.ioctl(KMSG_SET_LEVEL, level as usize).map(|_| ()))
+}
+
+pub fn clear() -> Result<(), ()> {
+    with_kmsg(|fd| fd.ioctl(KMSG_CLEAR, 0).map(|_| ()))
+}
@@ -4,10 +4,19 @@
 pub extern crate alloc;
 
 pub mod debug;
+pub mod disk;
+pub mod fb;
 pub mod fs;
 pub mod io;
+pub mod ipc;
+pub mod keymap;
+pub mod kmsg;
 pub mod mm;
+pub mod shm;
+pub mod signal;
+pub mod socket;
 pub mod task;
+pub mod tty;
 
 use core::panic::PanicInfo;
 pub use core::*;
@@ -19,6 +28,9 @@ fn panic(info: &PanicInfo) -> ! {
     loop {}
 }
 
+/// Make a syscall. Handlers of signals that came in meanwhile run before it
+/// returns, see [`signal`]. The stack pointer goes along as the sixth
+/// argument, so the kernel can find the return address.
 #[naked]
 extern "C" fn syscall(
     _id: u64,
@@ -36,6 +48,7 @@ extern "C" fn syscall(
             "mov rdx, rcx",
             "mov r10, r8",
             "mov r8, r9",
+            "mov r9, rsp",
             "syscall",
             "ret",
             options(noreturn)
@@ -0,0 +1,86 @@
+use crate::fs::{munmap, FileDescriptor};
+
+const SHM_SET_SIZE: usize = 1;
+
+/// A named piece of memory under `/dev/shm`. Every process that maps the
+/// same name sees the same bytes.
+pub struct SharedMemory {
+    fd: FileDescriptor,
+}
+
+impl SharedMemory {
+    fn shm_open(name: &str, size: usize) -> Result<Self, ()> {
+        const SHM_OPEN_SYSCALL_ID: u64 = 44;
+        let fd = crate::syscall(
+            SHM_OPEN_SYSCALL_ID,
+            name.as_ptr() as usize,
+            name.len(),
+            size,
+            0,
+            0,
+        );
+        if fd == 0 {
+            Err(())
+        } else {
+            Ok(Self {
+                fd: FileDescriptor::from_raw(fd),
+            })
+        }
+    }
+
+    /// Open an existing object.
+    pub fn open(name: &str) -> Result<Self, ()> {
+        Self::shm_open(name, 0)
+    }
+
+    /// Open the object, creating it with `size` zeroed bytes first if needed.
+    pub fn create(name: &str, size: usize) -> Result<Self, ()> {
+        if size == 0 {
+            return Err(());
+        }
+        Self::shm_open(name, size)
+    }
+
+    /// Remove the name. The memory lives on while it is open or mapped.
+    pub fn unlink(name: &str) -> Result<(), ()> {
+        const SHM_UNLINK_SYSCALL_ID: u64 = 45;
+        let code = crate::syscall(
+            SHM_UNLINK_SYSCALL_ID,
+            name.as_ptr() as usize,
+            name.len(),
+            0,
+            0,
+            0,
+        );
+        if code == 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn size(&self) -> usize {
+        self.fd.size()
+    }
+
+    /// Grow the object to `size` bytes. It can't shrink.
+    pub fn set_size(&self, size: usize) -> Result<(), ()> {
+        self.fd.ioctl(SHM_SET_SIZE, size).map(|_| ())
+    }
+
+    /// Map the whole object with the `PROT_*` flags in `prot`. The mapping
+    /// stays valid after the object is dropped, until [`unmap`](Self::unmap).
+    pub fn map(&self, prot: usize) -> Result<&'static mut [u8], ()> {
+        self.fd.mmap_with(0, self.size(), prot)
+    }
+
+    pub fn unmap(buf: &mut [u8]) -> Result<(), ()> {
+        munmap(buf)
+    }
+}
+
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        self.fd.close();
+    }
+}
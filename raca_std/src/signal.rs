@@ -0,0 +1,202 @@
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const SIGHUP: usize = 1;
+pub const SIGINT: usize = 2;
+pub const SIGQUIT: usize = 3;
+pub const SIGKILL: usize = 9;
+pub const SIGUSR1: usize = 10;
+pub const SIGUSR2: usize = 12;
+pub const SIGPIPE: usize = 13;
+pub const SIGTERM: usize = 15;
+pub const SIGCHLD: usize = 17;
+pub const SIGCONT: usize = 18;
+pub const SIGSTOP: usize = 19;
+pub const SIGTSTP: usize = 20;
+pub const SIGTTIN: usize = 21;
+pub const SIGTTOU: usize = 22;
+pub const SIGWINCH: usize = 28;
+
+/// Signal numbers are below this
+pub const NSIG: usize = 32;
+
+/// What a read, write or other call that waits returns when a signal
+/// handler had to run before it got anything done
+pub const INTERRUPTED: usize = usize::MAX - 1;
+
+const NAMES: [(usize, &str); 15] = [
+    (SIGHUP, "HUP"),
+    (SIGINT, "INT"),
+    (SIGQUIT, "QUIT"),
+    (SIGKILL, "KILL"),
+    (SIGUSR1, "USR1"),
+    (SIGUSR2, "USR2"),
+    (SIGPIPE, "PIPE"),
+    (SIGTERM, "TERM"),
+    (SIGCHLD, "CHLD"),
+    (SIGCONT, "CONT"),
+    (SIGSTOP, "STOP"),
+    (SIGTSTP, "TSTP"),
+    (SIGTTIN, "TTIN"),
+    (SIGTTOU, "TTOU"),
+    (SIGWINCH, "WINCH"),
+];
+
+/// Name of `sig` without the `SIG` prefix, like `INT`.
+pub fn name(sig: usize) -> Option<&'static str> {
+    NAMES
+        .iter()
+        .find(|(num, _)| *num == sig)
+        .map(|(_, name)| *name)
+}
+
+/// Parse a signal given as a number, a name like `INT` or `SIGINT`.
+pub fn from_name(name: &str) -> Option<usize> {
+    if let Ok(sig) = name.parse::<usize>() {
+        return (sig < NSIG).then_some(sig);
+    }
+    let name = name.strip_prefix("SIG").unwrap_or(name);
+    NAMES
+        .iter()
+        .find(|(_, known)| known.eq_ignore_ascii_case(name))
+        .map(|(num, _)| *num)
+}
+
+/// The set holding just `sig`, for [`block`] and friends.
+pub const fn sigmask(sig: usize) -> u64 {
+    1 << sig
+}
+
+pub type Handler = fn(sig: usize);
+
+pub enum Action {
+    /// What the kernel does without a handler: terminate, stop or nothing,
+    /// depending on the signal
+    Default,
+    Ignore,
+    Handle(Handler),
+}
+
+const SIG_DFL: usize = 0;
+const SIG_IGN: usize = 1;
+const SIG_HANDLE: usize = 2;
+
+const SIG_BLOCK: usize = 0;
+const SIG_UNBLOCK: usize = 1;
+const SIG_SETMASK: usize = 2;
+
+const SIGRETURN_SYSCALL_ID: u64 = 50;
+
+/// Handler addresses by signal number
+static HANDLERS: [AtomicUsize; NSIG] = [const { AtomicUsize::new(0) }; NSIG];
+
+/// What the kernel pushes onto the stack when a syscall returns into a
+/// handler. Laid out the way the kernel does.
+#[repr(C)]
+#[allow(dead_code)] // the trampoline reads the rest
+struct SignalFrame {
+    sig: u64,
+    blocked: u64,
+    /// What the syscall returned
+    ret: u64,
+    /// Where the syscall would have returned to, and the stack pointer then
+    rip: u64,
+    rsp: u64,
+}
+
+/// Send `sig` to process `pid`. Signal 0 only checks that it exists.
+pub fn kill(pid: usize, sig: usize) -> Result<(), ()> {
+    const KILL_SYSCALL_ID: u64 = 46;
+    if crate::syscall(KILL_SYSCALL_ID, pid, sig, 0, 0, 0) == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+/// Choose what happens when `sig` arrives. `SIGKILL` and `SIGSTOP` can't be
+/// changed.
+///
+/// Handlers run when the process next returns from a syscall, with `sig`
+/// blocked until the handler returns. A call that was waiting, such as a read
+/// from a pipe, gives up with [`INTERRUPTED`] to let the handler run.
+pub fn set_action(sig: usize, action: Action) -> Result<(), ()> {
+    const SIGACTION_SYSCALL_ID: u64 = 47;
+    if sig == 0 || sig >= NSIG {
+        return Err(());
+    }
+
+    let kind = match action {
+        Action::Default => SIG_DFL,
+        Action::Ignore => SIG_IGN,
+        Action::Handle(handler) => {
+            HANDLERS[sig].store(handler as usize, Ordering::SeqCst);
+            SIG_HANDLE
+        }
+    };
+    let trampoline = trampoline as usize;
+    if crate::syscall(SIGACTION_SYSCALL_ID, sig, kind, trampoline, 0, 0) == 0 {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
+fn sigprocmask(how: usize, set: u64) -> Result<u64, ()> {
+    const SIGPROCMASK_SYSCALL_ID: u64 = 48;
+    match crate::syscall(SIGPROCMASK_SYSCALL_ID, how, set as usize, 0, 0, 0) {
+        0 => Err(()),
+        old => Ok(old as u64 - 1),
+    }
+}
+
+/// Hold back the signals in `set` until they are unblocked. Returns the set
+/// blocked before.
+pub fn block(set: u64) -> Result<u64, ()> {
+    sigprocmask(SIG_BLOCK, set)
+}
+
+pub fn unblock(set: u64) -> Result<u64, ()> {
+    sigprocmask(SIG_UNBLOCK, set)
+}
+
+pub fn set_blocked(set: u64) -> Result<u64, ()> {
+    sigprocmask(SIG_SETMASK, set)
+}
+
+/// Where the kernel makes a syscall return to when a handler has to run,
+/// with the address of a [`SignalFrame`] as the return value. The handler
+/// runs on the stack below the frame, then everything the syscall stub
+/// would have returned with is taken from the frame.
+#[naked]
+extern "C" fn trampoline() {
+    unsafe {
+        core::arch::asm!(
+            "mov rsp, rax",
+            "mov rdi, rax",
+            "call {run_handler}",
+            "mov rax, [rsp + {ret}]",
+            "mov rcx, [rsp + {rip}]",
+            "mov rsp, [rsp + {rsp}]",
+            "jmp rcx",
+            run_handler = sym run_handler,
+            ret = const core::mem::offset_of!(SignalFrame, ret),
+            rip = const core::mem::offset_of!(SignalFrame, rip),
+            rsp = const core::mem::offset_of!(SignalFrame, rsp),
+            options(noreturn)
+        )
+    }
+}
+
+extern "C" fn run_handler(frame: &SignalFrame) {
+    let sig = frame.sig as usize;
+    let handler = HANDLERS
+        .get(sig)
+        .map_or(0, |handler| handler.load(Ordering::SeqCst));
+    if handler != 0 {
+        let handler: Handler = unsafe { core::mem::transmute(handler) };
+        handler(sig);
+    }
+    // lets `sig` through again
+    let frame = frame as *const SignalFrame as usize;
+    crate::syscall(SIGRETURN_SYSCALL_ID, frame, 0, 0, 0, 0);
+}
@@ -0,0 +1,137 @@
+use crate::fs::FileDescriptor;
+
+const SOCKET_SYSCALL_ID: u64 = 37;
+
+fn socket() -> Result<FileDescriptor, ()> {
+    let fd = crate::syscall(SOCKET_SYSCALL_ID, 0, 0, 0, 0, 0);
+    if fd == 0 {
+        Err(())
+    } else {
+        Ok(FileDescriptor::from_raw(fd))
+    }
+}
+
+/// A socket bound to a path that clients connect to. The socket file is
+/// removed once the listener is dropped.
+pub struct Listener {
+    fd: FileDescriptor,
+}
+
+impl Listener {
+    /// Create the socket file `path` and accept connections on it, with up
+    /// to `backlog` of them waiting at a time.
+    pub fn bind(path: &str, backlog: usize) -> Result<Self, ()> {
+        const BIND_SYSCALL_ID: u64 = 38;
+        const LISTEN_SYSCALL_ID: u64 = 39;
+
+        // dropped on error, which closes the socket again
+        let listener = Self { fd: socket()? };
+        let fd = listener.fd.0;
+        let code = crate::syscall(
+            BIND_SYSCALL_ID,
+            fd,
+            path.as_ptr() as usize,
+            path.len(),
+            0,
+            0,
+        );
+        if code == 0 {
+            return Err(());
+        }
+        if crate::syscall(LISTEN_SYSCALL_ID, fd, backlog, 0, 0, 0) == 0 {
+            return Err(());
+        }
+        Ok(listener)
+    }
+
+    /// Wait for the next client.
+    pub fn accept(&self) -> Result<Stream, ()> {
+        const ACCEPT_SYSCALL_ID: u64 = 40;
+        let fd = crate::syscall(ACCEPT_SYSCALL_ID, self.fd.0, 0, 0, 0, 0);
+        if fd == 0 {
+            Err(())
+        } else {
+            Ok(Stream {
+                fd: FileDescriptor::from_raw(fd),
+            })
+        }
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.fd.close();
+    }
+}
+
+/// A connection between two processes. Bytes written on one side are read
+/// on the other, in both directions.
+pub struct Stream {
+    fd: FileDescriptor,
+}
+
+impl Stream {
+    /// Connect to the listener bound at `path`.
+    pub fn connect(path: &str) -> Result<Self, ()> {
+        const CONNECT_SYSCALL_ID: u64 = 41;
+        let stream = Self { fd: socket()? };
+        let code = crate::syscall(
+            CONNECT_SYSCALL_ID,
+            stream.fd.0,
+            path.as_ptr() as usize,
+            path.len(),
+            0,
+            0,
+        );
+        if code == 0 {
+            Err(())
+        } else {
+            Ok(stream)
+        }
+    }
+
+    /// The descriptor of the connection, e.g. to use it as the stdin or
+    /// stdout of a new process.
+    pub fn fd(&self) -> FileDescriptor {
+        self.fd
+    }
+
+    /// Read what the other side wrote. Returns 0 once it closed the
+    /// connection and everything was read.
+    pub fn read(&self, buf: &mut [u8]) -> usize {
+        self.fd.read(buf)
+    }
+
+    /// Returns 0 if the other side closed the connection.
+    pub fn write(&self, buf: &[u8]) -> usize {
+        self.fd.write(buf)
+    }
+
+    /// Give the other side a copy of `fd`. Passed files don't travel with the
+    /// bytes, so tell the other side with a message that one is waiting.
+    pub fn send_fd(&self, fd: FileDescriptor) -> Result<(), ()> {
+        const SEND_FD_SYSCALL_ID: u64 = 42;
+        if crate::syscall(SEND_FD_SYSCALL_ID, self.fd.0, fd.0, 0, 0, 0) == 0 {
+            Err(())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Take the oldest file the other side sent. Fails if there is none.
+    pub fn receive_fd(&self) -> Result<FileDescriptor, ()> {
+        const RECEIVE_FD_SYSCALL_ID: u64 = 43;
+        let fd = crate::syscall(RECEIVE_FD_SYSCALL_ID, self.fd.0, 0, 0, 0, 0);
+        if fd == 0 {
+            Err(())
+        } else {
+            Ok(FileDescriptor::from_raw(fd))
+        }
+    }
+}
+
+impl Drop for Stream {
+    fn drop(&mut self) {
+        self.fd.close();
+    }
+}
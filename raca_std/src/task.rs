@@ -7,6 +7,8 @@ pub struct Process {
     name_len: usize,
     stdin: usize,
     stdout: usize,
+    cmdline_addr: usize,
+    cmdline_len: usize,
 }
 
 impl Process {
@@ -18,9 +20,18 @@ impl Process {
             name_len: name.len(),
             stdin,
             stdout,
+            cmdline_addr: 0,
+            cmdline_len: 0,
         }
     }
 
+    /// Record the command line the process was started with, shown in
+    /// `/proc/<pid>/cmdline`. `cmdline` has to live until [`run`](Self::run).
+    pub fn set_cmdline(&mut self, cmdline: &str) {
+        self.cmdline_addr = cmdline.as_ptr() as usize;
+        self.cmdline_len = cmdline.len();
+    }
+
     pub fn run(&self) -> usize {
         const CREATE_PROCESS_SYSCALL_ID: u64 = 6;
         crate::syscall(
@@ -44,6 +55,22 @@ pub fn exit(code: usize) -> ! {
 }
 
 pub fn wait() -> usize {
+    match wait_status() {
+        WaitStatus::Exited(code) => code,
+        WaitStatus::Stopped(sig) => 128 + sig,
+    }
+}
+
+/// How a child came back to [`wait_status`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WaitStatus {
+    Exited(usize),
+    /// Stopped by the signal, it can be continued with `SIGCONT`
+    Stopped(usize),
+}
+
+/// Wait until a child exits or is stopped.
+pub fn wait_status() -> WaitStatus {
     start_wait_for_signal(1);
     while !has_signal(1) {
         //crate::print!("NO");
@@ -51,7 +78,12 @@ pub fn wait() -> usize {
     //crate::print!("YES");
     let signal = get_signal(1).unwrap();
     done_signal(signal.ty);
-    signal.data[0] as usize
+    let code = signal.data[0] as usize;
+    if signal.data[1] != 0 {
+        WaitStatus::Stopped(code - 128)
+    } else {
+        WaitStatus::Exited(code)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -0,0 +1,101 @@
+use crate::fs::FileDescriptor;
+
+const TCGETS: usize = 0x5401;
+const TCSETS: usize = 0x5402;
+const TIOCGPGRP: usize = 0x540f;
+const TIOCSPGRP: usize = 0x5410;
+const TIOCGWINSZ: usize = 0x5413;
+const TIOCSWINSZ: usize = 0x5414;
+
+/// `lflag`: the interrupt, quit and suspend characters raise signals
+pub const ISIG: u32 = 0o1;
+/// `lflag`: input is edited and handed out a line at a time
+pub const ICANON: u32 = 0o2;
+/// `lflag`: typed characters are echoed
+pub const ECHO: u32 = 0o10;
+/// `lflag`: erasing takes the character off the screen as well
+pub const ECHOE: u32 = 0o20;
+/// `lflag`: control characters, like the escape starting the sequence an
+/// arrow key sends, are echoed as `^X`
+pub const ECHOCTL: u32 = 0o1000;
+
+/// Index into `cc` of each special character, a 0 there turns it off
+pub const VINTR: usize = 0;
+pub const VQUIT: usize = 1;
+pub const VERASE: usize = 2;
+pub const VKILL: usize = 3;
+pub const VEOF: usize = 4;
+pub const VSUSP: usize = 5;
+pub const NCCS: usize = 8;
+
+/// How a terminal treats its input.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Termios {
+    pub lflag: u32,
+    pub cc: [u8; NCCS],
+}
+
+impl Termios {
+    /// Switch to raw mode: every key goes to the reader as it is pressed,
+    /// without echo or signals.
+    pub fn make_raw(&mut self) {
+        self.lflag &= !(ISIG | ICANON | ECHO | ECHOE | ECHOCTL);
+    }
+
+    /// Back to line editing with echo and signals, undoing [`make_raw`].
+    ///
+    /// [`make_raw`]: Self::make_raw
+    pub fn make_cooked(&mut self) {
+        self.lflag |= ISIG | ICANON | ECHO | ECHOE | ECHOCTL;
+    }
+}
+
+/// Size of a terminal in character cells, and in pixels where known. All
+/// zero if the terminal doesn't know, like a serial line nobody set it for.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct WindowSize {
+    pub rows: u16,
+    pub cols: u16,
+    pub x_pixels: u16,
+    pub y_pixels: u16,
+}
+
+/// The settings of the terminal behind `fd`.
+pub fn get_attr(fd: FileDescriptor) -> Result<Termios, ()> {
+    let mut termios = Termios::default();
+    fd.ioctl(TCGETS, &mut termios as *mut Termios as usize)?;
+    Ok(termios)
+}
+
+pub fn set_attr(fd: FileDescriptor, termios: &Termios) -> Result<(), ()> {
+    fd.ioctl(TCSETS, termios as *const Termios as usize)
+        .map(|_| ())
+}
+
+/// The process whose job gets the signals typed on the terminal, if any.
+pub fn foreground(fd: FileDescriptor) -> Result<Option<usize>, ()> {
+    let mut pid = 0u64;
+    fd.ioctl(TIOCGPGRP, &mut pid as *mut u64 as usize)?;
+    Ok((pid != 0).then_some(pid as usize))
+}
+
+/// Make process `pid` and the processes it starts the foreground job, or
+/// have none with `None`.
+pub fn set_foreground(fd: FileDescriptor, pid: Option<usize>) -> Result<(), ()> {
+    fd.ioctl(TIOCSPGRP, pid.unwrap_or(0)).map(|_| ())
+}
+
+pub fn window_size(fd: FileDescriptor) -> Result<WindowSize, ()> {
+    let mut size = WindowSize::default();
+    fd.ioctl(TIOCGWINSZ, &mut size as *mut WindowSize as usize)?;
+    Ok(size)
+}
+
+/// Tell the terminal its size, e.g. for a serial line. The foreground job
+/// gets `SIGWINCH` if it changed.
+pub fn set_window_size(fd: FileDescriptor, size: &WindowSize) -> Result<(), ()> {
+    fd.ioctl(TIOCSWINSZ, size as *const WindowSize as usize)
+        .map(|_| ())
+}